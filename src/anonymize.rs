@@ -0,0 +1,102 @@
+use crate::output::CalendarEvent;
+
+const TITLES: &[&str] = &[
+    "Team sync",
+    "1:1",
+    "Design review",
+    "Planning",
+    "Lunch",
+    "Customer call",
+    "Retro",
+    "Focus block",
+    "Interview",
+    "Standup",
+    "Budget review",
+    "Coffee chat",
+    "Demo",
+    "All hands",
+    "Gym",
+    "Dentist",
+];
+
+const LOCATIONS: &[&str] = &[
+    "Room A",
+    "Room B",
+    "Main office",
+    "Cafe",
+    "3rd floor",
+    "Library",
+];
+
+const NAMES: &[&str] = &[
+    "Alex Morgan",
+    "Sam Lee",
+    "Jordan Park",
+    "Robin Diaz",
+    "Casey Kim",
+    "Taylor Reed",
+];
+
+/// Swaps everything personal in `events` for made-up stand-ins, keeping
+/// times, calendars' colors, kinds and responses so a screenshot or bug
+/// report still shows the same layout. The same original always gets the
+/// same stand-in, so recurring events still look alike.
+pub fn events(events: &mut [CalendarEvent]) {
+    let mut calendars: Vec<String> = Vec::new();
+    for event in events {
+        event.id = format!("{:016x}", fnv(&event.id));
+        event.title = pick(TITLES, &event.title).to_string();
+        event.description = event.description.as_ref().map(|_| "Notes".to_string());
+        event.location = event
+            .location
+            .as_deref()
+            .map(|location| pick(LOCATIONS, location).to_string());
+        event.organizer = event
+            .organizer
+            .as_deref()
+            .map(|name| pick(NAMES, name).to_string());
+        event.meeting_url = event
+            .meeting_url
+            .as_deref()
+            .map(|url| format!("https://meet.example.com/{:010x}", fnv(url) >> 24));
+        event.html_link = event
+            .html_link
+            .as_ref()
+            .map(|_| format!("https://calendar.example.com/event/{}", event.id));
+        for value in event.properties.values_mut() {
+            *value = format!("{:016x}", fnv(value));
+        }
+        event.series = event
+            .series
+            .as_deref()
+            .map(|id| format!("{:016x}", fnv(id)));
+
+        // Numbered in order of appearance, like the calendars themselves.
+        let index = match calendars
+            .iter()
+            .position(|name| *name == event.calendar_name)
+        {
+            Some(index) => index,
+            None => {
+                calendars.push(event.calendar_name.clone());
+                calendars.len() - 1
+            }
+        };
+        event.calendar_name = format!("Calendar {}", index + 1);
+    }
+}
+
+fn pick<'a>(choices: &[&'a str], original: &str) -> &'a str {
+    choices[(fnv(original) % choices.len() as u64) as usize]
+}
+
+/// FNV-1a: stable across builds, so stand-ins don't change between
+/// versions.
+fn fnv(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
@@ -0,0 +1,479 @@
+use crate::config::NetworkConfig;
+use crate::error::{CalendarError, Result};
+use crate::http_trace;
+use crate::metrics::{self, ApiOutcome};
+#[cfg(feature = "compression")]
+use flate2::read::{GzDecoder, ZlibDecoder};
+use google_calendar3::hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::{Method, Request, StatusCode, header};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::pem::PemObject;
+#[cfg(feature = "compression")]
+use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+pub const API_BASE: &str = "https://www.googleapis.com/calendar/v3";
+/// Where event attachments are downloaded from.
+pub const DRIVE_API_BASE: &str = "https://www.googleapis.com/drive/v3";
+
+/// Points every request at another server, e.g. the mock Google API the
+/// integration tests replay fixtures from.
+const API_BASE_ENV: &str = "CALLUX_API_BASE";
+
+// Google only compresses responses for user agents mentioning gzip.
+#[cfg(feature = "compression")]
+const USER_AGENT: &str = concat!("callux/", env!("CARGO_PKG_VERSION"), " (gzip)");
+#[cfg(not(feature = "compression"))]
+const USER_AGENT: &str = concat!("callux/", env!("CARGO_PKG_VERSION"));
+
+pub struct ApiResponse {
+    pub status: StatusCode,
+    pub etag: Option<String>,
+    pub body: Bytes,
+}
+
+impl ApiResponse {
+    pub fn not_modified(&self) -> bool {
+        self.status == StatusCode::NOT_MODIFIED
+    }
+}
+
+/// Minimal Calendar API transport. The generated `CalendarHub` call builders
+/// can't set request headers, which conditional requests need.
+pub struct ApiClient {
+    http: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+    retry_attempts: u32,
+    retry_base_delay: Duration,
+    max_retry_after: Duration,
+    throttle: Throttle,
+}
+
+/// A failed attempt; `CalendarError::is_retryable` decides whether to try
+/// again.
+struct AttemptError {
+    error: CalendarError,
+    retry_after: Option<Duration>,
+}
+
+impl From<CalendarError> for AttemptError {
+    fn from(error: CalendarError) -> Self {
+        Self {
+            error,
+            retry_after: None,
+        }
+    }
+}
+
+/// Spaces requests out and holds everyone back after Google asks us to slow
+/// down, shared by all calendars fetched through one client.
+struct Throttle {
+    next_allowed: Mutex<Instant>,
+    spacing: Duration,
+}
+
+impl Throttle {
+    fn new(spacing: Duration) -> Self {
+        Self {
+            next_allowed: Mutex::new(Instant::now()),
+            spacing,
+        }
+    }
+
+    async fn wait(&self) {
+        let mut next_allowed = self.next_allowed.lock().await;
+        let now = Instant::now();
+        if *next_allowed > now {
+            tokio::time::sleep_until(*next_allowed).await;
+        }
+        *next_allowed = Instant::now() + self.spacing;
+    }
+
+    async fn defer(&self, delay: Duration) {
+        let mut next_allowed = self.next_allowed.lock().await;
+        let until = Instant::now() + delay;
+        if until > *next_allowed {
+            *next_allowed = until;
+        }
+    }
+}
+
+impl ApiClient {
+    pub fn new(network: &NetworkConfig) -> Result<Self> {
+        let https = match &network.ca_bundle {
+            Some(path) => {
+                HttpsConnectorBuilder::new().with_tls_config(tls_config_with_bundle(path)?)
+            }
+            None => HttpsConnectorBuilder::new()
+                .with_native_roots()
+                .map_err(|e| CalendarError::io("Failed to build HTTPS connector", e))?,
+        }
+        .https_or_http()
+        .enable_http1()
+        // Offered over ALPN; Google negotiates h2, so the calendars fetched
+        // one after another, and the daemon's later refreshes, reuse one
+        // connection instead of a handshake each.
+        .enable_http2()
+        .build();
+
+        let http = Client::builder(hyper_util::rt::TokioExecutor::new()).build(https);
+
+        Ok(Self {
+            http,
+            retry_attempts: network.retry_attempts,
+            retry_base_delay: Duration::from_millis(network.retry_base_delay_ms),
+            max_retry_after: Duration::from_secs(network.max_retry_after_seconds),
+            throttle: Throttle::new(Duration::from_millis(network.request_spacing_ms)),
+        })
+    }
+
+    /// GETs `url`, retrying 429/5xx responses and connection failures with
+    /// jittered exponential backoff, or after the server's Retry-After.
+    pub async fn get(
+        &self,
+        url: &str,
+        token: &str,
+        if_none_match: Option<&str>,
+    ) -> Result<ApiResponse> {
+        self.send(Method::GET, url, token, if_none_match, None)
+            .await
+    }
+
+    /// POSTs a JSON body to `url`. Only rate-limited attempts are retried:
+    /// after a 5xx or a dropped connection Google may already have created
+    /// the event, and sending it again would create a second one.
+    pub async fn post_json(
+        &self,
+        url: &str,
+        token: &str,
+        body: &serde_json::Value,
+    ) -> Result<ApiResponse> {
+        let body = Bytes::from(body.to_string());
+        self.send(Method::POST, url, token, None, Some(body)).await
+    }
+
+    /// PATCHes `url` with a JSON body, with the same retry behaviour as `get`.
+    pub async fn patch_json(
+        &self,
+        url: &str,
+        token: &str,
+        body: &serde_json::Value,
+    ) -> Result<ApiResponse> {
+        let body = Bytes::from(body.to_string());
+        self.send(Method::PATCH, url, token, None, Some(body)).await
+    }
+
+    /// DELETEs `url`, with the same retry behaviour as `get`.
+    pub async fn delete(&self, url: &str, token: &str) -> Result<ApiResponse> {
+        self.send(Method::DELETE, url, token, None, None).await
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        token: &str,
+        if_none_match: Option<&str>,
+        body: Option<Bytes>,
+    ) -> Result<ApiResponse> {
+        let idempotent = method != Method::POST;
+        let mut attempt = 0;
+        loop {
+            self.throttle.wait().await;
+
+            http_trace::request(&method, url, attempt);
+            let started = Instant::now();
+            let result = self
+                .send_once(method.clone(), url, token, if_none_match, body.clone())
+                .await;
+            match &result {
+                Ok(response) => http_trace::response(
+                    response.status,
+                    response.body.len(),
+                    response.etag.as_deref(),
+                    started.elapsed(),
+                ),
+                Err(failed) => {
+                    http_trace::failure(&failed.error, failed.retry_after, started.elapsed())
+                }
+            }
+            metrics::record_api(
+                match &result {
+                    Ok(response) if response.not_modified() => ApiOutcome::NotModified,
+                    Ok(_) => ApiOutcome::Ok,
+                    Err(failed) if matches!(failed.error, CalendarError::RateLimited(_)) => {
+                        ApiOutcome::RateLimited
+                    }
+                    Err(_) => ApiOutcome::Error,
+                },
+                started.elapsed(),
+            );
+
+            match result {
+                Ok(response) => return Ok(response),
+                Err(failed)
+                    if failed.error.is_retryable()
+                        && (idempotent
+                            || matches!(failed.error, CalendarError::RateLimited(_)))
+                        && attempt < self.retry_attempts =>
+                {
+                    match failed.retry_after {
+                        Some(delay) if delay > self.max_retry_after => return Err(failed.error),
+                        Some(delay) => self.throttle.defer(delay).await,
+                        None => {
+                            tokio::time::sleep(backoff_delay(self.retry_base_delay, attempt)).await
+                        }
+                    }
+                    attempt += 1;
+                }
+                Err(failed) => return Err(failed.error),
+            }
+        }
+    }
+
+    async fn send_once(
+        &self,
+        method: Method,
+        url: &str,
+        token: &str,
+        if_none_match: Option<&str>,
+        body: Option<Bytes>,
+    ) -> std::result::Result<ApiResponse, AttemptError> {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(url)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::USER_AGENT, USER_AGENT);
+
+        #[cfg(feature = "compression")]
+        {
+            builder = builder.header(header::ACCEPT_ENCODING, "gzip, deflate");
+        }
+
+        if let Some(etag) = if_none_match {
+            builder = builder.header(header::IF_NONE_MATCH, etag);
+        }
+
+        if body.is_some() {
+            builder = builder.header(header::CONTENT_TYPE, "application/json");
+        }
+
+        let request = builder
+            .body(Full::new(body.unwrap_or_default()))
+            .map_err(|e| CalendarError::api_from("Failed to build request", e))?;
+
+        let response = self
+            .http
+            .request(request)
+            .await
+            .map_err(|e| CalendarError::api_from("Request failed", e))?;
+
+        let status = response.status();
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let encoding = response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_ascii_lowercase());
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+
+        let raw = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| CalendarError::api_from("Failed to read response", e))?
+            .to_bytes();
+        let body = decode_body(raw, encoding.as_deref())?;
+
+        if !status.is_success() && status != StatusCode::NOT_MODIFIED {
+            let text = String::from_utf8_lossy(&body);
+
+            if is_rate_limited(status, &text) {
+                return Err(AttemptError {
+                    error: CalendarError::RateLimited(rate_limit_message(
+                        status,
+                        error_reason(&text).as_deref(),
+                    )),
+                    retry_after,
+                });
+            }
+
+            if status == StatusCode::NOT_FOUND || status == StatusCode::GONE {
+                return Err(CalendarError::NotFound(format!("{} returned {}", url, status)).into());
+            }
+
+            if status == StatusCode::FORBIDDEN {
+                return Err(CalendarError::PermissionDenied(
+                    error_message(&text).unwrap_or_else(|| format!("{} returned {}", url, status)),
+                )
+                .into());
+            }
+
+            let message = format!("{} returned {}: {}", url, status, text);
+            return Err(CalendarError::http(status.as_u16(), message).into());
+        }
+
+        Ok(ApiResponse { status, etag, body })
+    }
+}
+
+/// Native roots plus the certificates in `path`, for corporate MITM proxies
+/// and servers signed by a private CA.
+fn tls_config_with_bundle(path: &str) -> Result<rustls::ClientConfig> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs().certs {
+        let _ = roots.add(cert);
+    }
+
+    let expanded = crate::config::expand_home(path);
+    let certs = CertificateDer::pem_file_iter(&expanded).map_err(|e| {
+        CalendarError::config_from(format!("Failed to read CA bundle {}", expanded), e)
+    })?;
+
+    let mut added = 0;
+    for cert in certs {
+        let cert = cert.map_err(|e| {
+            CalendarError::config_from(format!("Invalid certificate in {}", expanded), e)
+        })?;
+        roots.add(cert).map_err(|e| {
+            CalendarError::config_from(format!("Rejected certificate in {}", expanded), e)
+        })?;
+        added += 1;
+    }
+
+    if added == 0 {
+        return Err(CalendarError::config(format!(
+            "No certificates found in CA bundle {}",
+            expanded
+        )));
+    }
+
+    Ok(rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+fn is_rate_limited(status: StatusCode, body: &str) -> bool {
+    // Calendar reports quota exhaustion as 403 with a rateLimitExceeded,
+    // userRateLimitExceeded, quotaExceeded or dailyLimitExceeded reason as
+    // well as plain 429s.
+    let body = body.to_ascii_lowercase();
+    status == StatusCode::TOO_MANY_REQUESTS
+        || (status == StatusCode::FORBIDDEN
+            && ["ratelimitexceeded", "quotaexceeded", "dailylimitexceeded"]
+                .iter()
+                .any(|reason| body.contains(reason)))
+}
+
+/// What ran out, in words, since each reason calls for a different fix.
+/// `quota::advice` relies on the daily quota being called that.
+fn rate_limit_message(status: StatusCode, reason: Option<&str>) -> String {
+    match reason {
+        Some(reason @ "userRateLimitExceeded") => format!(
+            "Too many requests for your account in a short time ({})",
+            reason
+        ),
+        Some(reason @ "rateLimitExceeded") => format!(
+            "Too many requests with these credentials in a short time ({})",
+            reason
+        ),
+        Some(reason @ ("quotaExceeded" | "dailyLimitExceeded")) => format!(
+            "The daily quota of the Google Cloud project behind the credentials is used up ({})",
+            reason
+        ),
+        _ => format!("Google asked callux to slow down ({})", status),
+    }
+}
+
+/// The machine-readable `error.errors[0].reason` of a Google API error
+/// body, e.g. `userRateLimitExceeded`.
+fn error_reason(body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    value["error"]["errors"][0]["reason"]
+        .as_str()
+        .map(str::to_string)
+}
+
+/// The human-readable `error.message` of a Google API error body.
+fn error_message(body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    value["error"]["message"].as_str().map(str::to_string)
+}
+
+fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    let ceiling = base.saturating_mul(2u32.saturating_pow(attempt));
+    // Cheap jitter without pulling in an RNG: spread retries over the upper
+    // half of the window using the clock's sub-second noise.
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    let fraction = 0.5 + (nanos % 1000) as f64 / 2000.0;
+    ceiling.mul_f64(fraction)
+}
+
+#[cfg(not(feature = "compression"))]
+fn decode_body(raw: Bytes, _encoding: Option<&str>) -> Result<Bytes> {
+    Ok(raw)
+}
+
+#[cfg(feature = "compression")]
+fn decode_body(raw: Bytes, encoding: Option<&str>) -> Result<Bytes> {
+    let mut decoded = Vec::new();
+    let result = match encoding {
+        Some("gzip") => GzDecoder::new(raw.as_ref()).read_to_end(&mut decoded),
+        Some("deflate") => ZlibDecoder::new(raw.as_ref()).read_to_end(&mut decoded),
+        _ => return Ok(raw),
+    };
+
+    result.map_err(|e| CalendarError::io("Failed to decompress response", e))?;
+    Ok(Bytes::from(decoded))
+}
+
+pub fn encode_component(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for b in input.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+pub fn build_url(path: &str, query: &[(&str, String)]) -> String {
+    build_url_on(API_BASE, path, query)
+}
+
+/// `build_url` for the Drive API.
+pub fn build_drive_url(path: &str, query: &[(&str, String)]) -> String {
+    build_url_on(DRIVE_API_BASE, path, query)
+}
+
+fn build_url_on(default_base: &str, path: &str, query: &[(&str, String)]) -> String {
+    let base = std::env::var(API_BASE_ENV).unwrap_or_else(|_| default_base.to_string());
+    let mut url = format!("{}{}", base.trim_end_matches('/'), path);
+    for (i, (key, value)) in query.iter().enumerate() {
+        url.push(if i == 0 { '?' } else { '&' });
+        url.push_str(key);
+        url.push('=');
+        url.push_str(&encode_component(value));
+    }
+    url
+}
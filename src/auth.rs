@@ -1,11 +1,29 @@
 use crate::config::Config;
 use crate::error::{CalendarError, Result};
+use crate::lock::{self, FileLock};
+use crate::loopback::LoopbackFlowDelegate;
+use crate::metrics;
+use crate::security;
 use google_calendar3::hyper_rustls::HttpsConnector;
 use hyper_util::client::legacy::connect::HttpConnector;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use yup_oauth2::authenticator::Authenticator;
 use yup_oauth2::{ApplicationSecret, InstalledFlowAuthenticator, InstalledFlowReturnMethod};
 
+pub const SCOPES: &[&str] = &[
+    "https://www.googleapis.com/auth/calendar.readonly",
+    "https://www.googleapis.com/auth/calendar.events.readonly",
+];
+
+/// Asked for only by commands that change events, so read-only use never
+/// needs the broader grant. The first such command opens the consent page.
+pub const WRITE_SCOPES: &[&str] = &["https://www.googleapis.com/auth/calendar.events"];
+
+/// Asked for only by `callux attachments --download`, to read the Drive
+/// files attached to events.
+pub const DRIVE_SCOPES: &[&str] = &["https://www.googleapis.com/auth/drive.readonly"];
+
 pub struct AuthManager {
     config: Config,
 }
@@ -20,120 +38,138 @@ impl AuthManager {
         let token_cache_path = self.config.expand_path(&self.config.auth.token_cache_path);
 
         if !Path::new(&credentials_path).exists() {
-            return Err(CalendarError::AuthenticationFailed(format!(
+            return Err(CalendarError::config(format!(
                 "Credentials file not found at: {}",
                 credentials_path
             )));
         }
 
+        let allow_insecure = self.config.auth.allow_insecure_permissions;
+        security::check_private_file(Path::new(&credentials_path), allow_insecure)?;
+        security::check_private_file(Path::new(&token_cache_path), allow_insecure)?;
+
         let secret = self.load_application_secret(&credentials_path)?;
 
         if let Some(parent) = Path::new(&token_cache_path).parent() {
-            std::fs::create_dir_all(parent).map_err(|e| {
-                CalendarError::ConfigError(format!("Failed to create token cache directory: {}", e))
-            })?;
+            security::create_private_dir(parent)
+                .map_err(|e| CalendarError::io("Failed to create token cache directory", e))?;
         }
 
+        let delegate =
+            LoopbackFlowDelegate::bind(Duration::from_secs(self.config.auth.timeout_seconds))
+                .map_err(|e| {
+                    CalendarError::auth_from("Failed to bind loopback redirect listener", e)
+                })?;
+
         let authenticator =
-            InstalledFlowAuthenticator::builder(secret, InstalledFlowReturnMethod::HTTPRedirect)
+            InstalledFlowAuthenticator::builder(secret, InstalledFlowReturnMethod::Interactive)
                 .persist_tokens_to_disk(&token_cache_path)
+                .flow_delegate(Box::new(delegate))
                 .build()
                 .await
-                .map_err(|e| {
-                    CalendarError::AuthenticationFailed(format!(
-                        "Failed to create authenticator: {}",
-                        e
-                    ))
-                })?;
+                .map_err(|e| CalendarError::auth_from("Failed to create authenticator", e))?;
 
         Ok(authenticator)
     }
 
+    /// Held while signing in or refreshing, so concurrent invocations take
+    /// turns: the first one opens the browser and the rest find its token.
+    /// Take it before `get_authenticator`, which reads the token file once.
+    pub async fn lock(&self) -> Result<FileLock> {
+        let token_cache_path =
+            PathBuf::from(self.config.expand_path(&self.config.auth.token_cache_path));
+        if let Some(parent) = token_cache_path.parent() {
+            security::create_private_dir(parent)
+                .map_err(|e| CalendarError::io("Failed to create token cache directory", e))?;
+        }
+
+        FileLock::acquire_async(lock::lock_path(&token_cache_path))
+            .await
+            .map_err(|e| CalendarError::io("Failed to lock the token cache", e))
+    }
+
     pub async fn get_token(&self) -> Result<String> {
+        let _lock = self.lock().await?;
         let authenticator = self.get_authenticator().await?;
+        let token = self.token_from(&authenticator).await?;
 
-        let scopes = &[
-            "https://www.googleapis.com/auth/calendar.readonly",
-            "https://www.googleapis.com/auth/calendar.events.readonly",
-        ];
+        let token_cache_path = self.config.expand_path(&self.config.auth.token_cache_path);
+        if Path::new(&token_cache_path).exists() {
+            security::restrict_permissions(Path::new(&token_cache_path))?;
+        }
 
-        let token = authenticator.token(scopes).await.map_err(|e| {
-            CalendarError::AuthenticationFailed(format!("Failed to get token: {}", e))
-        })?;
+        Ok(token)
+    }
+
+    pub async fn token_from(
+        &self,
+        authenticator: &Authenticator<HttpsConnector<HttpConnector>>,
+    ) -> Result<String> {
+        self.token_for(authenticator, SCOPES).await
+    }
+
+    pub async fn token_for(
+        &self,
+        authenticator: &Authenticator<HttpsConnector<HttpConnector>>,
+        scopes: &[&str],
+    ) -> Result<String> {
+        let token = authenticator
+            .token(scopes)
+            .await
+            .map_err(|e| CalendarError::auth_from("Failed to get token", e))?;
+        if let Some(expires) = token.expiration_time() {
+            metrics::record_token_expiry(expires.unix_timestamp());
+        }
 
         Ok(token.token().unwrap_or_default().to_string())
     }
 
     fn load_application_secret(&self, path: &str) -> Result<ApplicationSecret> {
-        let content = std::fs::read_to_string(path).map_err(|e| {
-            CalendarError::ConfigError(format!("Failed to read credentials file: {}", e))
-        })?;
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| CalendarError::io("Failed to read credentials file", e))?;
 
-        let credentials: serde_json::Value = serde_json::from_str(&content).map_err(|e| {
-            CalendarError::ParseError(format!("Invalid JSON in credentials file: {}", e))
-        })?;
+        parse_application_secret(&content)
+    }
 
-        let installed = credentials
-            .get("installed")
-            .or_else(|| credentials.get("web"))
-            .ok_or_else(|| {
-                CalendarError::ParseError(
-                    "Missing 'installed' or 'web' section in credentials".to_string(),
-                )
-            })?;
-
-        let client_id = installed
-            .get("client_id")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| {
-                CalendarError::ParseError("Missing 'client_id' in credentials".to_string())
-            })?;
-
-        let client_secret = installed
-            .get("client_secret")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| {
-                CalendarError::ParseError("Missing 'client_secret' in credentials".to_string())
-            })?;
-
-        let auth_uri = installed
-            .get("auth_uri")
-            .and_then(|v| v.as_str())
-            .unwrap_or("https://accounts.google.com/o/oauth2/auth");
-
-        let token_uri = installed
-            .get("token_uri")
-            .and_then(|v| v.as_str())
-            .unwrap_or("https://oauth2.googleapis.com/token");
-
-        let redirect_uris = installed
-            .get("redirect_uris")
-            .and_then(|v| v.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|v| v.as_str())
-            .map(|s| vec![s.to_string()])
-            .unwrap_or_else(|| vec!["http://localhost:8080".to_string()]);
-
-        Ok(ApplicationSecret {
-            client_id: client_id.to_string(),
-            client_secret: client_secret.to_string(),
-            auth_uri: auth_uri.to_string(),
-            token_uri: token_uri.to_string(),
-            auth_provider_x509_cert_url: None,
-            client_x509_cert_url: None,
-            redirect_uris,
-            project_id: None,
-            client_email: None,
-        })
+    /// Reads the credentials file the way signing in would, without
+    /// contacting Google.
+    pub fn check_credentials(&self) -> Result<()> {
+        let credentials_path = self.config.expand_path(&self.config.auth.credentials_path);
+        if !Path::new(&credentials_path).exists() {
+            return Err(CalendarError::config(format!(
+                "Credentials file not found at: {}",
+                credentials_path
+            )));
+        }
+        security::check_private_file(
+            Path::new(&credentials_path),
+            self.config.auth.allow_insecure_permissions,
+        )?;
+        self.load_application_secret(&credentials_path).map(|_| ())
+    }
+
+    /// Checks `content` is a usable OAuth client JSON and stores it at the
+    /// configured credentials path, readable only by the owner.
+    pub fn install_credentials(&self, content: &str) -> Result<()> {
+        parse_application_secret(content)?;
+
+        let credentials_path = self.config.expand_path(&self.config.auth.credentials_path);
+        if let Some(parent) = Path::new(&credentials_path).parent() {
+            security::create_private_dir(parent)
+                .map_err(|e| CalendarError::io("Failed to create credentials directory", e))?;
+        }
+
+        std::fs::write(&credentials_path, content)
+            .map_err(|e| CalendarError::io("Failed to write credentials", e))?;
+        security::restrict_permissions(Path::new(&credentials_path))
     }
 
     pub fn create_sample_credentials(&self) -> Result<()> {
         let credentials_path = self.config.expand_path(&self.config.auth.credentials_path);
 
         if let Some(parent) = Path::new(&credentials_path).parent() {
-            std::fs::create_dir_all(parent).map_err(|e| {
-                CalendarError::ConfigError(format!("Failed to create credentials directory: {}", e))
-            })?;
+            security::create_private_dir(parent)
+                .map_err(|e| CalendarError::io("Failed to create credentials directory", e))?;
         }
 
         let sample_credentials = serde_json::json!({
@@ -150,10 +186,61 @@ impl AuthManager {
             &credentials_path,
             serde_json::to_string_pretty(&sample_credentials).unwrap(),
         )
-        .map_err(|e| {
-            CalendarError::ConfigError(format!("Failed to write sample credentials: {}", e))
-        })?;
+        .map_err(|e| CalendarError::io("Failed to write sample credentials", e))?;
+        security::restrict_permissions(Path::new(&credentials_path))?;
 
         Ok(())
     }
 }
+
+fn parse_application_secret(content: &str) -> Result<ApplicationSecret> {
+    let credentials: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| CalendarError::parse_from("Invalid JSON in credentials file", e))?;
+
+    let installed = credentials
+        .get("installed")
+        .or_else(|| credentials.get("web"))
+        .ok_or_else(|| {
+            CalendarError::parse("Missing 'installed' or 'web' section in credentials")
+        })?;
+
+    let client_id = installed
+        .get("client_id")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| CalendarError::parse("Missing 'client_id' in credentials"))?;
+
+    let client_secret = installed
+        .get("client_secret")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| CalendarError::parse("Missing 'client_secret' in credentials"))?;
+
+    let auth_uri = installed
+        .get("auth_uri")
+        .and_then(|v| v.as_str())
+        .unwrap_or("https://accounts.google.com/o/oauth2/auth");
+
+    let token_uri = installed
+        .get("token_uri")
+        .and_then(|v| v.as_str())
+        .unwrap_or("https://oauth2.googleapis.com/token");
+
+    let redirect_uris = installed
+        .get("redirect_uris")
+        .and_then(|v| v.as_array())
+        .and_then(|arr| arr.first())
+        .and_then(|v| v.as_str())
+        .map(|s| vec![s.to_string()])
+        .unwrap_or_else(|| vec!["http://localhost:8080".to_string()]);
+
+    Ok(ApplicationSecret {
+        client_id: client_id.to_string(),
+        client_secret: client_secret.to_string(),
+        auth_uri: auth_uri.to_string(),
+        token_uri: token_uri.to_string(),
+        auth_provider_x509_cert_url: None,
+        client_x509_cert_url: None,
+        redirect_uris,
+        project_id: None,
+        client_email: None,
+    })
+}
@@ -0,0 +1,119 @@
+use crate::output::{CalendarEvent, EventKind};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
+
+/// A stretch of time with at least one event in it; what the event was
+/// isn't kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Busy {
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+}
+
+/// The times `events` make you busy, merged where they overlap or touch.
+/// Like Google's free/busy, events marked free, invitations you declined and
+/// all-day events other than out-of-office don't count.
+pub fn busy_blocks(events: &[CalendarEvent]) -> Vec<Busy> {
+    let mut blocks: Vec<Busy> = events
+        .iter()
+        .filter(|event| counts_as_busy(event))
+        .map(|event| Busy {
+            start: event.start_time,
+            end: event.end_time,
+        })
+        .collect();
+    blocks.sort_by_key(|block| block.start);
+
+    let mut merged: Vec<Busy> = Vec::new();
+    for block in blocks {
+        match merged.last_mut() {
+            Some(last) if block.start <= last.end => last.end = last.end.max(block.end),
+            _ => merged.push(block),
+        }
+    }
+    merged
+}
+
+fn counts_as_busy(event: &CalendarEvent) -> bool {
+    if event.cancelled || event.free || event.response.as_deref() == Some("declined") {
+        return false;
+    }
+    match event.kind {
+        EventKind::WorkingLocation => false,
+        EventKind::OutOfOffice => true,
+        _ => !event.all_day,
+    }
+}
+
+/// An iCalendar file with one untitled opaque event per block, which any
+/// calendar app can import or overlay.
+pub fn to_ics(blocks: &[Busy]) -> String {
+    let stamp = ics_time(Utc::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:-//callux//callux {}//EN", env!("CARGO_PKG_VERSION")),
+        "METHOD:PUBLISH".to_string(),
+    ];
+    for block in blocks {
+        let start = block.start.with_timezone(&Utc);
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:busy-{}@callux", start.timestamp()),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART:{}", ics_time(start)),
+            format!("DTEND:{}", ics_time(block.end.with_timezone(&Utc))),
+            "SUMMARY:Busy".to_string(),
+            "TRANSP:OPAQUE".to_string(),
+            "CLASS:PRIVATE".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    // RFC 5545 wants CRLF line endings.
+    let mut out = lines.join("\r\n");
+    out.push_str("\r\n");
+    out
+}
+
+fn ics_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// One line per day from `first` through `last`, listing the busy times, to
+/// paste into an email or chat.
+pub fn to_text(blocks: &[Busy], first: NaiveDate, last: NaiveDate, zone_name: &str) -> String {
+    let mut out = format!("Busy times ({}):\n", zone_name);
+    let mut date = first;
+    while date <= last {
+        // Blocks running past midnight show up on each day they cover.
+        let ranges: Vec<String> = blocks
+            .iter()
+            .filter(|block| {
+                let last_day = (block.end - Duration::seconds(1)).date_naive();
+                block.start.date_naive() <= date && date <= last_day
+            })
+            .map(|block| {
+                let start = if block.start.date_naive() == date {
+                    block.start.format("%H:%M").to_string()
+                } else {
+                    "00:00".to_string()
+                };
+                let end = if block.end.date_naive() == date {
+                    block.end.format("%H:%M").to_string()
+                } else {
+                    "24:00".to_string()
+                };
+                format!("{}–{}", start, end)
+            })
+            .collect();
+        let busy = if ranges.is_empty() {
+            "free".to_string()
+        } else {
+            ranges.join(", ")
+        };
+        out.push_str(&format!("{}: {}\n", date.format("%a %b %d"), busy));
+        date += Duration::days(1);
+    }
+    out.trim_end().to_string()
+}
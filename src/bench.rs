@@ -0,0 +1,80 @@
+use crate::calendar::CalendarClient;
+use crate::config::Config;
+use crate::error::Result;
+use colored::*;
+use std::time::{Duration, Instant};
+
+pub struct BenchResult {
+    pub label: &'static str,
+    pub samples: Vec<Duration>,
+}
+
+impl BenchResult {
+    fn percentile(&self, p: f64) -> Duration {
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let rank = ((p / 100.0) * (sorted.len() as f64 - 1.0)).round() as usize;
+        sorted.get(rank).copied().unwrap_or_default()
+    }
+}
+
+/// Times `iterations` agenda fetches served from the cache (a fresh client
+/// each time, as Waybar would see it) and straight from the API.
+pub async fn run(
+    config: &Config,
+    iterations: usize,
+    days_ahead: i64,
+    skip_uncached: bool,
+) -> Result<Vec<BenchResult>> {
+    let mut results = Vec::new();
+
+    if !skip_uncached {
+        let client = CalendarClient::new(config.clone());
+        let mut samples = Vec::with_capacity(iterations);
+        for _ in 0..iterations {
+            let start = Instant::now();
+            client.prefetch(days_ahead).await?;
+            samples.push(start.elapsed());
+        }
+        results.push(BenchResult {
+            label: "uncached",
+            samples,
+        });
+    } else {
+        CalendarClient::new(config.clone())
+            .get_events(days_ahead, None, false)
+            .await?;
+    }
+
+    let mut samples = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        CalendarClient::new(config.clone())
+            .get_events(days_ahead, None, false)
+            .await?;
+        samples.push(start.elapsed());
+    }
+    results.push(BenchResult {
+        label: "cached",
+        samples,
+    });
+
+    Ok(results)
+}
+
+pub fn print(results: &[BenchResult]) {
+    println!("{}", "Benchmark:".bright_blue().bold());
+    for result in results {
+        println!(
+            "  {:<10} p50 {:>8.2}ms  p95 {:>8.2}ms  ({} runs)",
+            result.label,
+            ms(result.percentile(50.0)),
+            ms(result.percentile(95.0)),
+            result.samples.len()
+        );
+    }
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
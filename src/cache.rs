@@ -1,33 +1,282 @@
 use crate::config::CacheConfig;
+use crate::error::{CalendarError, Result};
+use crate::metrics;
 use crate::output::CalendarEvent;
 use moka::future::Cache;
-use std::time::Duration;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(not(feature = "persistent-cache"))]
+mod disabled;
+#[cfg(feature = "persistent-cache")]
+mod sqlite;
+
+#[cfg(not(feature = "persistent-cache"))]
+pub use disabled::PersistentStore;
+#[cfg(feature = "persistent-cache")]
+pub use sqlite::PersistentStore;
 
 pub struct EventCache {
     cache: Cache<String, Vec<CalendarEvent>>,
+    store: Option<PersistentStore>,
+    ttl_seconds: u64,
+    today_ttl_seconds: Option<u64>,
+    /// Drawn once per process so concurrent callers (one per bar/monitor)
+    /// expire the same entry at different moments.
+    jitter_seconds: u64,
+    fingerprint: String,
 }
 
 impl EventCache {
-    pub fn new(config: &CacheConfig) -> Self {
+    /// `fingerprint` identifies the configuration the cached results were
+    /// produced under and starts every key; results from other
+    /// configurations are dropped once they expire.
+    pub fn new(config: &CacheConfig, fingerprint: String) -> Self {
         let cache = Cache::builder()
             .max_capacity(config.max_entries)
             .time_to_live(Duration::from_secs(config.ttl_seconds))
             .build();
 
-        Self { cache }
+        let store = if config.persistent && cfg!(feature = "persistent-cache") {
+            match PersistentStore::open_configured(config) {
+                Ok(store) => Some(store),
+                Err(e) => {
+                    eprintln!("Warning: Persistent cache unavailable: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let longest_ttl = config
+            .ttl_seconds
+            .max(config.today_ttl_seconds.unwrap_or(0));
+        if let Some(store) = &store {
+            if let Err(e) = store.prune_other_fingerprints(&fingerprint, longest_ttl) {
+                eprintln!("Warning: Failed to prune old cache entries: {}", e);
+            }
+        }
+
+        Self {
+            cache,
+            store,
+            ttl_seconds: config.ttl_seconds,
+            today_ttl_seconds: config.today_ttl_seconds,
+            jitter_seconds: random_below(config.ttl_jitter_seconds + 1),
+            fingerprint,
+        }
     }
 
     pub async fn get(&self, key: &str) -> Option<Vec<CalendarEvent>> {
-        self.cache.get(key).await
+        let events = self.lookup(key).await;
+        metrics::record_cache(events.is_some());
+        if let Some(store) = &self.store {
+            store.bump_counter(if events.is_some() { "hits" } else { "misses" });
+        }
+        events
+    }
+
+    async fn lookup(&self, key: &str) -> Option<Vec<CalendarEvent>> {
+        if let Some(events) = self.cache.get(key).await {
+            return Some(events);
+        }
+
+        let entry = self.store.as_ref()?.get(key)?;
+        if entry.age_seconds() >= self.ttl_for(key) {
+            return None;
+        }
+
+        self.cache
+            .insert(key.to_string(), entry.events.clone())
+            .await;
+        Some(entry.events)
+    }
+
+    /// Short ranges ("today") can use their own TTL; either way the
+    /// per-process jitter shortens it a little.
+    fn ttl_for(&self, key: &str) -> u64 {
+        let (_, days) = split_key(key);
+        let base = match self.today_ttl_seconds {
+            Some(ttl) if days <= 1 => ttl,
+            _ => self.ttl_seconds,
+        };
+        base.saturating_sub(self.jitter_seconds)
+    }
+
+    /// Returns true if the caller should start a background refresh of
+    /// `key`; at most one claim is handed out per `window_seconds`.
+    pub fn claim_revalidation(&self, key: &str, window_seconds: u64) -> bool {
+        match &self.store {
+            Some(store) => store
+                .claim(&format!("revalidate:{}", key), window_seconds)
+                .unwrap_or_else(|e| {
+                    eprintln!("Warning: Failed to claim a background refresh: {}", e);
+                    false
+                }),
+            None => false,
+        }
+    }
+
+    /// Drops every cached result and stored ETag, in memory and on disk.
+    pub async fn clear(&self) -> Result<()> {
+        self.cache.invalidate_all();
+        if let Some(store) = &self.store {
+            store
+                .clear()
+                .map_err(|e| CalendarError::cache_from("Failed to clear the cache", e))?;
+        }
+        Ok(())
+    }
+
+    /// Forgets every cached result after callux changed an event, so the
+    /// next agenda is fetched fresh.
+    pub async fn invalidate(&self) -> Result<()> {
+        self.cache.invalidate_all();
+        if let Some(store) = &self.store {
+            store
+                .invalidate_results()
+                .map_err(|e| CalendarError::cache_from("Failed to invalidate results", e))?;
+        }
+        Ok(())
+    }
+
+    /// Removes persisted results older than the TTL, returning how many went.
+    pub fn prune(&self) -> Result<usize> {
+        let longest_ttl = self.ttl_seconds.max(self.today_ttl_seconds.unwrap_or(0));
+        match &self.store {
+            Some(store) => store
+                .prune(longest_ttl)
+                .map_err(|e| CalendarError::cache_from("Failed to prune the cache", e)),
+            None => Ok(0),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            memory_entries: self.cache.entry_count(),
+            ttl_seconds: self.ttl_seconds,
+            persistent: self.store.as_ref().and_then(|store| store.stats().ok()),
+        }
+    }
+
+    /// Returns the last persisted result for `key` regardless of its age.
+    pub fn get_stale(&self, key: &str) -> Option<PersistedEntry> {
+        self.store.as_ref()?.get(key)
     }
 
     pub async fn set(&self, key: String, events: Vec<CalendarEvent>) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.set(&key, &events) {
+                eprintln!("Warning: Failed to write persistent cache: {}", e);
+            }
+        }
+
         self.cache.insert(key, events).await;
     }
 
-    pub fn generate_key(&self, calendar_ids: &[String], days_ahead: i64) -> String {
-        let mut key = calendar_ids.join(",");
-        key.push_str(&format!(":{}", days_ahead));
-        key
+    /// Whether results (and snapshots) survive between invocations.
+    pub fn has_store(&self) -> bool {
+        self.store.is_some()
     }
+
+    pub fn snapshot(&self, name: &str) -> Option<PersistedEntry> {
+        self.store.as_ref()?.get_snapshot(name)
+    }
+
+    pub fn set_snapshot(&self, name: &str, events: &[CalendarEvent]) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.set_snapshot(name, events) {
+                eprintln!("Warning: Failed to store snapshot: {}", e);
+            }
+        }
+    }
+
+    /// Returns the stored ETag and raw response body for an API resource.
+    pub fn get_conditional(&self, resource: &str) -> Option<(String, String)> {
+        self.store.as_ref()?.get_etag(resource)
+    }
+
+    pub fn set_conditional(&self, resource: &str, etag: &str, payload: &str) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.set_etag(resource, etag, payload) {
+                eprintln!("Warning: Failed to store ETag: {}", e);
+            }
+        }
+    }
+
+    pub fn generate_key(&self, calendar_ids: &[String], lookahead: &str) -> String {
+        format!(
+            "{}/{}:{}",
+            self.fingerprint,
+            calendar_ids.join(","),
+            lookahead
+        )
+    }
+}
+
+pub struct CacheStats {
+    pub memory_entries: u64,
+    pub ttl_seconds: u64,
+    pub persistent: Option<PersistentStats>,
+}
+
+pub struct PersistentStats {
+    pub path: PathBuf,
+    pub file_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: Vec<EntryStats>,
+    pub etag_entries: u64,
+}
+
+pub struct EntryStats {
+    pub key: String,
+    pub events: usize,
+    pub bytes: usize,
+    pub age_seconds: u64,
+}
+
+pub struct PersistedEntry {
+    pub events: Vec<CalendarEvent>,
+    pub fetched_at: u64,
+}
+
+impl PersistedEntry {
+    pub fn age_seconds(&self) -> u64 {
+        unix_now().saturating_sub(self.fetched_at)
+    }
+}
+
+/// The calendar ids and days ahead of a cache key. Hours count as the days
+/// they span, and `--until` ranges as short ones; `--past` doesn't count.
+fn split_key(key: &str) -> (&str, i64) {
+    let key = key.split_once('/').map_or(key, |(_, rest)| rest);
+    let Some((ids, range)) = key.rsplit_once(':') else {
+        return (key, 0);
+    };
+    let ahead = range.split_once('+').map_or(range, |(ahead, _)| ahead);
+    let days = match ahead.strip_suffix('h') {
+        Some(hours) => hours.parse::<i64>().map_or(0, |hours| (hours + 23) / 24),
+        None => ahead.parse().unwrap_or_default(),
+    };
+    (ids, days)
+}
+
+fn random_below(bound: u64) -> u64 {
+    if bound <= 1 {
+        return 0;
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or_default();
+    (nanos ^ (std::process::id() as u64).rotate_left(17)) % bound
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
 }
@@ -0,0 +1,74 @@
+use super::{PersistedEntry, PersistentStats};
+use crate::config::CacheConfig;
+use crate::output::CalendarEvent;
+use std::convert::Infallible;
+
+/// Stand-in for builds without the `persistent-cache` feature. It can never
+/// be constructed, so every method is unreachable.
+pub enum PersistentStore {}
+
+impl PersistentStore {
+    pub fn path_for(_config: &CacheConfig) -> Option<std::path::PathBuf> {
+        None
+    }
+
+    pub fn open_configured(_config: &CacheConfig) -> Result<Self, &'static str> {
+        Err("callux was built without the persistent-cache feature")
+    }
+
+    pub fn get(&self, _key: &str) -> Option<PersistedEntry> {
+        match *self {}
+    }
+
+    pub fn set(&self, _key: &str, _events: &[CalendarEvent]) -> Result<(), Infallible> {
+        match *self {}
+    }
+
+    pub fn get_snapshot(&self, _name: &str) -> Option<PersistedEntry> {
+        match *self {}
+    }
+
+    pub fn set_snapshot(&self, _name: &str, _events: &[CalendarEvent]) -> Result<(), Infallible> {
+        match *self {}
+    }
+
+    pub fn get_etag(&self, _resource: &str) -> Option<(String, String)> {
+        match *self {}
+    }
+
+    pub fn set_etag(&self, _resource: &str, _etag: &str, _payload: &str) -> Result<(), Infallible> {
+        match *self {}
+    }
+
+    pub fn prune_other_fingerprints(
+        &self,
+        _fingerprint: &str,
+        _ttl_seconds: u64,
+    ) -> Result<usize, Infallible> {
+        match *self {}
+    }
+
+    pub fn invalidate_results(&self) -> Result<(), Infallible> {
+        match *self {}
+    }
+
+    pub fn clear(&self) -> Result<(), Infallible> {
+        match *self {}
+    }
+
+    pub fn prune(&self, _ttl_seconds: u64) -> Result<usize, Infallible> {
+        match *self {}
+    }
+
+    pub fn claim(&self, _name: &str, _window_seconds: u64) -> Result<bool, Infallible> {
+        match *self {}
+    }
+
+    pub fn bump_counter(&self, _name: &str) {
+        match *self {}
+    }
+
+    pub fn stats(&self) -> Result<PersistentStats, Infallible> {
+        match *self {}
+    }
+}
@@ -0,0 +1,400 @@
+use super::{EntryStats, PersistedEntry, PersistentStats, split_key, unix_now};
+use crate::config::{CacheConfig, Config, expand_home};
+use crate::lock::{self, FileLock};
+use crate::output::CalendarEvent;
+use rusqlite::{Connection, OptionalExtension, params};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How long a statement waits while another process holds SQLite's own
+/// lock (a reader, or a writer between `write_lock` and commit) before
+/// failing.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Hit and miss counts are written once this many are pending (and when the
+/// store is dropped), not on every read.
+const COUNTER_FLUSH_EVERY: u64 = 32;
+
+/// SQLite-backed store that survives between invocations, so a bar polling
+/// callux every few seconds doesn't hit the API each time.
+pub struct PersistentStore {
+    conn: Mutex<Connection>,
+    path: PathBuf,
+    /// Counter increments not written yet.
+    pending: Mutex<HashMap<String, u64>>,
+}
+
+impl PersistentStore {
+    /// `cache.path` if set, otherwise a file under the XDG cache directory.
+    /// Each profile gets its own database; they would otherwise keep
+    /// invalidating each other's entries through the config fingerprint.
+    pub fn path_for(config: &CacheConfig) -> Option<PathBuf> {
+        if let Some(path) = &config.path {
+            return Some(PathBuf::from(expand_home(path)));
+        }
+
+        let file = match Config::profile() {
+            Some(profile) => format!("cache-{}.db", profile),
+            None => "cache.db".to_string(),
+        };
+        dirs::cache_dir().map(|dir| dir.join("callux").join(file))
+    }
+
+    pub fn open_configured(config: &CacheConfig) -> rusqlite::Result<Self> {
+        let path = Self::path_for(config).ok_or_else(|| {
+            rusqlite::Error::InvalidPath(PathBuf::from("<no XDG cache directory>"))
+        })?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|_| rusqlite::Error::InvalidPath(parent.to_path_buf()))?;
+        }
+
+        Self::open(&path)
+    }
+
+    pub fn open(path: &std::path::Path) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS event_cache (
+                cache_key    TEXT PRIMARY KEY,
+                calendar_ids TEXT NOT NULL,
+                range_days   INTEGER NOT NULL,
+                fetched_at   INTEGER NOT NULL,
+                payload      TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS etags (
+                resource     TEXT PRIMARY KEY,
+                etag         TEXT NOT NULL,
+                payload      TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS counters (
+                name         TEXT PRIMARY KEY,
+                value        INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS meta (
+                name         TEXT PRIMARY KEY,
+                value        TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS snapshots (
+                name         TEXT PRIMARY KEY,
+                fetched_at   INTEGER NOT NULL,
+                payload      TEXT NOT NULL
+            );",
+        )?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            path: path.to_path_buf(),
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Taken around every write, so processes refreshing at the same moment
+    /// take turns instead of failing on SQLite's own lock.
+    fn write_lock(&self) -> rusqlite::Result<FileLock> {
+        let path = lock::lock_path(&self.path);
+        FileLock::acquire(&path).map_err(|_| rusqlite::Error::InvalidPath(path))
+    }
+
+    pub fn get(&self, key: &str) -> Option<PersistedEntry> {
+        let conn = self.conn.lock().ok()?;
+        let row: Option<(String, i64)> = conn
+            .query_row(
+                "SELECT payload, fetched_at FROM event_cache WHERE cache_key = ?1",
+                params![key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .ok()?;
+
+        let (payload, fetched_at) = row?;
+        let events = serde_json::from_str(&payload).ok()?;
+
+        Some(PersistedEntry {
+            events,
+            fetched_at: fetched_at as u64,
+        })
+    }
+
+    pub fn set(&self, key: &str, events: &[CalendarEvent]) -> rusqlite::Result<()> {
+        let _lock = self.write_lock()?;
+        let payload = serde_json::to_string(events)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let (calendar_ids, range_days) = split_key(key);
+
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|_| rusqlite::Error::InvalidQuery)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO event_cache
+                (cache_key, calendar_ids, range_days, fetched_at, payload)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![key, calendar_ids, range_days, unix_now() as i64, payload],
+        )?;
+
+        Ok(())
+    }
+
+    /// A named copy of an agenda kept apart from the cache entries, which
+    /// every fetch overwrites.
+    pub fn get_snapshot(&self, name: &str) -> Option<PersistedEntry> {
+        let conn = self.conn.lock().ok()?;
+        let (payload, fetched_at): (String, i64) = conn
+            .query_row(
+                "SELECT payload, fetched_at FROM snapshots WHERE name = ?1",
+                params![name],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .ok()??;
+
+        Some(PersistedEntry {
+            events: serde_json::from_str(&payload).ok()?,
+            fetched_at: fetched_at as u64,
+        })
+    }
+
+    pub fn set_snapshot(&self, name: &str, events: &[CalendarEvent]) -> rusqlite::Result<()> {
+        let _lock = self.write_lock()?;
+        let payload = serde_json::to_string(events)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|_| rusqlite::Error::InvalidQuery)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO snapshots (name, fetched_at, payload) VALUES (?1, ?2, ?3)",
+            params![name, unix_now() as i64, payload],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_etag(&self, resource: &str) -> Option<(String, String)> {
+        let conn = self.conn.lock().ok()?;
+        conn.query_row(
+            "SELECT etag, payload FROM etags WHERE resource = ?1",
+            params![resource],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .ok()?
+    }
+
+    pub fn set_etag(&self, resource: &str, etag: &str, payload: &str) -> rusqlite::Result<()> {
+        let _lock = self.write_lock()?;
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|_| rusqlite::Error::InvalidQuery)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO etags (resource, etag, payload) VALUES (?1, ?2, ?3)",
+            params![resource, etag, payload],
+        )?;
+
+        Ok(())
+    }
+
+    /// Drops results stored under another configuration fingerprint once
+    /// they are older than `ttl_seconds`. Keys start with the fingerprint, so
+    /// runs with one-off flags keep entries of their own next to the plain
+    /// agenda's instead of wiping them; snapshots and ETags stay either way.
+    pub fn prune_other_fingerprints(
+        &self,
+        fingerprint: &str,
+        ttl_seconds: u64,
+    ) -> rusqlite::Result<usize> {
+        let cutoff = unix_now().saturating_sub(ttl_seconds) as i64;
+        let prefix = format!("{}/", fingerprint);
+        let query = "FROM event_cache WHERE substr(cache_key, 1, length(?1)) != ?1
+                     AND fetched_at <= ?2";
+
+        // Checked first so the common case doesn't take the write lock.
+        let stale: bool = {
+            let conn = self
+                .conn
+                .lock()
+                .map_err(|_| rusqlite::Error::InvalidQuery)?;
+            conn.query_row(
+                &format!("SELECT EXISTS(SELECT 1 {})", query),
+                params![prefix, cutoff],
+                |row| row.get(0),
+            )?
+        };
+        if !stale {
+            return Ok(0);
+        }
+
+        let _lock = self.write_lock()?;
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|_| rusqlite::Error::InvalidQuery)?;
+        conn.execute(&format!("DELETE {}", query), params![prefix, cutoff])
+    }
+
+    /// Drops cached results but keeps ETags and snapshots, which stay
+    /// valid after an event changes.
+    pub fn invalidate_results(&self) -> rusqlite::Result<()> {
+        let _lock = self.write_lock()?;
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|_| rusqlite::Error::InvalidQuery)?;
+        conn.execute("DELETE FROM event_cache", []).map(|_| ())
+    }
+
+    pub fn clear(&self) -> rusqlite::Result<()> {
+        let _lock = self.write_lock()?;
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|_| rusqlite::Error::InvalidQuery)?;
+        conn.execute_batch(
+            "BEGIN;
+             DELETE FROM event_cache; DELETE FROM etags; DELETE FROM snapshots;
+             COMMIT;",
+        )
+    }
+
+    pub fn prune(&self, ttl_seconds: u64) -> rusqlite::Result<usize> {
+        let _lock = self.write_lock()?;
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|_| rusqlite::Error::InvalidQuery)?;
+        let cutoff = unix_now().saturating_sub(ttl_seconds) as i64;
+        conn.execute(
+            "DELETE FROM event_cache WHERE fetched_at <= ?1",
+            params![cutoff],
+        )
+    }
+
+    pub fn claim(&self, name: &str, window_seconds: u64) -> rusqlite::Result<bool> {
+        let _lock = self.write_lock()?;
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|_| rusqlite::Error::InvalidQuery)?;
+        let now = unix_now() as i64;
+        let cutoff = now - window_seconds as i64;
+        conn.execute(
+            "INSERT INTO counters (name, value) VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE SET value = ?2 WHERE value <= ?3",
+            params![name, now, cutoff],
+        )
+        .map(|changed| changed > 0)
+    }
+
+    pub fn bump_counter(&self, name: &str) {
+        let due = match self.pending.lock() {
+            Ok(mut pending) => {
+                *pending.entry(name.to_string()).or_default() += 1;
+                pending.values().sum::<u64>() >= COUNTER_FLUSH_EVERY
+            }
+            Err(_) => return,
+        };
+        if due {
+            self.flush_counters();
+        }
+    }
+
+    /// Writes pending counter increments in one transaction.
+    fn flush_counters(&self) {
+        let pending = match self.pending.lock() {
+            Ok(mut pending) => std::mem::take(&mut *pending),
+            Err(_) => return,
+        };
+        if pending.is_empty() {
+            return;
+        }
+
+        let write = || -> rusqlite::Result<()> {
+            let _lock = self.write_lock()?;
+            let mut conn = self
+                .conn
+                .lock()
+                .map_err(|_| rusqlite::Error::InvalidQuery)?;
+            let tx = conn.transaction()?;
+            for (name, count) in &pending {
+                tx.execute(
+                    "INSERT INTO counters (name, value) VALUES (?1, ?2)
+                     ON CONFLICT(name) DO UPDATE SET value = value + ?2",
+                    params![name, *count as i64],
+                )?;
+            }
+            tx.commit()
+        };
+        if let Err(e) = write() {
+            eprintln!("Warning: Failed to update cache counters: {}", e);
+        }
+    }
+
+    pub fn stats(&self) -> rusqlite::Result<PersistentStats> {
+        self.flush_counters();
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|_| rusqlite::Error::InvalidQuery)?;
+
+        let counter = |name: &str| -> rusqlite::Result<u64> {
+            let value: Option<i64> = conn
+                .query_row(
+                    "SELECT value FROM counters WHERE name = ?1",
+                    params![name],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(value.unwrap_or_default() as u64)
+        };
+        let hits = counter("hits")?;
+        let misses = counter("misses")?;
+
+        let now = unix_now();
+        let mut stmt = conn.prepare(
+            "SELECT cache_key, fetched_at, payload FROM event_cache ORDER BY fetched_at DESC",
+        )?;
+        let entries = stmt
+            .query_map([], |row| {
+                let key: String = row.get(0)?;
+                let fetched_at: i64 = row.get(1)?;
+                let payload: String = row.get(2)?;
+                let events = serde_json::from_str::<Vec<serde_json::Value>>(&payload)
+                    .map(|events| events.len())
+                    .unwrap_or_default();
+                Ok(EntryStats {
+                    key,
+                    events,
+                    bytes: payload.len(),
+                    age_seconds: now.saturating_sub(fetched_at as u64),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let etag_entries: i64 =
+            conn.query_row("SELECT COUNT(*) FROM etags", [], |row| row.get(0))?;
+        let file_bytes = std::fs::metadata(&self.path)
+            .map(|m| m.len())
+            .unwrap_or_default();
+
+        Ok(PersistentStats {
+            path: self.path.clone(),
+            file_bytes,
+            hits,
+            misses,
+            entries,
+            etag_entries: etag_entries as u64,
+        })
+    }
+}
+
+impl Drop for PersistentStore {
+    fn drop(&mut self) {
+        self.flush_counters();
+    }
+}
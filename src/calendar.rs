@@ -1,188 +1,1011 @@
-use crate::auth::AuthManager;
-use crate::cache::EventCache;
-use crate::config::Config;
+use crate::api::{self, ApiClient};
+use crate::auth::{AuthManager, DRIVE_SCOPES, SCOPES, WRITE_SCOPES};
+use crate::cache::{CacheStats, EventCache, PersistedEntry};
+use crate::config::{CacheStrategy, CalendarConfig, Config, FilterConfig, VisibilityPolicy};
+use crate::diff::EventDiff;
 use crate::error::{CalendarError, Result};
-use crate::output::CalendarEvent;
-use chrono::{DateTime, Local, TimeZone, Utc};
-use google_calendar3::hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
-use google_calendar3::{
-    CalendarHub,
-    api::{CalendarListEntry, Event},
+use crate::filter::EventFilter;
+use crate::hours::WorkingHours;
+use crate::http_trace;
+use crate::journal::{self, Entry, Operation};
+use crate::output::{CalendarEvent, EventKind};
+use crate::sanitize;
+use crate::timings;
+use crate::when::Start;
+use crate::zone::Zone;
+use chrono::{DateTime, Datelike, FixedOffset, Utc};
+use google_calendar3::api::{
+    CalendarList, CalendarListEntry, Event, EventAttachment, EventAttendee, EventReminder, Events,
 };
+use google_calendar3::hyper_rustls::HttpsConnector;
+use hyper::Method;
 use hyper_util::client::legacy::connect::HttpConnector;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::OnceCell;
+use yup_oauth2::authenticator::Authenticator;
+
+/// Partial-response selectors: only request what `convert_event` and the
+/// calendar listing actually read.
+const EVENT_FIELDS: &str = "nextPageToken,defaultReminders,items(id,eventType,summary,description,status,transparency,visibility,location,start,end,htmlLink,source(url),reminders,hangoutLink,recurringEventId,extendedProperties,organizer(self,email,displayName),attendees(self,resource,responseStatus),conferenceData(entryPoints(entryPointType,uri)))";
+const RECURRENCE_FIELDS: &str = "nextPageToken,items(id,recurrence,start(date,dateTime))";
+/// Where the `source` of an event Gmail created links to.
+const GMAIL_URL: &str = "https://mail.google.com/";
+/// Ids of the calendar Google fills with your contacts' birthdays end in
+/// this.
+const BIRTHDAYS_CALENDAR: &str = "#contacts@group.v.calendar.google.com";
+const CALENDAR_LIST_FIELDS: &str = "nextPageToken,items(id,summary,primary,accessRole)";
+
+/// How long one background refresh is trusted to finish before another
+/// invocation may start a new one.
+const REVALIDATE_WINDOW_SECONDS: u64 = 60;
+
+/// An event for `create_event`.
+#[derive(Debug, Clone)]
+pub struct NewEvent {
+    pub title: String,
+    pub start: Start,
+    pub duration: chrono::Duration,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    /// Ask Google to set up a new Meet call for it.
+    pub meet: bool,
+    pub invitees: Vec<Invitee>,
+    /// Popup reminders, in minutes before the start, instead of the
+    /// calendar's defaults.
+    pub reminders: Vec<i64>,
+    /// An `RRULE:` line for a recurring event.
+    pub recurrence: Option<String>,
+    /// `sendUpdates`: `all`, `externalOnly` or `none`.
+    pub send_updates: Option<&'static str>,
+}
+
+/// A guest to add with `--invite`/`--optional`.
+#[derive(Debug, Clone)]
+pub struct Invitee {
+    pub email: String,
+    pub optional: bool,
+}
+
+impl Invitee {
+    /// Parses a comma-separated list of addresses.
+    pub fn parse_list(list: &str, optional: bool) -> Result<Vec<Invitee>> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|email| !email.is_empty())
+            .map(|email| {
+                if email.contains('@') {
+                    Ok(Invitee {
+                        email: email.to_string(),
+                        optional,
+                    })
+                } else {
+                    Err(CalendarError::config(format!(
+                        "'{}' is not an email address",
+                        email
+                    )))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Fields to change with `update_event`; `None` leaves one as it is.
+#[derive(Debug, Clone, Default)]
+pub struct EventChanges {
+    pub title: Option<String>,
+    /// Start and duration always change together since Google stores the end.
+    pub times: Option<(Start, chrono::Duration)>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    /// The whole guest list; Google replaces it rather than merging.
+    pub attendees: Option<Vec<EventAttendee>>,
+    /// `sendUpdates`; not a change by itself.
+    pub send_updates: Option<&'static str>,
+}
+
+impl EventChanges {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.times.is_none()
+            && self.location.is_none()
+            && self.description.is_none()
+            && self.attendees.is_none()
+    }
+}
+
+/// An event looked up by id, with the calendar it lives in.
+pub struct FoundEvent {
+    pub calendar: CalendarConfig,
+    pub event: CalendarEvent,
+    pub location: Option<String>,
+    /// Id of the recurring event this is an occurrence of.
+    pub series: Option<String>,
+    pub attendees: Vec<EventAttendee>,
+    /// Files attached to it, mostly from Google Drive.
+    pub attachments: Vec<EventAttachment>,
+}
+
+pub struct Agenda {
+    pub events: Vec<CalendarEvent>,
+    /// Set when the events came from the persistent cache instead of the API.
+    pub stale: Option<Staleness>,
+    /// Calendars that failed or came back incomplete while the rest were
+    /// fetched.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Staleness {
+    pub since: Option<DateTime<FixedOffset>>,
+    pub reason: StaleReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StaleReason {
+    /// `--offline` was requested.
+    Offline,
+    /// Google could not be reached or returned errors.
+    Unreachable,
+    /// Google asked us to back off; cached data is served until it recovers.
+    RateLimited,
+    /// Past its TTL under `cache.strategy = "swr"`; a background refresh is
+    /// fetching a newer copy.
+    Revalidating,
+}
+
+/// How far ahead an agenda reaches: whole days (`--days`), hours
+/// (`--hours`) or up to a moment (`--until`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookahead {
+    Days(i64),
+    Hours(i64),
+    Until(DateTime<FixedOffset>),
+}
+
+impl Lookahead {
+    /// Where the window starting at `now` ends.
+    fn end(self, zone: Zone, now: &DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Lookahead::Days(days) => zone.add_days(now, days).with_timezone(&Utc),
+            Lookahead::Hours(hours) => *now + chrono::Duration::hours(hours),
+            Lookahead::Until(at) => at.with_timezone(&Utc),
+        }
+    }
+
+    /// Its part of the cache key. Days keep their bare number, so entries
+    /// written before hours existed still match.
+    fn key(self) -> String {
+        match self {
+            Lookahead::Days(days) => days.to_string(),
+            Lookahead::Hours(hours) => format!("{}h", hours),
+            Lookahead::Until(at) => format!("until{}", at.timestamp()),
+        }
+    }
+
+    /// The `callux prefetch` flag that fetches the same window.
+    fn prefetch_args(self) -> [String; 2] {
+        match self {
+            Lookahead::Days(days) => ["--days".to_string(), days.to_string()],
+            Lookahead::Hours(hours) => ["--hours".to_string(), hours.to_string()],
+            // RFC 3339 keeps the seconds, so the key comes out the same.
+            Lookahead::Until(at) => ["--until".to_string(), at.to_rfc3339()],
+        }
+    }
+}
+
+impl std::fmt::Display for Lookahead {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lookahead::Days(days) => write!(f, "{} days", days),
+            Lookahead::Hours(hours) => write!(f, "{} hours", hours),
+            Lookahead::Until(at) => write!(f, "until {}", at.format("%Y-%m-%d %H:%M")),
+        }
+    }
+}
+
+impl Agenda {
+    fn fresh(events: Vec<CalendarEvent>, limit: Option<usize>) -> Self {
+        Self {
+            events: apply_limit(events, limit),
+            stale: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn stale(entry: PersistedEntry, reason: StaleReason, limit: Option<usize>, zone: Zone) -> Self {
+        Self {
+            events: apply_limit(entry.events, limit),
+            stale: Some(Staleness {
+                since: DateTime::from_timestamp(entry.fetched_at as i64, 0)
+                    .map(|fetched_at| zone.convert(&fetched_at)),
+                reason,
+            }),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+fn apply_limit(events: Vec<CalendarEvent>, limit: Option<usize>) -> Vec<CalendarEvent> {
+    match limit {
+        Some(limit) => events.into_iter().take(limit).collect(),
+        None => events,
+    }
+}
+
+/// A push notification channel registered with `events.watch`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchChannel {
+    pub id: String,
+    pub resource_id: String,
+    /// Milliseconds since the epoch, as a string.
+    pub expiration: Option<String>,
+}
 
 pub struct CalendarClient {
     config: Config,
     auth_manager: AuthManager,
     cache: EventCache,
+    zone: Zone,
+    /// Set when `display.hide_outside_working_hours` is on.
+    working_hours: Option<WorkingHours>,
+    session: OnceCell<ApiSession>,
+    /// Print writes instead of sending them.
+    dry_run: bool,
+    /// Whole days before today that agendas also cover (`--past`).
+    past_days: Option<i64>,
+}
+
+/// What occurrences need from their recurring event.
+struct Series {
+    rule: Option<String>,
+    first_day: Option<chrono::NaiveDate>,
+}
+
+/// Authenticator and HTTP client, built on first use and shared by every
+/// request this client makes so connections and tokens are reused.
+struct ApiSession {
+    api: ApiClient,
+    /// `None` when `CALLUX_ACCESS_TOKEN` stands in for signing in.
+    authenticator: Option<Authenticator<HttpsConnector<HttpConnector>>>,
+}
+
+/// A bearer token used as is instead of OAuth, for the integration tests'
+/// mock API and for scripts that already hold a token.
+const ACCESS_TOKEN_ENV: &str = "CALLUX_ACCESS_TOKEN";
+
+pub fn fixed_access_token() -> Option<String> {
+    std::env::var(ACCESS_TOKEN_ENV)
+        .ok()
+        .filter(|token| !token.is_empty())
 }
 
 impl CalendarClient {
     pub fn new(config: Config) -> Self {
         let auth_manager = AuthManager::new(config.clone());
-        let cache = EventCache::new(&config.cache);
+        let cache = timings::time("cache open", || {
+            EventCache::new(&config.cache, config.cache_fingerprint())
+        });
+
+        let working_hours = match &config.display.working_hours {
+            Some(hours) if config.display.hide_outside_working_hours => WorkingHours::parse(hours)
+                .map_err(|e| eprintln!("Warning: {}; showing all events", e))
+                .ok(),
+            _ => None,
+        };
 
         Self {
+            zone: Zone::from_config(&config.display.timezone),
+            working_hours,
             config,
             auth_manager,
             cache,
+            session: OnceCell::new(),
+            dry_run: false,
+            past_days: None,
+        }
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Makes agendas start at midnight `days` ago (today's for 0) instead of
+    /// now.
+    pub fn with_past(mut self, days: Option<i64>) -> Self {
+        self.past_days = days.map(|days| days.max(0));
+        self
+    }
+
+    pub async fn clear_cache(&self) -> Result<()> {
+        self.cache.clear().await
+    }
+
+    pub fn prune_cache(&self) -> Result<usize> {
+        self.cache.prune()
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    async fn session(&self) -> Result<&ApiSession> {
+        self.session
+            .get_or_try_init(|| async {
+                let authenticator = match fixed_access_token() {
+                    Some(_) => None,
+                    None => Some(
+                        timings::time_async("auth", self.auth_manager.get_authenticator()).await?,
+                    ),
+                };
+                let api = timings::time("tls", || ApiClient::new(&self.config.network))?;
+                Ok::<_, CalendarError>(ApiSession { api, authenticator })
+            })
+            .await
+    }
+
+    async fn authorized_api(&self) -> Result<(&ApiClient, String)> {
+        self.authorized(SCOPES).await
+    }
+
+    /// Like `authorized_api`, but with a token allowed to change events.
+    /// Dry runs only read, so they don't ask for that permission.
+    async fn authorized_write_api(&self) -> Result<(&ApiClient, String)> {
+        if self.dry_run {
+            return self.authorized_api().await;
         }
+        self.authorized(WRITE_SCOPES).await
+    }
+
+    /// The session is set up under the auth lock as well, so an invocation
+    /// that waited for another one to sign in reads the token it saved.
+    async fn authorized(&self, scopes: &[&str]) -> Result<(&ApiClient, String)> {
+        let _lock = match fixed_access_token() {
+            Some(_) => None,
+            None => Some(self.auth_manager.lock().await?),
+        };
+        let session = self.session().await?;
+        let Some(authenticator) = &session.authenticator else {
+            return Ok((&session.api, fixed_access_token().unwrap_or_default()));
+        };
+        let token =
+            timings::time_async("token", self.auth_manager.token_for(authenticator, scopes))
+                .await?;
+        Ok((&session.api, token))
+    }
+
+    /// Fetches `days_ahead` from the API unconditionally and stores the
+    /// result, so later invocations are served from the cache.
+    pub async fn prefetch(&self, days_ahead: i64) -> Result<usize> {
+        self.prefetch_within(Lookahead::Days(days_ahead)).await
+    }
+
+    /// `prefetch` for any lookahead.
+    pub async fn prefetch_within(&self, lookahead: Lookahead) -> Result<usize> {
+        let calendar_ids = self.enabled_calendar_ids();
+        let cache_key = self
+            .cache
+            .generate_key(&calendar_ids, &self.range_key(lookahead));
+
+        let (events, warnings) = self.fetch_events_from_api(&calendar_ids, lookahead).await?;
+        for warning in warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        let count = events.len();
+        self.cache.set(cache_key, events).await;
+
+        Ok(count)
+    }
+
+    /// Refreshes the cache from a detached `callux prefetch` process so the
+    /// refresh outlives this (short-lived) invocation.
+    fn spawn_revalidation(&self, lookahead: Lookahead) {
+        let Ok(exe) = std::env::current_exe() else {
+            return;
+        };
+
+        let mut command = std::process::Command::new(exe);
+        command
+            .arg("prefetch")
+            .args(lookahead.prefetch_args())
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+        if let Some(days) = self.past_days {
+            command.arg("--past").arg(days.to_string());
+        }
+        if self.config.auth.allow_insecure_permissions {
+            command.arg("--insecure-ok");
+        }
+        if let Some(path) = Config::path_override() {
+            command.arg("--config").arg(path);
+        }
+        if let Some(profile) = Config::profile() {
+            command.arg("--profile").arg(profile);
+        }
+
+        if let Err(e) = command.spawn() {
+            eprintln!("Warning: Failed to start background refresh: {}", e);
+        }
+    }
+
+    /// The cache key part for `lookahead`, plus `--past` when set.
+    fn range_key(&self, lookahead: Lookahead) -> String {
+        match self.past_days {
+            Some(days) => format!("{}+past{}", lookahead.key(), days),
+            None => lookahead.key(),
+        }
+    }
+
+    /// Where fetched windows start: now, or midnight `past_days` ago.
+    fn window_start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let Some(days) = self.past_days else {
+            return now;
+        };
+        let past = chrono::Duration::days(days);
+        let date = self.zone.convert(&now).date_naive() - past;
+        self.zone
+            .start_of_day(date)
+            .map_or(now - past, |start| start.with_timezone(&Utc))
+    }
+
+    fn calendar_config(&self, id: &str) -> Option<&CalendarConfig> {
+        self.config.calendars.iter().find(|cal| cal.id == id)
+    }
+
+    pub fn enabled_calendar_ids(&self) -> Vec<String> {
+        self.config
+            .calendars
+            .iter()
+            .filter(|cal| cal.enabled)
+            .map(|cal| cal.id.clone())
+            .collect()
     }
 
     pub async fn get_events(
         &self,
         days_ahead: i64,
         limit: Option<usize>,
-    ) -> Result<Vec<CalendarEvent>> {
-        let enabled_calendars: Vec<_> = self
-            .config
-            .calendars
-            .iter()
-            .filter(|cal| cal.enabled)
-            .collect();
+        offline: bool,
+    ) -> Result<Agenda> {
+        self.get_events_within(Lookahead::Days(days_ahead), limit, offline)
+            .await
+    }
+
+    /// `get_events` for any lookahead, each cached on its own.
+    pub async fn get_events_within(
+        &self,
+        lookahead: Lookahead,
+        limit: Option<usize>,
+        offline: bool,
+    ) -> Result<Agenda> {
+        let calendar_ids = self.enabled_calendar_ids();
+        let cache_key = self
+            .cache
+            .generate_key(&calendar_ids, &self.range_key(lookahead));
 
-        let calendar_ids: Vec<String> =
-            enabled_calendars.iter().map(|cal| cal.id.clone()).collect();
+        if offline {
+            let mut entry = self
+                .cache
+                .get_stale(&cache_key)
+                .ok_or_else(|| CalendarError::api("Offline and no cached events available"))?;
+            entry.events = self.unfinished(entry.events);
+            return Ok(Agenda::stale(entry, StaleReason::Offline, limit, self.zone));
+        }
 
-        let cache_key = self.cache.generate_key(&calendar_ids, days_ahead);
+        if let Some(cached_events) = timings::time_async("cache", self.cache.get(&cache_key)).await
+        {
+            return Ok(Agenda::fresh(self.unfinished(cached_events), limit));
+        }
 
-        if let Some(cached_events) = self.cache.get(&cache_key).await {
-            return Ok(cached_events);
+        if self.config.cache.strategy == CacheStrategy::Swr {
+            if let Some(mut entry) = self.cache.get_stale(&cache_key) {
+                if self
+                    .cache
+                    .claim_revalidation(&cache_key, REVALIDATE_WINDOW_SECONDS)
+                {
+                    self.spawn_revalidation(lookahead);
+                }
+                entry.events = self.unfinished(entry.events);
+                return Ok(Agenda::stale(
+                    entry,
+                    StaleReason::Revalidating,
+                    limit,
+                    self.zone,
+                ));
+            }
         }
 
-        let events = self
-            .fetch_events_from_api(&calendar_ids, days_ahead)
-            .await?;
+        let (events, warnings) = match self.fetch_events_from_api(&calendar_ids, lookahead).await {
+            Ok(fetched) => fetched,
+            Err(e) => match self.cache.get_stale(&cache_key) {
+                Some(mut entry) => {
+                    eprintln!("Warning: {}; showing cached events", e);
+                    let reason = match e {
+                        CalendarError::RateLimited(_) => StaleReason::RateLimited,
+                        _ => StaleReason::Unreachable,
+                    };
+                    entry.events = self.unfinished(entry.events);
+                    return Ok(Agenda::stale(entry, reason, limit, self.zone));
+                }
+                None => return Err(e),
+            },
+        };
         self.cache.set(cache_key, events.clone()).await;
 
-        let limited_events = if let Some(limit) = limit {
-            events.into_iter().take(limit).collect()
-        } else {
-            events
-        };
+        Ok(Agenda {
+            warnings,
+            ..Agenda::fresh(self.unfinished(events), limit)
+        })
+    }
 
-        Ok(limited_events)
+    /// Drops events that are already over when `display.hide_past` is set,
+    /// unless `--past` asked for them. Cached results need this: they hold
+    /// events that were still ahead when they were fetched.
+    fn unfinished(&self, mut events: Vec<CalendarEvent>) -> Vec<CalendarEvent> {
+        if self.config.display.hide_past && self.past_days.is_none() {
+            let now = Utc::now();
+            events.retain(|event| event.end_time.with_timezone(&Utc) > now);
+        }
+        events
     }
 
+    /// Compares the agenda with the snapshot taken by the previous call and
+    /// replaces that snapshot unless `keep` is set. `None` means there was no
+    /// snapshot yet to compare with.
+    pub async fn diff(
+        &self,
+        days_ahead: i64,
+        offline: bool,
+        keep: bool,
+    ) -> Result<Option<EventDiff>> {
+        if !self.cache.has_store() {
+            return Err(CalendarError::cache(
+                "callux diff needs the persistent cache",
+            ));
+        }
+
+        let calendar_ids = self.enabled_calendar_ids();
+        let name = format!(
+            "diff:{}",
+            self.cache
+                .generate_key(&calendar_ids, &Lookahead::Days(days_ahead).key())
+        );
+        let current = self.get_events(days_ahead, None, offline).await?.events;
+
+        let diff = self
+            .cache
+            .snapshot(&name)
+            .map(|previous| EventDiff::between(&previous, &current, days_ahead, self.zone));
+        if !keep {
+            self.cache.set_snapshot(&name, &current);
+        }
+
+        Ok(diff)
+    }
+
+    /// Events of every calendar, with a warning for each one that failed or
+    /// was cut short.
     async fn fetch_events_from_api(
         &self,
         calendar_ids: &[String],
-        days_ahead: i64,
-    ) -> Result<Vec<CalendarEvent>> {
-        let authenticator = self.auth_manager.get_authenticator().await?;
-
-        let https = HttpsConnectorBuilder::new()
-            .with_native_roots()
-            .map_err(|e| {
-                CalendarError::ApiError(format!("Failed to build HTTPS connector: {}", e))
-            })?
-            .https_or_http()
-            .enable_http1()
-            .build();
-
-        let client =
-            hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
-                .build(https);
-        let hub = CalendarHub::new(client, authenticator);
+        lookahead: Lookahead,
+    ) -> Result<(Vec<CalendarEvent>, Vec<String>)> {
+        let (api, token) = self.authorized_api().await?;
 
         let now = Utc::now();
-        let end_time = now + chrono::Duration::days(days_ahead);
+        let start_time = self.window_start(now);
 
         let mut all_events = Vec::new();
+        let mut warnings = Vec::new();
+        let mut last_error = None;
+        let mut fetched_any = false;
 
         for calendar_id in calendar_ids {
+            // A calendar's own days_ahead shortens a range in days but never
+            // reaches past --days; hours and --until apply to all.
+            let own_days = self
+                .calendar_config(calendar_id)
+                .and_then(|cal| cal.days_ahead);
+            let mut end_time = match (lookahead, own_days) {
+                (Lookahead::Days(days), Some(own)) => Lookahead::Days(days.min(own)),
+                _ => lookahead,
+            }
+            .end(self.zone, &now);
+            // max_days_ahead caps every kind of lookahead.
+            if let Some(max) = self
+                .calendar_config(calendar_id)
+                .and_then(|cal| cal.max_days_ahead)
+            {
+                end_time = end_time.min(Lookahead::Days(max).end(self.zone, &now));
+            }
+
             match self
-                .fetch_calendar_events(&hub, calendar_id, &now, &end_time)
+                .fetch_calendar_events(
+                    api,
+                    &token,
+                    calendar_id,
+                    &start_time,
+                    &end_time,
+                    &mut warnings,
+                )
                 .await
             {
-                Ok(events) => all_events.extend(events),
+                Ok(events) => {
+                    all_events.extend(events);
+                    fetched_any = true;
+                }
+                // Further calendars would only hit the same quota; let the
+                // caller fall back to cached data for the whole agenda.
+                Err(e @ CalendarError::RateLimited(_)) => return Err(e),
                 Err(e) => {
-                    eprintln!(
-                        "Warning: Failed to fetch events from calendar {}: {}",
+                    warnings.push(format!(
+                        "Failed to fetch events from calendar {}: {}",
                         calendar_id, e
-                    );
+                    ));
+                    last_error = Some(e);
                 }
             }
         }
 
+        // Only treat the fetch as failed when nothing came back, so one broken
+        // calendar doesn't push the whole agenda onto stale data.
+        if let Some(e) = last_error {
+            if !fetched_any {
+                return Err(e);
+            }
+        }
+
         all_events.sort_by(|a, b| a.start_time.cmp(&b.start_time));
-        Ok(all_events)
+        Ok((all_events, warnings))
     }
 
     async fn fetch_calendar_events(
         &self,
-        hub: &CalendarHub<HttpsConnector<HttpConnector>>,
+        api: &ApiClient,
+        token: &str,
         calendar_id: &str,
         start_time: &DateTime<Utc>,
         end_time: &DateTime<Utc>,
+        warnings: &mut Vec<String>,
     ) -> Result<Vec<CalendarEvent>> {
-        let result = hub
-            .events()
-            .list(calendar_id)
-            .time_min(*start_time)
-            .time_max(*end_time)
-            .single_events(true)
-            .order_by("startTime")
-            .max_results(250)
-            .doit()
-            .await
-            .map_err(|e| CalendarError::ApiError(format!("Failed to fetch events: {}", e)))?;
+        // Query whole local days so the request URL stays identical between
+        // polls and the stored ETag can actually match; trim locally below.
+        let (window_start, window_end) = day_aligned_window(self.zone, start_time, end_time);
+        let path = format!("/calendars/{}/events", api::encode_component(calendar_id));
 
-        let calendar_config = self
-            .config
-            .calendars
-            .iter()
-            .find(|cal| cal.id == calendar_id)
-            .ok_or_else(|| {
-                CalendarError::ConfigError(format!(
-                    "Calendar config not found for ID: {}",
-                    calendar_id
-                ))
-            })?;
+        let max_results = self
+            .calendar_config(calendar_id)
+            .and_then(|cal| cal.max_results)
+            .unwrap_or(self.config.network.max_results)
+            .to_string();
+
+        let mut events = Vec::new();
+        let mut default_reminders = Vec::new();
+        let mut page_token: Option<String> = None;
 
-        let events = result.1.items.unwrap_or_default();
+        for page in 0.. {
+            if page >= self.config.network.max_pages {
+                warnings.push(format!(
+                    "Calendar {} has more than {} pages of events; results truncated",
+                    calendar_id, self.config.network.max_pages
+                ));
+                break;
+            }
+
+            let mut query = vec![
+                ("timeMin", window_start.to_rfc3339()),
+                ("timeMax", window_end.to_rfc3339()),
+                ("singleEvents", "true".to_string()),
+                ("orderBy", "startTime".to_string()),
+                ("maxResults", max_results.clone()),
+                ("fields", EVENT_FIELDS.to_string()),
+            ];
+            if self.config.display.show_cancelled {
+                query.push(("showDeleted", "true".to_string()));
+            }
+            if let Some(next) = &page_token {
+                query.push(("pageToken", next.clone()));
+            }
+
+            let url = api::build_url(&path, &query);
+            let payload = self.conditional_get(api, token, &url).await?;
+            let result: Events = serde_json::from_str(&payload)
+                .map_err(|e| CalendarError::parse_from("Invalid events response", e))?;
+
+            if let Some(defaults) = result.default_reminders {
+                default_reminders = popup_minutes(&defaults);
+            }
+            let items = result.items.unwrap_or_default();
+            http_trace::note(&format!(
+                "{}: {} events on page {}",
+                calendar_id,
+                items.len(),
+                page + 1
+            ));
+            events.extend(items);
+            page_token = result.next_page_token;
+            if page_token.is_none() {
+                break;
+            }
+        }
+
+        let calendar_config = self.calendar_config(calendar_id).ok_or_else(|| {
+            CalendarError::config(format!("Calendar config not found for ID: {}", calendar_id))
+        })?;
+
+        // Occurrences don't carry their series' rule, so look the rules up
+        // once per calendar, and only when there are recurring events.
+        let recurring = if events.iter().any(|e| e.recurring_event_id.is_some()) {
+            self.recurring_series(api, token, &path, &window_start, &window_end, &max_results)
+                .await
+                .unwrap_or_else(|e| {
+                    warnings.push(format!(
+                        "Failed to fetch recurrence rules of calendar {}: {}",
+                        calendar_id, e
+                    ));
+                    HashMap::new()
+                })
+        } else {
+            HashMap::new()
+        };
+
+        // A calendar's own policy can only be stricter than the global one.
+        let visibility = calendar_config
+            .visibility
+            .unwrap_or_default()
+            .max(self.config.display.visibility);
+        let filter = EventFilter::new(&self.config.filters, &calendar_config.filters)?
+            .with_visibility(visibility)
+            .with_display(&self.config.display);
         let mut calendar_events = Vec::new();
 
         for event in events {
-            if let Some(cal_event) = self.convert_event(event, calendar_config)? {
-                calendar_events.push(cal_event);
+            if let Some(mut cal_event) =
+                self.convert_event(event, calendar_config, &filter, &default_reminders)?
+            {
+                if let Some(series) = cal_event.series.as_ref().and_then(|id| recurring.get(id)) {
+                    cal_event.recurrence = series.rule.clone();
+                    // A birthday series started in the year of birth, when
+                    // Google knew it.
+                    if cal_event.kind == EventKind::Birthday {
+                        cal_event.born = series
+                            .first_day
+                            .map(|day| day.year())
+                            .filter(|year| *year < cal_event.start_time.year());
+                    }
+                }
+                cal_event.title = sanitize::clean_title(&cal_event.title);
+                cal_event.description =
+                    cal_event.description.as_deref().map(sanitize::html_to_text);
+                if cal_event.all_day && !calendar_config.show_all_day {
+                    continue;
+                }
+                if let Some(hours) = &self.working_hours {
+                    if !cal_event.all_day
+                        && !hours.overlaps(&cal_event.start_time, &cal_event.end_time)
+                    {
+                        continue;
+                    }
+                }
+                if cal_event.end_time > *start_time && cal_event.start_time < *end_time {
+                    calendar_events.push(cal_event);
+                }
             }
         }
 
+        if let Some(max_events) = calendar_config.max_events {
+            calendar_events.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+            calendar_events.truncate(max_events);
+        }
+
         Ok(calendar_events)
     }
 
+    /// The recurring events with occurrences in the window, by id.
+    async fn recurring_series(
+        &self,
+        api: &ApiClient,
+        token: &str,
+        path: &str,
+        window_start: &DateTime<Utc>,
+        window_end: &DateTime<Utc>,
+        max_results: &str,
+    ) -> Result<HashMap<String, Series>> {
+        let mut recurring = HashMap::new();
+        let mut page_token: Option<String> = None;
+
+        for _ in 0..self.config.network.max_pages {
+            let mut query = vec![
+                ("timeMin", window_start.to_rfc3339()),
+                ("timeMax", window_end.to_rfc3339()),
+                ("singleEvents", "false".to_string()),
+                ("maxResults", max_results.to_string()),
+                ("fields", RECURRENCE_FIELDS.to_string()),
+            ];
+            if let Some(next) = &page_token {
+                query.push(("pageToken", next.clone()));
+            }
+
+            let url = api::build_url(path, &query);
+            let payload = self.conditional_get(api, token, &url).await?;
+            let result: Events = serde_json::from_str(&payload)
+                .map_err(|e| CalendarError::parse_from("Invalid events response", e))?;
+
+            for event in result.items.unwrap_or_default() {
+                let Some(id) = event.id else {
+                    continue;
+                };
+                let rule = event
+                    .recurrence
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|line| line.starts_with("RRULE:"));
+                let first_day = event.start.and_then(|start| {
+                    start
+                        .date
+                        .or_else(|| start.date_time.map(|at| at.date_naive()))
+                });
+                recurring.insert(id, Series { rule, first_day });
+            }
+            page_token = result.next_page_token;
+            if page_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(recurring)
+    }
+
     fn convert_event(
         &self,
         event: Event,
-        calendar_config: &crate::config::CalendarConfig,
+        calendar_config: &CalendarConfig,
+        filter: &EventFilter,
+        default_reminders: &[u32],
     ) -> Result<Option<CalendarEvent>> {
+        // Deleted occurrences of a recurring event come back as cancelled
+        // exceptions until the series itself changes.
+        let display = filter.display();
+        let cancelled = event.status.as_deref() == Some("cancelled");
+        if cancelled && display.is_some_and(|display| !display.show_cancelled) {
+            return Ok(None);
+        }
+        let attendees = event.attendees.as_deref().unwrap_or_default();
+        let response = attendees
+            .iter()
+            .find(|attendee| attendee.self_ == Some(true))
+            .and_then(|me| me.response_status.clone());
+        let guests = attendees
+            .iter()
+            .filter(|attendee| attendee.self_ != Some(true) && attendee.resource != Some(true))
+            .count();
+        if response.as_deref() == Some("declined")
+            && display.is_some_and(|display| display.hide_declined)
+        {
+            return Ok(None);
+        }
+        let free = event.transparency.as_deref() == Some("transparent");
+        if free && display.is_some_and(|display| !display.show_free) {
+            return Ok(None);
+        }
+        let kind = match EventKind::parse(event.event_type.as_deref()) {
+            _ if calendar_config.id.ends_with(BIRTHDAYS_CALENDAR) => EventKind::Birthday,
+            // Events Gmail added before the `fromGmail` type existed only
+            // point back at the email they came from.
+            EventKind::Default
+                if event
+                    .source
+                    .as_ref()
+                    .and_then(|source| source.url.as_deref())
+                    .is_some_and(|url| url.starts_with(GMAIL_URL)) =>
+            {
+                EventKind::FromGmail
+            }
+            kind => kind,
+        };
+        let shown = display.is_none_or(|display| match kind {
+            EventKind::Default | EventKind::Birthday => true,
+            EventKind::FromGmail => display.show_from_gmail,
+            EventKind::OutOfOffice => display.show_out_of_office,
+            EventKind::FocusTime => display.show_focus_time,
+            EventKind::WorkingLocation => display.show_working_location,
+        });
+        if !shown {
+            return Ok(None);
+        }
+        let policy = filter.visibility();
+        let private = matches!(
+            event.visibility.as_deref(),
+            Some("private" | "confidential")
+        );
+        match policy {
+            VisibilityPolicy::HidePrivate if private => return Ok(None),
+            VisibilityPolicy::OnlyPublic if event.visibility.as_deref() != Some("public") => {
+                return Ok(None);
+            }
+            _ => {}
+        }
+        let masked = private && policy == VisibilityPolicy::Mask;
+        let meeting_url = meeting_url(&event).filter(|_| !masked);
+        let html_link = event.html_link.filter(|_| !masked);
         let id = event.id.unwrap_or_default();
         let title = event
             .summary
             .unwrap_or_else(|| "Untitled Event".to_string());
-        let description = event.description;
+        if !filter.allows(&title, event.location.as_deref()) {
+            return Ok(None);
+        }
+        let (title, description, location) = if masked {
+            ("Busy".to_string(), None, None)
+        } else {
+            (title, event.description, event.location)
+        };
+
+        // Configured lead times win; otherwise follow what the event (or the
+        // calendar's defaults) say in Google Calendar.
+        let reminder_minutes = if !calendar_config.reminder_minutes.is_empty() {
+            calendar_config.reminder_minutes.clone()
+        } else {
+            match &event.reminders {
+                Some(reminders) if reminders.use_default == Some(false) => reminders
+                    .overrides
+                    .as_deref()
+                    .map(popup_minutes)
+                    .unwrap_or_default(),
+                _ => default_reminders.to_vec(),
+            }
+        };
 
+        let time_zone = event
+            .start
+            .as_ref()
+            .and_then(|start| start.time_zone.clone());
+        let properties = event
+            .extended_properties
+            .map(|extended| {
+                let shared = extended.shared.unwrap_or_default();
+                let private = extended.private.unwrap_or_default();
+                shared.into_iter().chain(private).collect()
+            })
+            .filter(|_| !masked)
+            .unwrap_or_default();
         let (start_time, end_time, all_day) = if let Some(start) = event.start {
             if let Some(date_time) = &start.date_time {
-                let start_dt = date_time.with_timezone(&Local);
+                let start_dt = self.zone.convert(date_time);
 
                 let end_dt = if let Some(end) = event.end {
                     if let Some(end_date_time) = &end.date_time {
-                        end_date_time.with_timezone(&Local)
+                        self.zone.convert(end_date_time)
                     } else {
                         start_dt + chrono::Duration::hours(1)
                     }
                 } else {
                     start_dt + chrono::Duration::hours(1)
                 };
+                // Overnight events keep their real end; a malformed end
+                // before the start collapses to the start.
+                let end_dt = end_dt.max(start_dt);
 
                 (start_dt, end_dt, false)
             } else if let Some(date) = &start.date {
-                let start_dt = Local
-                    .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
-                    .unwrap();
-                let end_dt = start_dt + chrono::Duration::days(1);
+                let Some(start_dt) = self.zone.start_of_day(*date) else {
+                    return Ok(None);
+                };
+                // `end.date` is exclusive: a one-day event on the 3rd ends
+                // on the 4th. Missing or malformed ends count as one day.
+                let end_date = event
+                    .end
+                    .and_then(|end| end.date)
+                    .filter(|end| end > date)
+                    .unwrap_or(*date + chrono::Duration::days(1));
+                let Some(end_dt) = self.zone.start_of_day(end_date) else {
+                    return Ok(None);
+                };
 
                 (start_dt, end_dt, true)
             } else {
@@ -191,6 +1014,9 @@ impl CalendarClient {
         } else {
             return Ok(None);
         };
+        if !all_day && !filter.allows_duration(end_time - start_time) {
+            return Ok(None);
+        }
 
         Ok(Some(CalendarEvent {
             id,
@@ -198,36 +1024,849 @@ impl CalendarClient {
             description,
             start_time,
             end_time,
-            calendar_name: calendar_config.name.clone(),
+            calendar_name: self.config.calendar_label(calendar_config).to_string(),
             calendar_color: calendar_config.color.clone(),
             all_day,
+            reminder_minutes,
+            meeting_url,
+            html_link,
+            location,
+            visibility: event.visibility,
+            cancelled,
+            response,
+            guests,
+            is_organizer: event
+                .organizer
+                .as_ref()
+                .and_then(|organizer| organizer.self_)
+                == Some(true),
+            organizer: event
+                .organizer
+                .and_then(|organizer| organizer.display_name.or(organizer.email))
+                .filter(|_| !masked),
+            free,
+            kind,
+            series: event.recurring_event_id,
+            recurrence: event
+                .recurrence
+                .unwrap_or_default()
+                .into_iter()
+                .find(|line| line.starts_with("RRULE:"))
+                .filter(|_| !masked),
+            time_zone,
+            born: None,
+            properties,
         }))
     }
 
+    /// Downloads a Drive attachment. Google Docs, Sheets and Slides have no
+    /// file of their own, so they're exported as PDF; the returned name
+    /// then ends in `.pdf`.
+    pub async fn download_attachment(
+        &self,
+        attachment: &EventAttachment,
+    ) -> Result<(String, Vec<u8>)> {
+        let title = attachment.title.as_deref().unwrap_or("attachment");
+        let Some(file_id) = &attachment.file_id else {
+            return Err(CalendarError::NotFound(format!(
+                "'{}' isn't a Drive file; open {} instead",
+                title,
+                attachment.file_url.as_deref().unwrap_or("its link")
+            )));
+        };
+
+        let path = format!("/files/{}", api::encode_component(file_id));
+        let native = attachment
+            .mime_type
+            .as_deref()
+            .is_some_and(|mime| mime.starts_with("application/vnd.google-apps."));
+        let (url, name) = if native {
+            (
+                api::build_drive_url(
+                    &format!("{}/export", path),
+                    &[("mimeType", "application/pdf".to_string())],
+                ),
+                format!("{}.pdf", title),
+            )
+        } else {
+            (
+                api::build_drive_url(&path, &[("alt", "media".to_string())]),
+                title.to_string(),
+            )
+        };
+
+        let (api, token) = self.authorized(DRIVE_SCOPES).await?;
+        let response = api.get(&url, &token, None).await?;
+        Ok((name, response.body.to_vec()))
+    }
+
+    /// Gets a token the way every command does, refreshing it if it expired.
+    pub async fn check_token(&self) -> Result<()> {
+        self.authorized_api().await.map(|_| ())
+    }
+
+    /// Asks for a single event of `calendar_id`, the cheapest read that
+    /// proves the calendar is reachable.
+    pub async fn check_calendar(&self, calendar_id: &str) -> Result<()> {
+        let (api, token) = self.authorized_api().await?;
+        let url = api::build_url(
+            &format!("/calendars/{}/events", api::encode_component(calendar_id)),
+            &[
+                ("maxResults", "1".to_string()),
+                ("fields", "kind".to_string()),
+            ],
+        );
+        api.get(&url, &token, None).await?;
+        Ok(())
+    }
+
     pub async fn list_calendars(&self) -> Result<Vec<CalendarListEntry>> {
-        let authenticator = self.auth_manager.get_authenticator().await?;
-
-        let https = HttpsConnectorBuilder::new()
-            .with_native_roots()
-            .map_err(|e| {
-                CalendarError::ApiError(format!("Failed to build HTTPS connector: {}", e))
-            })?
-            .https_or_http()
-            .enable_http1()
-            .build();
-
-        let client =
-            hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
-                .build(https);
-        let hub = CalendarHub::new(client, authenticator);
-
-        let result = hub
-            .calendar_list()
-            .list()
-            .doit()
-            .await
-            .map_err(|e| CalendarError::ApiError(format!("Failed to list calendars: {}", e)))?;
+        let (api, token) = self.authorized_api().await?;
+
+        let url = api::build_url(
+            "/users/me/calendarList",
+            &[("fields", CALENDAR_LIST_FIELDS.to_string())],
+        );
+        let payload = self.conditional_get(api, &token, &url).await?;
+        let result: CalendarList = serde_json::from_str(&payload)
+            .map_err(|e| CalendarError::parse_from("Invalid calendar list response", e))?;
+
+        Ok(result.items.unwrap_or_default())
+    }
+
+    /// Asks Google to POST to `address` whenever events in `calendar_id`
+    /// change, for up to `ttl_seconds`.
+    pub async fn watch_events(
+        &self,
+        calendar_id: &str,
+        channel_id: &str,
+        address: &str,
+        token: &str,
+        ttl_seconds: u64,
+    ) -> Result<WatchChannel> {
+        let (api, access_token) = self.authorized_api().await?;
+        let url = api::build_url(
+            &format!(
+                "/calendars/{}/events/watch",
+                api::encode_component(calendar_id)
+            ),
+            &[],
+        );
+        let body = serde_json::json!({
+            "id": channel_id,
+            "type": "web_hook",
+            "address": address,
+            "token": token,
+            "params": { "ttl": ttl_seconds.to_string() },
+        });
+
+        let response = api.post_json(&url, &access_token, &body).await?;
+        serde_json::from_slice(&response.body)
+            .map_err(|e| CalendarError::parse_from("Invalid watch response", e))
+    }
+
+    /// Inserts `event` into `calendar` and returns it as the agenda would
+    /// show it. Cached agendas are dropped so it shows up right away.
+    pub async fn create_event(
+        &self,
+        calendar: &CalendarConfig,
+        event: &NewEvent,
+    ) -> Result<CalendarEvent> {
+        let (start, end) = event_times(event.start, event.duration);
+        let mut body = serde_json::json!({
+            "summary": event.title,
+            "start": start,
+            "end": end,
+        });
+        if let Some(location) = &event.location {
+            body["location"] = location.clone().into();
+        }
+        if let Some(description) = &event.description {
+            body["description"] = description.clone().into();
+        }
+        if event.meet {
+            body["conferenceData"] = new_meet();
+        }
+        if !event.invitees.is_empty() {
+            body["attendees"] = attendees_value(&with_invitees(&[], &event.invitees))?;
+        }
+        if !event.reminders.is_empty() {
+            let overrides: Vec<_> = event
+                .reminders
+                .iter()
+                .map(|minutes| serde_json::json!({ "method": "popup", "minutes": minutes }))
+                .collect();
+            body["reminders"] = serde_json::json!({
+                "useDefault": false,
+                "overrides": overrides,
+            });
+        }
+
+        if let Some(rule) = &event.recurrence {
+            body["recurrence"] = serde_json::json!([rule]);
+            // Google expands timed recurrences in a named zone, so DST
+            // changes keep the wall-clock time.
+            if let Start::Timed(_) = event.start {
+                let zone = self.zone.iana_name().ok_or_else(|| {
+                    CalendarError::config(
+                        "Can't tell the local timezone's name; set display.timezone for recurring events",
+                    )
+                })?;
+                body["start"]["timeZone"] = zone.clone().into();
+                body["end"]["timeZone"] = zone.into();
+            }
+        }
+
+        self.insert_event(calendar, &body, event.send_updates).await
+    }
+
+    /// Copies the event `found` refers to into `to` at a new time, keeping
+    /// its description, location, color, reminders and video call (or
+    /// replacing the call with a new Meet when `meet` is set). Guests aren't
+    /// copied, so nobody gets invited by surprise.
+    pub async fn duplicate_event(
+        &self,
+        found: &FoundEvent,
+        start: Start,
+        duration: chrono::Duration,
+        to: &CalendarConfig,
+        meet: bool,
+    ) -> Result<CalendarEvent> {
+        let (api, access_token) = self.authorized_write_api().await?;
+        let response = api
+            .get(
+                &event_url(&found.calendar.id, &found.event.id),
+                &access_token,
+                None,
+            )
+            .await?;
+        let original: Event = serde_json::from_slice(&response.body)
+            .map_err(|e| CalendarError::parse_from("Invalid event response", e))?;
+
+        let (start, end) = event_times(start, duration);
+        let mut body = serde_json::json!({
+            "summary": original.summary,
+            "start": start,
+            "end": end,
+            "description": original.description,
+            "location": original.location,
+            "colorId": original.color_id,
+            "transparency": original.transparency,
+            "visibility": original.visibility,
+            "reminders": original.reminders,
+        });
+        if let Some(fields) = body.as_object_mut() {
+            fields.retain(|_, value| !value.is_null());
+        }
+        if meet {
+            body["conferenceData"] = new_meet();
+        } else if let Some(mut conference) = original.conference_data {
+            // Reuse the existing call rather than asking for a new one.
+            conference.create_request = None;
+            body["conferenceData"] = serde_json::to_value(conference)
+                .map_err(|e| CalendarError::parse_from("Invalid conference data", e))?;
+        }
+
+        self.insert_event(to, &body, None).await
+    }
+
+    /// POSTs an event body to `calendar`. Bodies with `conferenceData` are
+    /// sent with `conferenceDataVersion=1`, without which Google drops it.
+    async fn insert_event(
+        &self,
+        calendar: &CalendarConfig,
+        body: &serde_json::Value,
+        send_updates: Option<&str>,
+    ) -> Result<CalendarEvent> {
+        let (api, access_token) = self.authorized_write_api().await?;
+        let mut query = Vec::new();
+        if body.get("conferenceData").is_some() {
+            query.push(("conferenceDataVersion", "1".to_string()));
+        }
+        if let Some(send_updates) = send_updates {
+            query.push(("sendUpdates", send_updates.to_string()));
+        }
+        let url = api::build_url(
+            &format!("/calendars/{}/events", api::encode_component(&calendar.id)),
+            &query,
+        );
+
+        let response = self
+            .send_write(api, &access_token, Method::POST, &url, Some(body), None)
+            .await?;
+
+        let created = self
+            .convert_event(parse_event(response)?, calendar, &unfiltered()?, &[])?
+            .ok_or_else(|| CalendarError::parse("Created event has no start"))?;
+        self.journal(
+            Operation::Created,
+            &calendar.id,
+            &created.id,
+            &created.title,
+        );
+        Ok(created)
+    }
+
+    /// Looks `event_id` up in `calendar`, or in each enabled calendar in
+    /// turn when none is given.
+    pub async fn find_event(&self, event_id: &str, calendar: Option<&str>) -> Result<FoundEvent> {
+        let candidates: Vec<&CalendarConfig> = match calendar {
+            Some(name) => vec![
+                self.config
+                    .find_calendar(name)
+                    .map_err(|e| CalendarError::config(e.to_string()))?,
+            ],
+            None => self.config.calendars.iter().filter(|c| c.enabled).collect(),
+        };
+
+        let (api, access_token) = self.authorized_api().await?;
+        for candidate in candidates {
+            let url = event_url(&candidate.id, event_id);
+            let response = match api.get(&url, &access_token, None).await {
+                Ok(response) => response,
+                Err(CalendarError::NotFound(_)) => continue,
+                Err(e) => return Err(e),
+            };
+
+            let event: Event = serde_json::from_slice(&response.body)
+                .map_err(|e| CalendarError::parse_from("Invalid event response", e))?;
+            if event.status.as_deref() == Some("cancelled") {
+                continue;
+            }
+            let location = event.location.clone();
+            let series = event.recurring_event_id.clone();
+            let attendees = event.attendees.clone().unwrap_or_default();
+            let attachments = event.attachments.clone().unwrap_or_default();
+            if let Some(converted) = self.convert_event(event, candidate, &unfiltered()?, &[])? {
+                return Ok(FoundEvent {
+                    calendar: candidate.clone(),
+                    event: converted,
+                    location,
+                    series,
+                    attendees,
+                    attachments,
+                });
+            }
+        }
+
+        Err(CalendarError::NotFound(format!(
+            "No event '{}' in {}",
+            event_id,
+            calendar.unwrap_or("any enabled calendar")
+        )))
+    }
+
+    /// Applies `changes` to an event and returns the result as the agenda
+    /// would show it. Cached agendas are dropped so the change shows up
+    /// right away.
+    pub async fn update_event(
+        &self,
+        calendar: &CalendarConfig,
+        event_id: &str,
+        changes: &EventChanges,
+    ) -> Result<CalendarEvent> {
+        let mut body = serde_json::Map::new();
+        if let Some(title) = &changes.title {
+            body.insert("summary".to_string(), title.clone().into());
+        }
+        if let Some((start, duration)) = changes.times {
+            let (start, end) = event_times(start, duration);
+            body.insert("start".to_string(), start);
+            body.insert("end".to_string(), end);
+        }
+        if let Some(location) = &changes.location {
+            body.insert("location".to_string(), location.clone().into());
+        }
+        if let Some(description) = &changes.description {
+            body.insert("description".to_string(), description.clone().into());
+        }
+        if let Some(attendees) = &changes.attendees {
+            body.insert("attendees".to_string(), attendees_value(attendees)?);
+        }
+
+        let (api, access_token) = self.authorized_write_api().await?;
+        let before = get_raw(api, &access_token, &event_url(&calendar.id, event_id)).await?;
+        let mut url = event_url(&calendar.id, event_id);
+        if let Some(send_updates) = changes.send_updates {
+            url.push_str(&format!("?sendUpdates={}", send_updates));
+        }
+        let patch = serde_json::Value::Object(body.clone());
+        let response = self
+            .send_write(
+                api,
+                &access_token,
+                Method::PATCH,
+                &url,
+                Some(&patch),
+                Some(&before),
+            )
+            .await?;
+        self.journal(
+            Operation::Updated {
+                restore: restore_of(&before, &body),
+            },
+            &calendar.id,
+            event_id,
+            &title_of(&before),
+        );
+
+        self.convert_event(parse_event(response)?, calendar, &unfiltered()?, &[])?
+            .ok_or_else(|| CalendarError::parse("Updated event has no start"))
+    }
+
+    /// Sets your own attendee `response` (`accepted`, `declined` or
+    /// `tentative`) on an invitation, with an optional note to the organizer.
+    pub async fn respond(
+        &self,
+        calendar: &CalendarConfig,
+        event_id: &str,
+        response: &str,
+        comment: Option<&str>,
+    ) -> Result<CalendarEvent> {
+        let (api, access_token) = self.authorized_write_api().await?;
+        let url = event_url(&calendar.id, event_id);
+        let before = get_raw(api, &access_token, &url).await?;
+        let event: Event = serde_json::from_value(before.clone())
+            .map_err(|e| CalendarError::parse_from("Invalid event response", e))?;
+
+        // Attendees are replaced as a whole, so send everyone back with only
+        // our own entry changed.
+        let mut attendees = event.attendees.unwrap_or_default();
+        let me = attendees
+            .iter_mut()
+            .find(|attendee| attendee.self_ == Some(true))
+            .ok_or_else(|| {
+                CalendarError::NotFound(format!(
+                    "You aren't a guest of '{}'",
+                    event.summary.as_deref().unwrap_or("Untitled")
+                ))
+            })?;
+        me.response_status = Some(response.to_string());
+        if let Some(comment) = comment {
+            me.comment = Some(comment.to_string());
+        }
+
+        let mut body = serde_json::Map::new();
+        body.insert("attendees".to_string(), attendees_value(&attendees)?);
+        let patch = serde_json::Value::Object(body.clone());
+        let patched = self
+            .send_write(
+                api,
+                &access_token,
+                Method::PATCH,
+                &url,
+                Some(&patch),
+                Some(&before),
+            )
+            .await?;
+        self.journal(
+            Operation::Updated {
+                restore: restore_of(&before, &body),
+            },
+            &calendar.id,
+            event_id,
+            &title_of(&before),
+        );
+
+        self.convert_event(parse_event(patched)?, calendar, &unfiltered()?, &[])?
+            .ok_or_else(|| CalendarError::parse("Updated event has no start"))
+    }
+
+    /// Moves an event from `from` to `to`, keeping its id. Google only
+    /// moves whole events, so recurring occurrences must go as a series.
+    pub async fn move_event(
+        &self,
+        from: &CalendarConfig,
+        event_id: &str,
+        to: &CalendarConfig,
+    ) -> Result<CalendarEvent> {
+        let (api, access_token) = self.authorized_write_api().await?;
+        let before = get_raw(api, &access_token, &event_url(&from.id, event_id)).await?;
+        let response = self
+            .send_write(
+                api,
+                &access_token,
+                Method::POST,
+                &move_url(&from.id, event_id, &to.id),
+                None,
+                Some(&before),
+            )
+            .await?;
+
+        let moved = self
+            .convert_event(parse_event(response)?, to, &unfiltered()?, &[])?
+            .ok_or_else(|| CalendarError::parse("Moved event has no start"))?;
+        self.journal(
+            Operation::Moved {
+                from: from.id.clone(),
+            },
+            &to.id,
+            event_id,
+            &moved.title,
+        );
+        Ok(moved)
+    }
+
+    /// Deletes an event, or a whole recurring series when given its id.
+    /// Cached agendas are dropped so it disappears right away.
+    pub async fn delete_event(&self, calendar: &CalendarConfig, event_id: &str) -> Result<()> {
+        let (api, access_token) = self.authorized_write_api().await?;
+        let url = event_url(&calendar.id, event_id);
+        let before = get_raw(api, &access_token, &url).await?;
+        self.send_write(api, &access_token, Method::DELETE, &url, None, None)
+            .await?;
+        self.journal(
+            Operation::Deleted,
+            &calendar.id,
+            event_id,
+            &title_of(&before),
+        );
+        Ok(())
+    }
+
+    /// Reverses a journaled write. Deleted events are restored by flipping
+    /// them back from cancelled, which Google allows while it keeps them.
+    pub async fn undo(&self, entry: &Entry) -> Result<()> {
+        let (api, access_token) = self.authorized_write_api().await?;
+        let url = event_url(&entry.calendar_id, &entry.event_id);
+        let sent = match &entry.operation {
+            Operation::Created => {
+                self.send_write(api, &access_token, Method::DELETE, &url, None, None)
+                    .await
+            }
+            Operation::Updated { restore } => {
+                let current = get_raw(api, &access_token, &url).await?;
+                self.send_write(
+                    api,
+                    &access_token,
+                    Method::PATCH,
+                    &url,
+                    Some(restore),
+                    Some(&current),
+                )
+                .await
+            }
+            Operation::Deleted => {
+                let body = serde_json::json!({ "status": "confirmed" });
+                self.send_write(api, &access_token, Method::PATCH, &url, Some(&body), None)
+                    .await
+            }
+            Operation::Moved { from } => {
+                let url = move_url(&entry.calendar_id, &entry.event_id, from);
+                self.send_write(api, &access_token, Method::POST, &url, None, None)
+                    .await
+            }
+        };
+        match sent {
+            // Already deleted by hand is as good as undone.
+            Err(CalendarError::NotFound(_)) if matches!(entry.operation, Operation::Created) => {
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    /// Sends a write and drops cached agendas so it shows up right away.
+    /// With `--dry-run` it prints the request instead (PATCHes as a diff
+    /// against `before`) and answers as Google would have.
+    async fn send_write(
+        &self,
+        api: &ApiClient,
+        token: &str,
+        method: Method,
+        url: &str,
+        body: Option<&serde_json::Value>,
+        before: Option<&serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        if self.dry_run {
+            print_dry_run(&method, url, body, before);
+            return Ok(simulate_write(&method, body, before));
+        }
+
+        let response = match (&method, body) {
+            (&Method::PATCH, Some(body)) => api.patch_json(url, token, body).await?,
+            (&Method::DELETE, _) => api.delete(url, token).await?,
+            (_, body) => {
+                let empty = serde_json::json!({});
+                api.post_json(url, token, body.unwrap_or(&empty)).await?
+            }
+        };
+        self.cache.invalidate().await?;
+
+        if response.body.is_empty() {
+            return Ok(serde_json::Value::Null);
+        }
+        serde_json::from_slice(&response.body)
+            .map_err(|e| CalendarError::parse_from("Invalid event response", e))
+    }
+
+    /// Records a write for `callux undo`, unless it was a dry run.
+    fn journal(&self, operation: Operation, calendar_id: &str, event_id: &str, title: &str) {
+        if !self.dry_run {
+            journal::record(operation, calendar_id, event_id, title);
+        }
+    }
+
+    pub async fn stop_channel(&self, channel: &WatchChannel) -> Result<()> {
+        let (api, access_token) = self.authorized_api().await?;
+        let url = api::build_url("/channels/stop", &[]);
+        let body = serde_json::json!({
+            "id": channel.id,
+            "resourceId": channel.resource_id,
+        });
+
+        api.post_json(&url, &access_token, &body).await?;
+        Ok(())
+    }
+
+    /// GETs `url`, sending the stored ETag so unchanged resources come back
+    /// as a body-less 304 and are served from the persistent store instead.
+    async fn conditional_get(&self, api: &ApiClient, token: &str, url: &str) -> Result<String> {
+        let stored = self.cache.get_conditional(url);
+        let if_none_match = stored.as_ref().map(|(etag, _)| etag.as_str());
+        let response = timings::time_async("api", api.get(url, token, if_none_match)).await?;
+
+        if response.not_modified() {
+            if let Some((_, payload)) = stored {
+                return Ok(payload);
+            }
+        }
+
+        let payload = String::from_utf8(response.body.to_vec())
+            .map_err(|e| CalendarError::parse_from("Response is not UTF-8", e))?;
+
+        if let Some(etag) = &response.etag {
+            self.cache.set_conditional(url, etag, &payload);
+        }
 
-        Ok(result.1.items.unwrap_or_default())
+        Ok(payload)
     }
 }
+
+fn event_url(calendar_id: &str, event_id: &str) -> String {
+    api::build_url(
+        &format!(
+            "/calendars/{}/events/{}",
+            api::encode_component(calendar_id),
+            api::encode_component(event_id)
+        ),
+        &[],
+    )
+}
+
+fn parse_event(value: serde_json::Value) -> Result<Event> {
+    serde_json::from_value(value)
+        .map_err(|e| CalendarError::parse_from("Invalid event response", e))
+}
+
+fn print_dry_run(
+    method: &Method,
+    url: &str,
+    body: Option<&serde_json::Value>,
+    before: Option<&serde_json::Value>,
+) {
+    println!("Dry run: {} {}", method, url);
+    let Some(fields) = body.and_then(|body| body.as_object()) else {
+        return;
+    };
+    match before {
+        Some(before) if method == Method::PATCH => {
+            for (key, value) in fields {
+                let old = before.get(key).unwrap_or(&serde_json::Value::Null);
+                println!("  {}: {} -> {}", key, old, value);
+            }
+        }
+        _ if !fields.is_empty() => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(fields).unwrap_or_default()
+            );
+        }
+        _ => {}
+    }
+}
+
+/// What Google would answer to a write: the patched or moved event, or the
+/// inserted one with a placeholder id.
+fn simulate_write(
+    method: &Method,
+    body: Option<&serde_json::Value>,
+    before: Option<&serde_json::Value>,
+) -> serde_json::Value {
+    let mut event = before
+        .cloned()
+        .unwrap_or_else(|| serde_json::json!({ "id": "dry-run" }));
+    if *method != Method::POST || before.is_none() {
+        if let (Some(event), Some(fields)) = (
+            event.as_object_mut(),
+            body.and_then(|body| body.as_object()),
+        ) {
+            event.extend(fields.clone());
+        }
+    }
+    event
+}
+
+fn move_url(from: &str, event_id: &str, to: &str) -> String {
+    api::build_url(
+        &format!(
+            "/calendars/{}/events/{}/move",
+            api::encode_component(from),
+            api::encode_component(event_id)
+        ),
+        &[("destination", to.to_string())],
+    )
+}
+
+/// An event as Google returns it, for journaling what a write replaces.
+async fn get_raw(api: &ApiClient, token: &str, url: &str) -> Result<serde_json::Value> {
+    let response = api.get(url, token, None).await?;
+    serde_json::from_slice(&response.body)
+        .map_err(|e| CalendarError::parse_from("Invalid event response", e))
+}
+
+/// The fields a patch `body` sets, as they were in `before`; `null` clears
+/// those that weren't set.
+fn restore_of(
+    before: &serde_json::Value,
+    body: &serde_json::Map<String, serde_json::Value>,
+) -> serde_json::Value {
+    body.keys()
+        .map(|key| {
+            let value = before.get(key).cloned().unwrap_or(serde_json::Value::Null);
+            (key.clone(), value)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+fn title_of(event: &serde_json::Value) -> String {
+    event["summary"].as_str().unwrap_or("Untitled").to_string()
+}
+
+/// Events callux itself created, changed or looked up are shown whatever the
+/// filters and `[display]` settings say.
+fn unfiltered() -> Result<EventFilter> {
+    EventFilter::new(&FilterConfig::default(), &FilterConfig::default())
+}
+
+/// `existing` guests plus `invitees`; inviting someone already on the list
+/// only updates whether they're optional.
+pub fn with_invitees(existing: &[EventAttendee], invitees: &[Invitee]) -> Vec<EventAttendee> {
+    let mut attendees = existing.to_vec();
+    for invitee in invitees {
+        let known = attendees.iter_mut().find(|attendee| {
+            attendee
+                .email
+                .as_deref()
+                .is_some_and(|email| email.eq_ignore_ascii_case(&invitee.email))
+        });
+        match known {
+            Some(attendee) => attendee.optional = Some(invitee.optional),
+            None => attendees.push(EventAttendee {
+                email: Some(invitee.email.clone()),
+                optional: Some(invitee.optional),
+                ..Default::default()
+            }),
+        }
+    }
+    attendees
+}
+
+fn attendees_value(attendees: &[EventAttendee]) -> Result<serde_json::Value> {
+    serde_json::to_value(attendees).map_err(|e| CalendarError::parse_from("Invalid attendees", e))
+}
+
+/// `conferenceData` asking for a new Meet call. The request id only has to
+/// be unique per request.
+fn new_meet() -> serde_json::Value {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    serde_json::json!({
+        "createRequest": {
+            "requestId": format!("callux-{}-{}", std::process::id(), nanos),
+            "conferenceSolutionKey": { "type": "hangoutsMeet" },
+        }
+    })
+}
+
+/// `start`/`end` of an event body. All-day events end on the exclusive day
+/// after, rounding the duration up to whole days.
+fn event_times(start: Start, duration: chrono::Duration) -> (serde_json::Value, serde_json::Value) {
+    match start {
+        Start::Timed(at) => (
+            serde_json::json!({ "dateTime": at.to_rfc3339() }),
+            serde_json::json!({ "dateTime": (at + duration).to_rfc3339() }),
+        ),
+        Start::AllDay(date) => {
+            // Nearest whole day, as a span of days across a DST change is
+            // an hour short or long.
+            let days = ((duration.num_minutes() + 720) / 1440).max(1);
+            (
+                serde_json::json!({ "date": date.to_string() }),
+                serde_json::json!({ "date": (date + chrono::Duration::days(days)).to_string() }),
+            )
+        }
+    }
+}
+
+/// The video call link: Meet/conference data first, then a URL in the
+/// location, which is where Zoom and Teams links usually end up.
+fn meeting_url(event: &Event) -> Option<String> {
+    if let Some(link) = &event.hangout_link {
+        return Some(link.clone());
+    }
+
+    let video = event
+        .conference_data
+        .as_ref()
+        .and_then(|data| data.entry_points.as_ref())
+        .and_then(|points| {
+            points
+                .iter()
+                .find(|p| p.entry_point_type.as_deref() == Some("video"))
+        })
+        .and_then(|point| point.uri.clone());
+    if video.is_some() {
+        return video;
+    }
+
+    event
+        .location
+        .as_deref()?
+        .split_whitespace()
+        .find(|word| word.starts_with("https://"))
+        .map(|url| url.trim_end_matches([',', ';', ')']).to_string())
+}
+
+/// Lead times of the reminders that pop up on screen; email and SMS
+/// reminders are left to Google.
+fn popup_minutes(reminders: &[EventReminder]) -> Vec<u32> {
+    let mut minutes: Vec<u32> = reminders
+        .iter()
+        .filter(|r| r.method.as_deref() == Some("popup"))
+        .filter_map(|r| u32::try_from(r.minutes?).ok())
+        .collect();
+    minutes.sort_unstable();
+    minutes.dedup();
+    minutes
+}
+
+fn day_aligned_window(
+    zone: Zone,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> (DateTime<Utc>, DateTime<Utc>) {
+    let midnight =
+        |date: chrono::NaiveDate| zone.start_of_day(date).map(|dt| dt.with_timezone(&Utc));
+
+    let start_date = zone.convert(start).date_naive();
+    let end_date = zone.convert(end).date_naive() + chrono::Duration::days(1);
+
+    (
+        midnight(start_date).unwrap_or(*start),
+        midnight(end_date).unwrap_or(*end),
+    )
+}
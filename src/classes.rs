@@ -0,0 +1,65 @@
+use crate::config::ClassRule;
+use crate::output::CalendarEvent;
+use regex::Regex;
+
+/// `display.classes` with their title patterns compiled.
+#[derive(Default)]
+pub struct ClassRules {
+    rules: Vec<(ClassRule, Option<Regex>)>,
+}
+
+impl ClassRules {
+    /// Rules with an invalid pattern are left out with a warning rather than
+    /// failing the agenda; `config validate` reports them.
+    pub fn compile(rules: &[ClassRule]) -> Self {
+        let rules = rules
+            .iter()
+            .filter_map(|rule| {
+                let title = match rule.title.as_deref().map(Regex::new).transpose() {
+                    Ok(title) => title,
+                    Err(e) => {
+                        eprintln!("Warning: Ignoring class rule '{}': {}", rule.class, e);
+                        return None;
+                    }
+                };
+                Some((rule.clone(), title))
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// The class of every rule `event` satisfies, in config order.
+    pub fn classes_for(&self, event: &CalendarEvent) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter(|(rule, title)| {
+                rule.calendar
+                    .as_ref()
+                    .is_none_or(|name| name.eq_ignore_ascii_case(&event.calendar_name))
+                    && title.as_ref().is_none_or(|re| re.is_match(&event.title))
+                    && rule
+                        .meeting
+                        .is_none_or(|meeting| meeting == event.meeting_url.is_some())
+                    && rule.min_guests.is_none_or(|min| event.guests >= min)
+                    && rule.max_guests.is_none_or(|max| event.guests <= max)
+            })
+            .map(|(rule, _)| rule.class.clone())
+            .collect()
+    }
+}
+
+/// Describes each rule that can't apply, for `config validate`.
+pub fn check(rules: &[ClassRule]) -> Vec<(usize, String)> {
+    let mut problems = Vec::new();
+    for (i, rule) in rules.iter().enumerate() {
+        if rule.class.trim().is_empty() {
+            problems.push((i, "Rule has no class".to_string()));
+        } else if rule.class.contains(char::is_whitespace) {
+            problems.push((i, format!("Class '{}' contains spaces", rule.class)));
+        }
+        if let Some(Err(e)) = rule.title.as_deref().map(Regex::new) {
+            problems.push((i, format!("Invalid title pattern: {}", e)));
+        }
+    }
+    problems
+}
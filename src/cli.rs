@@ -1,12 +1,51 @@
 use clap::{Parser, Subcommand};
 
+/// Longest look-ahead `--days` accepts; the daemon and `callux serve` hold
+/// their clients to it too.
+pub const MAX_DAYS: i64 = 366;
+
 #[derive(Parser)]
 #[command(name = "callux")]
 #[command(about = "A fast calendar agenda utility for Waybar and Hyprland")]
 #[command(version = "0.1.0")]
 pub struct Cli {
+    #[arg(
+        long,
+        global = true,
+        help = "Only warn when credential or token files are readable by others"
+    )]
+    pub insecure_ok: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "Report time spent in each startup and fetch phase on stderr"
+    )]
+    pub timings: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "Log Google API requests and responses on stderr, with tokens redacted"
+    )]
+    pub debug_http: bool,
+    #[arg(
+        short,
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Use this configuration file instead of the default"
+    )]
+    pub config: Option<String>,
+    #[arg(
+        short,
+        long,
+        global = true,
+        value_name = "NAME",
+        help = "Apply the [profile.NAME] section of the config (or set CALLUX_PROFILE)"
+    )]
+    pub profile: Option<String>,
+    /// Falls back to `cli.default_command` from the config when omitted.
     #[command(subcommand)]
-    pub command: Commands,
+    pub command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
@@ -17,11 +56,388 @@ pub enum Commands {
         format: OutputFormat,
         #[arg(short, long, help = "Number of events to show")]
         limit: Option<usize>,
-        #[arg(short, long, help = "Days to look ahead")]
+        #[arg(
+            short,
+            long,
+            value_parser = clap::value_parser!(i64).range(1..=MAX_DAYS),
+            help = "Days to look ahead"
+        )]
         days: Option<i64>,
+        #[arg(
+            long,
+            conflicts_with = "days",
+            help = "Hours to look ahead instead of days"
+        )]
+        hours: Option<i64>,
+        #[arg(
+            long,
+            value_name = "TIME",
+            conflicts_with_all = ["days", "hours"],
+            help = "Look ahead up to a time, e.g. 18:00, \"friday 12:00\" or a day alone"
+        )]
+        until: Option<String>,
+        #[arg(
+            long,
+            value_name = "DAYS",
+            help = "Also show past events, from midnight this many days ago (0 for today)"
+        )]
+        past: Option<i64>,
+        #[arg(long, help = "Serve cached events without contacting Google")]
+        offline: bool,
+        #[arg(long, help = "Fetch directly even if `callux daemon` is running")]
+        no_daemon: bool,
+        #[arg(
+            long,
+            help = "Include cancelled events (defaults to display.show_cancelled)"
+        )]
+        show_cancelled: bool,
+        #[arg(
+            long,
+            help = "Include invitations you declined despite display.hide_declined"
+        )]
+        include_declined: bool,
+        #[arg(
+            long,
+            help = "Hide events Gmail added from your email despite display.show_from_gmail"
+        )]
+        hide_gmail: bool,
+        #[arg(
+            long,
+            help = "Show week numbers (defaults to display.show_week_numbers)"
+        )]
+        week_numbers: bool,
+        #[arg(
+            long,
+            value_enum,
+            default_value = "day",
+            help = "Group by week, with event counts, for long ranges"
+        )]
+        group_by: GroupBy,
+        #[arg(
+            long,
+            help = "Show free time between events (defaults to display.show_gaps)"
+        )]
+        gaps: bool,
+        #[arg(
+            long,
+            help = "Style colored titles by your response (defaults to display.color_by_response)"
+        )]
+        color_by_response: bool,
+        #[arg(long, help = "Fail if any calendar couldn't be fetched completely")]
+        strict: bool,
+        #[arg(
+            long,
+            value_name = "DURATION",
+            help = "Hide timed events shorter than this, e.g. 10m (overrides filters.min_duration)"
+        )]
+        min_duration: Option<String>,
+        #[arg(
+            long,
+            value_name = "DURATION",
+            help = "Hide timed events longer than this, e.g. 2h (overrides filters.max_duration)"
+        )]
+        max_duration: Option<String>,
+        #[arg(
+            long,
+            value_name = "REGEX",
+            help = "Only show events whose title or location matches (repeatable)"
+        )]
+        filter: Vec<String>,
+        #[arg(
+            long,
+            value_name = "REGEX",
+            help = "Hide events whose title or location matches (repeatable)"
+        )]
+        exclude: Vec<String>,
+        #[arg(
+            long,
+            value_name = "KEY[=VALUE]",
+            help = "Only show events with this extended property, or with this value (repeatable)"
+        )]
+        property: Vec<String>,
+        #[arg(long, help = "Only show events with other guests")]
+        meetings_only: bool,
+        #[arg(
+            long,
+            conflicts_with = "meetings_only",
+            help = "Only show events without other guests, such as personal blocks"
+        )]
+        blocks_only: bool,
+        #[arg(
+            long,
+            value_enum,
+            help = "Only show events you organized (me) or were invited to (others)"
+        )]
+        organizer: Option<OrganizerFilter>,
+        #[arg(long, help = "Leave out private and confidential events")]
+        hide_private: bool,
+        #[arg(
+            long,
+            conflicts_with = "hide_private",
+            help = "Only show events marked public, e.g. when sharing your screen"
+        )]
+        only_public: bool,
+        #[arg(
+            long,
+            help = "Replace titles, locations, links and calendar names with stand-ins, for screenshots"
+        )]
+        anonymize: bool,
+        #[arg(
+            short,
+            long,
+            value_name = "PATH",
+            help = "Write to a file atomically instead of stdout"
+        )]
+        output: Option<String>,
+    },
+    #[command(about = "Render a JSON array of events from stdin, as printed by --format json-raw")]
+    Fmt {
+        #[arg(short, long, value_enum, default_value = "human")]
+        format: OutputFormat,
+        #[arg(short, long, help = "Number of events to show")]
+        limit: Option<usize>,
+        #[arg(
+            long,
+            value_enum,
+            default_value = "day",
+            help = "Group by week, with event counts, for long ranges"
+        )]
+        group_by: GroupBy,
+        #[arg(
+            long,
+            help = "Replace titles, locations, links and calendar names with stand-ins, for screenshots"
+        )]
+        anonymize: bool,
+        #[arg(
+            short,
+            long,
+            value_name = "PATH",
+            help = "Write to a file atomically instead of stdout"
+        )]
+        output: Option<String>,
+    },
+    #[command(about = "Create an event")]
+    Add {
+        #[arg(help = "Event title (optional with a template that has one)")]
+        title: Option<String>,
+        #[arg(
+            short,
+            long,
+            value_name = "NAME",
+            help = "Start from a [[templates]] preset"
+        )]
+        template: Option<String>,
+        #[arg(
+            long,
+            help = "Start, e.g. \"2024-06-03 14:00\", \"friday 9:30\", or a day alone for all day"
+        )]
+        at: String,
+        #[arg(long, help = "Length, e.g. 45m, 1h30m or 2d (defaults to 1h)")]
+        duration: Option<String>,
+        #[arg(long, help = "Calendar name or id (defaults to the first enabled one)")]
+        calendar: Option<String>,
+        #[arg(short, long, help = "Where it takes place")]
+        location: Option<String>,
+        #[arg(long, help = "Event description")]
+        description: Option<String>,
+        #[arg(long, help = "Add a new Google Meet video call")]
+        meet: bool,
+        #[arg(
+            long,
+            value_name = "BEFORE",
+            help = "Remind this long before, e.g. 10m or 1h (repeatable; replaces the calendar's defaults)"
+        )]
+        remind: Vec<String>,
+        #[arg(long, value_enum, help = "Make it recurring")]
+        repeat: Option<Repeat>,
+        #[arg(
+            long,
+            default_value_t = 1,
+            requires = "repeat",
+            help = "Repeat every N days/weeks/months/years"
+        )]
+        every: u32,
+        #[arg(
+            long,
+            value_name = "DAYS",
+            requires = "repeat",
+            help = "Weekdays it happens on, e.g. mon,wed"
+        )]
+        on: Option<String>,
+        #[arg(
+            long,
+            value_name = "DATE",
+            requires = "repeat",
+            conflicts_with = "count",
+            help = "Last day it may happen on, e.g. 2024-12-20 or \"in 3 months\""
+        )]
+        until: Option<String>,
+        #[arg(long, requires = "repeat", help = "Number of occurrences")]
+        count: Option<u32>,
+        #[arg(
+            long,
+            conflicts_with = "repeat",
+            help = "Raw recurrence rule, e.g. \"FREQ=MONTHLY;BYDAY=1MO\""
+        )]
+        rrule: Option<String>,
+        #[arg(long, value_name = "EMAILS", help = "Comma-separated guests to invite")]
+        invite: Option<String>,
+        #[arg(long, value_name = "EMAILS", help = "Comma-separated optional guests")]
+        optional: Option<String>,
+        #[arg(
+            long,
+            value_enum,
+            help = "Who Google emails about it (defaults to all when inviting, else none)"
+        )]
+        send_updates: Option<SendUpdates>,
+        #[arg(long, help = "Print the request instead of sending it")]
+        dry_run: bool,
+    },
+    #[command(about = "Change an event, with flags or in $EDITOR")]
+    Edit {
+        #[arg(help = "Event id (from the JSON output)")]
+        event: String,
+        #[arg(
+            long,
+            help = "Calendar holding the event (defaults to searching all enabled)"
+        )]
+        calendar: Option<String>,
+        #[arg(long, help = "New title")]
+        title: Option<String>,
+        #[arg(
+            long,
+            help = "New start; keeps the duration unless --duration is given"
+        )]
+        at: Option<String>,
+        #[arg(long, help = "New length, e.g. 45m, 1h30m or 2d")]
+        duration: Option<String>,
+        #[arg(short, long, help = "New location")]
+        location: Option<String>,
+        #[arg(long, help = "New description")]
+        description: Option<String>,
+        #[arg(long, value_name = "EMAILS", help = "Comma-separated guests to add")]
+        invite: Option<String>,
+        #[arg(
+            long,
+            value_name = "EMAILS",
+            help = "Comma-separated optional guests to add"
+        )]
+        optional: Option<String>,
+        #[arg(
+            long,
+            value_enum,
+            help = "Who Google emails about it (defaults to all when inviting, else none)"
+        )]
+        send_updates: Option<SendUpdates>,
+        #[arg(long, help = "Print the request instead of sending it")]
+        dry_run: bool,
+    },
+    #[command(about = "Delete an event")]
+    Delete {
+        #[arg(help = "Event id (from the JSON output)")]
+        event: String,
+        #[arg(
+            long,
+            help = "Calendar holding the event (defaults to searching all enabled)"
+        )]
+        calendar: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "series",
+            help = "Only this occurrence of a recurring event"
+        )]
+        instance: bool,
+        #[arg(long, help = "Every occurrence of a recurring event")]
+        series: bool,
+        #[arg(short, long, help = "Don't ask for confirmation")]
+        yes: bool,
+        #[arg(long, help = "Print the request instead of sending it")]
+        dry_run: bool,
+    },
+    #[command(about = "Copy an event to a new time, e.g. to postpone it")]
+    Dup {
+        #[arg(help = "Event id (from the JSON output)")]
+        event: String,
+        #[arg(
+            long,
+            help = "New start, e.g. \"2024-06-10 14:00\", \"next monday 9:30\", or a day alone for all day"
+        )]
+        at: String,
+        #[arg(long, help = "New length (defaults to the original's)")]
+        duration: Option<String>,
+        #[arg(
+            long,
+            help = "Give the copy a new Google Meet call instead of the original's"
+        )]
+        meet: bool,
+        #[arg(
+            long,
+            help = "Calendar name or id to copy it to (defaults to the original's)"
+        )]
+        to: Option<String>,
+        #[arg(
+            long,
+            help = "Calendar holding the event (defaults to searching all enabled)"
+        )]
+        calendar: Option<String>,
+        #[arg(long, help = "Print the request instead of sending it")]
+        dry_run: bool,
+    },
+    #[command(about = "Move an event to another calendar")]
+    Move {
+        #[arg(help = "Event id (from the JSON output)")]
+        event: String,
+        #[arg(long, help = "Calendar name or id to move it to")]
+        to: String,
+        #[arg(
+            long,
+            help = "Calendar holding the event (defaults to searching all enabled)"
+        )]
+        calendar: Option<String>,
+        #[arg(long, help = "Print the request instead of sending it")]
+        dry_run: bool,
+    },
+    #[command(about = "Accept, decline or tentatively accept an invitation")]
+    Rsvp {
+        #[arg(help = "Event id (from the JSON output)")]
+        event: String,
+        #[arg(value_enum)]
+        response: RsvpResponse,
+        #[arg(long, help = "Note for the organizer")]
+        comment: Option<String>,
+        #[arg(
+            long,
+            help = "Calendar holding the event (defaults to searching all enabled)"
+        )]
+        calendar: Option<String>,
+        #[arg(long, help = "Print the request instead of sending it")]
+        dry_run: bool,
+    },
+    #[command(about = "Create events from a CSV or iCalendar (.ics) file")]
+    Import {
+        #[arg(help = "File to import (.csv or .ics)")]
+        file: String,
+        #[arg(long, help = "Calendar name or id (defaults to the first enabled one)")]
+        calendar: Option<String>,
+        #[arg(short, long, help = "Don't ask for confirmation")]
+        yes: bool,
+        #[arg(long, help = "Print the requests instead of sending them")]
+        dry_run: bool,
+    },
+    #[command(about = "Reverse the last add, edit, delete, move or rsvp")]
+    Undo {
+        #[arg(long, help = "Show the journal of recent changes instead")]
+        list: bool,
+        #[arg(long, help = "Print the request instead of sending it")]
+        dry_run: bool,
     },
     #[command(about = "List available calendars")]
     ListCalendars,
+    #[command(about = "Manage the calendars in the configuration")]
+    Calendars {
+        #[command(subcommand)]
+        action: CalendarsAction,
+    },
     #[command(about = "Configure the application")]
     Config {
         #[command(subcommand)]
@@ -29,12 +445,374 @@ pub enum Commands {
     },
     #[command(about = "Authenticate with Google Calendar")]
     Auth,
+    #[command(about = "Check config, credentials, sign-in, calendars and cache, with fixes")]
+    Doctor,
+    #[command(about = "Refresh the persistent cache ahead of time")]
+    Prefetch {
+        #[arg(
+            short,
+            long,
+            value_parser = clap::value_parser!(i64).range(1..=MAX_DAYS),
+            help = "Days to look ahead (repeatable; defaults to cache.prefetch_days)"
+        )]
+        days: Vec<i64>,
+        #[arg(long, help = "Hours to look ahead (repeatable)")]
+        hours: Vec<i64>,
+        #[arg(
+            long,
+            value_name = "TIME",
+            help = "Look ahead up to a time, as for agenda"
+        )]
+        until: Option<String>,
+        #[arg(
+            long,
+            value_name = "DAYS",
+            help = "Also fetch past days, as for agenda"
+        )]
+        past: Option<i64>,
+    },
+    #[command(about = "Show events added, removed, moved or renamed since the last diff")]
+    Diff {
+        #[arg(short, long, value_enum, default_value = "human")]
+        format: OutputFormat,
+        #[arg(short, long, default_value_t = 7, help = "Days to look ahead")]
+        days: i64,
+        #[arg(long, help = "Compare against cached events without contacting Google")]
+        offline: bool,
+        #[arg(
+            long,
+            help = "Don't replace the snapshot, so the next diff reports the same changes"
+        )]
+        keep: bool,
+    },
+    #[command(about = "Keep auth, connections and cache warm and serve agenda queries")]
+    Daemon,
+    #[command(about = "Serve the agenda as read-only JSON over HTTP")]
+    Serve {
+        #[arg(
+            short,
+            long,
+            value_name = "ADDR",
+            help = "Address to listen on (defaults to serve.listen)"
+        )]
+        listen: Option<String>,
+        #[arg(long, help = "Require this bearer token (defaults to serve.token)")]
+        token: Option<String>,
+    },
+    #[command(about = "Show the agenda in a popup window (wlr-layer-shell)")]
+    Popup {
+        #[arg(short, long, help = "Days to look ahead (defaults to popup.days)")]
+        days: Option<i64>,
+        #[arg(
+            long,
+            help = "Seconds until it closes, 0 for never (defaults to popup.timeout_seconds)"
+        )]
+        timeout: Option<u32>,
+    },
+    #[command(about = "Copy an event's meeting link to the Wayland clipboard")]
+    CopyLink {
+        #[arg(
+            default_value = "next",
+            help = "Event id, or \"next\" for the running or next event with a link"
+        )]
+        event: String,
+        #[arg(short, long, default_value_t = 30, help = "Days to look ahead")]
+        days: i64,
+    },
+    #[command(about = "Open an event in Google Calendar in the browser")]
+    Open {
+        #[arg(
+            default_value = "next",
+            help = "Event id, or \"next\" for the running or next event"
+        )]
+        event: String,
+        #[arg(
+            long,
+            help = "Calendar holding the event (defaults to searching all enabled)"
+        )]
+        calendar: Option<String>,
+        #[arg(short, long, default_value_t = 30, help = "Days to look ahead")]
+        days: i64,
+        #[arg(long, help = "Print the link instead of opening it")]
+        print: bool,
+    },
+    #[command(about = "Show everything about an event: times, organizer, guests, notes")]
+    Show {
+        #[arg(
+            default_value = "next",
+            help = "Event id, or \"next\" for the running or next event"
+        )]
+        event: String,
+        #[arg(
+            long,
+            help = "Calendar holding the event (defaults to searching all enabled)"
+        )]
+        calendar: Option<String>,
+        #[arg(short, long, default_value_t = 30, help = "Days to look ahead")]
+        days: i64,
+    },
+    #[command(about = "Show an event's time in other timezones")]
+    Tz {
+        #[arg(
+            default_value = "next",
+            help = "Event id, or \"next\" for the running or next event"
+        )]
+        event: String,
+        #[arg(
+            long,
+            required = true,
+            value_delimiter = ',',
+            value_name = "ZONES",
+            help = "IANA timezones, comma-separated, e.g. Europe/Berlin,America/New_York"
+        )]
+        to: Vec<String>,
+        #[arg(
+            long,
+            help = "Calendar holding the event (defaults to searching all enabled)"
+        )]
+        calendar: Option<String>,
+        #[arg(short, long, default_value_t = 30, help = "Days to look ahead")]
+        days: i64,
+    },
+    #[command(about = "List an event's attachments, or download its Drive files")]
+    Attachments {
+        #[arg(help = "Event id (from the JSON output)")]
+        event: String,
+        #[arg(
+            long,
+            help = "Calendar holding the event (defaults to searching all enabled)"
+        )]
+        calendar: Option<String>,
+        #[arg(
+            long,
+            value_name = "DIR",
+            help = "Save the files here; Google Docs, Sheets and Slides as PDF"
+        )]
+        download: Option<String>,
+    },
+    #[command(about = "List upcoming birthdays with ages and countdowns")]
+    Birthdays {
+        #[arg(short, long, default_value_t = 30, help = "Days to look ahead")]
+        days: i64,
+    },
+    #[command(about = "Share when you're busy, without event details")]
+    Availability {
+        #[arg(short, long, default_value_t = 1, help = "Weeks to look ahead")]
+        weeks: i64,
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Write an iCalendar file of busy blocks (\"-\" for stdout) instead of text"
+        )]
+        ics: Option<String>,
+    },
+    #[command(about = "Wait until shortly before an event, then notify or run a command")]
+    Remind {
+        #[arg(
+            default_value = "next",
+            help = "Event id, or \"next\" for the next event to start"
+        )]
+        event: String,
+        #[arg(
+            long,
+            default_value = "5m",
+            help = "How long before the start to fire, e.g. 5m or 1h"
+        )]
+        before: String,
+        #[arg(
+            long,
+            help = "Calendar holding the event (defaults to searching all enabled)"
+        )]
+        calendar: Option<String>,
+        #[arg(
+            short,
+            long,
+            default_value_t = 1,
+            help = "Days to look ahead for the next event"
+        )]
+        days: i64,
+        #[arg(
+            long,
+            help = "Run this shell command instead of notifying, with CALLUX_EVENT_* set"
+        )]
+        command: Option<String>,
+    },
+    #[command(about = "Print the time until the next event starts, for scripts")]
+    UntilNext {
+        #[arg(long, value_enum, default_value = "seconds")]
+        format: CountdownFormat,
+        #[arg(short, long, default_value_t = 7, help = "Days to look ahead")]
+        days: i64,
+        #[arg(long, help = "Serve cached events without contacting Google")]
+        offline: bool,
+    },
+    #[command(about = "Set up systemd units for the daemon and prefetch")]
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+    #[command(about = "Measure agenda fetch latency")]
+    Bench {
+        #[arg(short = 'n', long, default_value_t = 10, help = "Iterations per mode")]
+        iterations: usize,
+        #[arg(short, long, default_value_t = 7, help = "Days to look ahead")]
+        days: i64,
+        #[arg(long, help = "Only measure cache-served fetches")]
+        cached_only: bool,
+    },
+    #[command(about = "Inspect and manage the event cache")]
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    #[command(about = "Update callux to the latest release from GitHub")]
+    SelfUpdate {
+        #[arg(long, help = "Only report whether a newer release is available")]
+        check_only: bool,
+    },
+    #[command(about = "Collect diagnostics for a bug report")]
+    Debug {
+        #[command(subcommand)]
+        action: DebugAction,
+    },
+    #[command(about = "Print a shell completion script")]
+    Completions {
+        #[arg(value_enum)]
+        shell: CompletionShell,
+    },
+    /// Lists calendar names, templates or cached event ids for the
+    /// completion scripts, one per line with a description after a tab.
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[arg(value_enum)]
+        kind: CompleteKind,
+    },
+    #[command(about = "Print the JSON Schema of a structured output")]
+    Schema {
+        #[arg(value_enum, help = "Which output to describe")]
+        output: SchemaKind,
+    },
+}
+
+impl Commands {
+    /// Commands that stop cleanly on Ctrl-C/SIGTERM by themselves, rather
+    /// than being cancelled wherever they are.
+    pub fn handles_signals(&self) -> bool {
+        matches!(self, Commands::Daemon)
+    }
+
+    /// Whether the command was asked for `--format json`, so its errors
+    /// should be JSON too.
+    pub fn wants_json(&self) -> bool {
+        matches!(
+            self,
+            Commands::Agenda {
+                format: OutputFormat::Json | OutputFormat::JsonRaw,
+                ..
+            } | Commands::Fmt {
+                format: OutputFormat::Json | OutputFormat::JsonRaw,
+                ..
+            } | Commands::Diff {
+                format: OutputFormat::Json | OutputFormat::JsonRaw,
+                ..
+            }
+        )
+    }
+}
+
+#[derive(Subcommand)]
+pub enum CalendarsAction {
+    #[command(about = "Show configured calendars")]
+    List,
+    #[command(about = "Add a calendar (pick from your account if no ID is given)")]
+    Add {
+        #[arg(help = "Calendar ID, or part of its title in your account")]
+        id: Option<String>,
+        #[arg(long, help = "Display name (defaults to the calendar's title)")]
+        name: Option<String>,
+        #[arg(
+            long,
+            help = "Color as #RRGGBB (defaults to the calendar's Google color)"
+        )]
+        color: Option<String>,
+        #[arg(long, help = "Add the calendar disabled")]
+        disabled: bool,
+    },
+    #[command(about = "Add newly discovered calendars (disabled) and flag removed ones")]
+    Sync,
+    #[command(about = "Remove a calendar")]
+    Remove {
+        #[arg(help = "Calendar ID or name")]
+        id: String,
+    },
+    #[command(about = "Include a calendar in the agenda")]
+    Enable {
+        #[arg(help = "Calendar ID or name")]
+        id: String,
+    },
+    #[command(about = "Exclude a calendar from the agenda")]
+    Disable {
+        #[arg(help = "Calendar ID or name")]
+        id: String,
+    },
+    #[command(about = "Change a calendar's display name")]
+    Rename {
+        #[arg(help = "Calendar ID or name")]
+        id: String,
+        #[arg(help = "New display name")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ServiceAction {
+    #[command(about = "Write systemd units for the daemon socket and prefetch timer")]
+    Install {
+        #[arg(
+            long,
+            help = "Install user units (required; system units are not supported)"
+        )]
+        user: bool,
+        #[arg(long, help = "Print the units instead of writing them")]
+        print: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+    #[command(about = "Show cache hit rates, entries and sizes")]
+    Stats,
+    #[command(about = "Remove all cached events and ETags")]
+    Clear,
+    #[command(about = "Remove expired entries from the persistent cache")]
+    Prune,
+}
+
+#[derive(Subcommand)]
+pub enum DebugAction {
+    #[command(
+        about = "Write version, paths, redacted config, cache stats and the last API error as JSON"
+    )]
+    Bundle {
+        #[arg(
+            short,
+            long,
+            value_name = "PATH",
+            help = "Write to a file instead of stdout"
+        )]
+        output: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
 pub enum ConfigAction {
     #[command(about = "Show current configuration")]
     Show,
+    #[command(about = "Print a configuration value")]
+    Get {
+        #[arg(help = "Configuration key, e.g. display.max_events")]
+        key: String,
+    },
     #[command(about = "Set a configuration value")]
     Set {
         #[arg(help = "Configuration key")]
@@ -42,11 +820,32 @@ pub enum ConfigAction {
         #[arg(help = "Configuration value")]
         value: String,
     },
-    #[command(about = "Initialize default configuration")]
-    Init,
+    #[command(about = "Reset a configuration value to its default")]
+    Unset {
+        #[arg(help = "Configuration key")]
+        key: String,
+    },
+    #[command(about = "Open the configuration file in $EDITOR")]
+    Edit,
+    #[command(about = "Print the paths of the files callux uses")]
+    Path,
+    #[command(about = "Check the configuration for problems")]
+    Validate {
+        #[arg(long, help = "Also check that calendars are reachable via the API")]
+        online: bool,
+    },
+    #[command(about = "Set up credentials, sign in and choose calendars")]
+    Init {
+        #[arg(
+            long,
+            help = "Write a default config and sample credentials without prompting"
+        )]
+        non_interactive: bool,
+    },
 }
 
-#[derive(clap::ValueEnum, Clone)]
+#[derive(clap::ValueEnum, Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum OutputFormat {
     #[value(name = "json")]
     Json,
@@ -54,4 +853,128 @@ pub enum OutputFormat {
     Human,
     #[value(name = "colored")]
     Colored,
+    /// The events themselves, in the shape `callux fmt` reads back.
+    #[value(name = "json-raw")]
+    JsonRaw,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    #[value(name = "day")]
+    Day,
+    /// Day groups under a heading per week, with its event count.
+    #[value(name = "week")]
+    Week,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum CountdownFormat {
+    /// Whole seconds, e.g. `4980`.
+    #[value(name = "seconds")]
+    Seconds,
+    /// e.g. `1h23m`.
+    #[value(name = "human")]
+    Human,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum CompletionShell {
+    #[value(name = "bash")]
+    Bash,
+    #[value(name = "zsh")]
+    Zsh,
+    #[value(name = "fish")]
+    Fish,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum CompleteKind {
+    #[value(name = "calendars")]
+    Calendars,
+    #[value(name = "templates")]
+    Templates,
+    /// Events in the cached agenda; completing never waits on Google.
+    #[value(name = "events")]
+    Events,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum SchemaKind {
+    /// The daemon's `get_agenda` result and `callux serve`'s `/agenda`.
+    #[value(name = "events")]
+    Events,
+    /// `--format json-raw`, which `callux fmt` reads.
+    #[value(name = "raw")]
+    Raw,
+    /// `agenda --format json`.
+    #[value(name = "waybar")]
+    Waybar,
+    /// What `--format json` commands print when they fail.
+    #[value(name = "error")]
+    Error,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum RsvpResponse {
+    #[value(name = "accept")]
+    Accept,
+    #[value(name = "decline")]
+    Decline,
+    #[value(name = "tentative")]
+    Tentative,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum SendUpdates {
+    #[value(name = "all")]
+    All,
+    #[value(name = "external")]
+    External,
+    #[value(name = "none")]
+    None,
+}
+
+impl SendUpdates {
+    /// The API's `sendUpdates` value.
+    pub fn as_param(self) -> &'static str {
+        match self {
+            SendUpdates::All => "all",
+            SendUpdates::External => "externalOnly",
+            SendUpdates::None => "none",
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum OrganizerFilter {
+    /// Events you organized, which you can move yourself.
+    #[value(name = "me")]
+    Me,
+    /// Events someone else invited you to.
+    #[value(name = "others")]
+    Others,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum Repeat {
+    #[value(name = "daily")]
+    Daily,
+    #[value(name = "weekly")]
+    Weekly,
+    #[value(name = "monthly")]
+    Monthly,
+    #[value(name = "yearly")]
+    Yearly,
+}
+
+impl Repeat {
+    /// The RRULE `FREQ` value.
+    pub fn as_frequency(self) -> &'static str {
+        match self {
+            Repeat::Daily => "DAILY",
+            Repeat::Weekly => "WEEKLY",
+            Repeat::Monthly => "MONTHLY",
+            Repeat::Yearly => "YEARLY",
+        }
+    }
 }
@@ -0,0 +1,201 @@
+use crate::cli::{Cli, CompletionShell};
+use clap::CommandFactory;
+
+/// Options whose values `callux __complete` can list, with what it lists.
+const DYNAMIC_OPTIONS: &[(&str, &str)] =
+    &[("--calendar", "calendars"), ("--template", "templates")];
+
+/// A completion script for `shell`. Subcommands and flags come from the
+/// CLI definition; calendar names, templates and event ids are looked up
+/// when you press Tab, through `callux __complete`.
+pub fn script(shell: CompletionShell) -> String {
+    let cli = Cli::command();
+    let commands: Vec<&clap::Command> = cli
+        .get_subcommands()
+        .filter(|command| !command.is_hide_set())
+        .collect();
+    // Commands taking an event id as their first argument, e.g. `edit`.
+    let event_commands: Vec<&str> = commands
+        .iter()
+        .filter(|command| {
+            command
+                .get_positionals()
+                .next()
+                .is_some_and(|arg| arg.get_id() == "event")
+        })
+        .map(|command| command.get_name())
+        .collect();
+
+    match shell {
+        CompletionShell::Bash | CompletionShell::Zsh => {
+            let mut out = String::new();
+            if matches!(shell, CompletionShell::Zsh) {
+                out.push_str("autoload -U +X bashcompinit && bashcompinit\n\n");
+            }
+            out.push_str(&bash_script(&cli, &commands, &event_commands));
+            out
+        }
+        CompletionShell::Fish => fish_script(&cli, &commands, &event_commands),
+    }
+}
+
+fn long_flags(command: &clap::Command) -> Vec<String> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .filter_map(|arg| arg.get_long())
+        .map(|long| format!("--{}", long))
+        .collect()
+}
+
+/// The global flags followed by a value, e.g. `--profile work`, in the
+/// `a|b` form of a `case` pattern.
+fn valued_flags(cli: &clap::Command) -> String {
+    cli.get_arguments()
+        .filter(|arg| !arg.is_positional() && arg.get_action().takes_values())
+        .flat_map(|arg| {
+            let long = arg.get_long().map(|long| format!("--{}", long));
+            let short = arg.get_short().map(|short| format!("-{}", short));
+            long.into_iter().chain(short)
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+fn bash_script(
+    cli: &clap::Command,
+    commands: &[&clap::Command],
+    event_commands: &[&str],
+) -> String {
+    let names: Vec<&str> = commands.iter().map(|command| command.get_name()).collect();
+    let global = long_flags(cli).join(" ");
+
+    let mut flags = String::new();
+    for command in commands {
+        let mut words = long_flags(command);
+        words.extend(
+            command
+                .get_subcommands()
+                .map(|sub| sub.get_name().to_string()),
+        );
+        flags.push_str(&format!(
+            "        {}) opts=\"{}\" ;;\n",
+            command.get_name(),
+            words.join(" ")
+        ));
+    }
+
+    let mut dynamic = String::new();
+    for (option, kind) in DYNAMIC_OPTIONS {
+        dynamic.push_str(&format!(
+            "        {}) _callux_dynamic {}; return ;;\n",
+            option, kind
+        ));
+    }
+
+    format!(
+        r#"_callux_dynamic() {{
+    local IFS=$'\n'
+    COMPREPLY=($(compgen -W "$(callux __complete "$1" 2>/dev/null | cut -f1)" -- "$cur"))
+}}
+
+_callux() {{
+    local cur=${{COMP_WORDS[COMP_CWORD]}} prev=${{COMP_WORDS[COMP_CWORD-1]}}
+    local sub= subi=0 opts i
+
+    # The subcommand is the first word that is neither a global flag nor
+    # the value after one, as in `callux --profile work agenda`.
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${{COMP_WORDS[i]}}" in
+            {valued}) ((i++)) ;;
+            -*) ;;
+            *) sub=${{COMP_WORDS[i]}} subi=$i; break ;;
+        esac
+    done
+
+    case "$prev" in
+{dynamic}    esac
+    if [[ -z $sub ]]; then
+        COMPREPLY=($(compgen -W "{names} {global}" -- "$cur"))
+        return
+    fi
+    if [[ $cur != -* ]]; then
+        case "$sub" in
+            {events}) [[ $COMP_CWORD -eq $((subi + 1)) ]] && _callux_dynamic events && return ;;
+            calendars)
+                case "${{COMP_WORDS[subi+1]}}" in
+                    enable|disable|remove|rename) [[ $COMP_CWORD -eq $((subi + 2)) ]] && _callux_dynamic calendars && return ;;
+                esac ;;
+        esac
+    fi
+
+    case "$sub" in
+{flags}    esac
+    COMPREPLY=($(compgen -W "$opts {global}" -- "$cur"))
+}}
+
+complete -F _callux callux
+"#,
+        dynamic = dynamic,
+        valued = valued_flags(cli),
+        names = names.join(" "),
+        events = event_commands.join("|"),
+        flags = flags,
+        global = global,
+    )
+}
+
+fn fish_script(
+    cli: &clap::Command,
+    commands: &[&clap::Command],
+    event_commands: &[&str],
+) -> String {
+    let mut out = String::from("complete -c callux -f\n");
+    for flag in long_flags(cli) {
+        out.push_str(&format!("complete -c callux -l {}\n", &flag[2..]));
+    }
+
+    for command in commands {
+        let about = command
+            .get_about()
+            .map(|about| about.to_string().replace('\'', "\\'"))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "complete -c callux -n __fish_use_subcommand -a {} -d '{}'\n",
+            command.get_name(),
+            about
+        ));
+        let seen = format!("'__fish_seen_subcommand_from {}'", command.get_name());
+        for flag in long_flags(command) {
+            out.push_str(&format!(
+                "complete -c callux -n {} -l {}\n",
+                seen,
+                &flag[2..]
+            ));
+        }
+        for sub in command.get_subcommands() {
+            out.push_str(&format!(
+                "complete -c callux -n {} -a {}\n",
+                seen,
+                sub.get_name()
+            ));
+        }
+    }
+
+    for (option, kind) in DYNAMIC_OPTIONS {
+        out.push_str(&format!(
+            "complete -c callux -l {} -x -a '(callux __complete {} 2>/dev/null)'\n",
+            &option[2..],
+            kind
+        ));
+    }
+    // Fish shows the title after the tab as a description.
+    out.push_str(&format!(
+        "complete -c callux -n '__fish_seen_subcommand_from {}' -a '(callux __complete events 2>/dev/null)'\n",
+        event_commands.join(" ")
+    ));
+    out.push_str(
+        "complete -c callux -n '__fish_seen_subcommand_from enable disable remove rename' -a '(callux __complete calendars 2>/dev/null)'\n",
+    );
+    out
+}
@@ -1,25 +1,144 @@
+use crate::cli::OutputFormat;
+use crate::{fuzzy, migrate, security};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const ENV_PREFIX: &str = "CALLUX_";
+
+pub const DEFAULT_CALENDAR_COLOR: &str = "#1976d2";
+
+/// Set once from `--config` before anything loads the configuration.
+static PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+/// Set once from `--profile`/`CALLUX_PROFILE`, likewise.
+static PROFILE: OnceLock<String> = OnceLock::new();
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Schema version of the file; older files are migrated on load.
+    #[serde(default = "default_version")]
+    pub version: u32,
     pub auth: AuthConfig,
     pub cache: CacheConfig,
     pub display: DisplayConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
     pub calendars: Vec<CalendarConfig>,
+    /// Short names for calendar ids (`w = "c_1f9...@group.calendar.google.com"`),
+    /// accepted wherever a calendar is named.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    #[serde(default)]
+    pub filters: FilterConfig,
+    #[serde(default)]
+    pub cli: CliConfig,
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub serve: ServeConfig,
+    #[serde(default)]
+    pub mqtt: MqttConfig,
+    #[serde(default)]
+    pub tracking: TrackingConfig,
+    #[serde(default)]
+    pub dnd: DndConfig,
+    #[serde(default)]
+    pub inhibit: InhibitConfig,
+    #[serde(default)]
+    pub popup: PopupConfig,
+    #[serde(default)]
+    pub snapshot: SnapshotConfig,
+    /// Presets for `callux add --template`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<TemplateConfig>,
+    /// Named overlays (`[profile.work]`) merged over the settings above when
+    /// selected with `--profile` or `CALLUX_PROFILE`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profile: BTreeMap<String, toml::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthConfig {
     pub credentials_path: String,
+    /// Defaults to the XDG data directory; tokens are state, not config.
+    #[serde(default = "default_token_cache_path")]
     pub token_cache_path: String,
+    #[serde(default = "default_auth_timeout_seconds")]
+    pub timeout_seconds: u64,
+    #[serde(default)]
+    pub allow_insecure_permissions: bool,
+}
+
+/// Files written before `version` existed.
+fn default_version() -> u32 {
+    1
+}
+
+/// Where tokens used to live before they moved out of the config directory.
+pub const LEGACY_TOKEN_CACHE_PATH: &str = "~/.config/callux/token.json";
+
+/// `$XDG_DATA_HOME/callux/token.json`, written with `~` when under home.
+pub fn default_token_cache_path() -> String {
+    let Some(data_dir) = dirs::data_dir() else {
+        return LEGACY_TOKEN_CACHE_PATH.to_string();
+    };
+    let path = data_dir.join("callux").join("token.json");
+
+    match dirs::home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.display().to_string(),
+    }
+}
+
+fn default_auth_timeout_seconds() -> u64 {
+    300
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheConfig {
     pub ttl_seconds: u64,
     pub max_entries: u64,
+    #[serde(default = "default_true")]
+    pub persistent: bool,
+    /// Look-ahead ranges (in days) refreshed by `callux prefetch`.
+    #[serde(default = "default_prefetch_days")]
+    pub prefetch_days: Vec<i64>,
+    /// TTL for ranges of a day or less, which change more often.
+    #[serde(default)]
+    pub today_ttl_seconds: Option<u64>,
+    /// Up to this many seconds are shaved off the TTL at random per process.
+    #[serde(default)]
+    pub ttl_jitter_seconds: u64,
+    #[serde(default)]
+    pub strategy: CacheStrategy,
+    /// Database location; defaults to the XDG cache directory.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheStrategy {
+    /// Expired entries are refetched before answering.
+    #[default]
+    Strict,
+    /// Expired entries are served immediately while a detached
+    /// `callux prefetch` refreshes them in the background.
+    Swr,
+}
+
+fn default_prefetch_days() -> Vec<i64> {
+    vec![7]
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +146,490 @@ pub struct DisplayConfig {
     pub max_events: usize,
     pub date_format: String,
     pub timezone: String,
+    #[serde(default)]
+    pub working_hours: Option<WorkingHoursConfig>,
+    /// Drop timed events that don't overlap `working_hours`.
+    #[serde(default)]
+    pub hide_outside_working_hours: bool,
+    #[serde(default)]
+    pub week_start: WeekStart,
+    /// Print a "Week N" heading whenever the agenda enters a new week.
+    #[serde(default)]
+    pub show_week_numbers: bool,
+    /// Where `show_week_numbers` puts the number.
+    #[serde(default)]
+    pub week_number_placement: WeekNumberPlacement,
+    /// Keep cancelled events (including deleted occurrences of recurring
+    /// ones), marked as such, instead of dropping them.
+    #[serde(default)]
+    pub show_cancelled: bool,
+    /// Drop invitations you declined.
+    #[serde(default)]
+    pub hide_declined: bool,
+    /// List events marked "Free" in Google. They never count as busy.
+    #[serde(default = "default_true")]
+    pub show_free: bool,
+    #[serde(default = "default_true")]
+    pub show_out_of_office: bool,
+    #[serde(default = "default_true")]
+    pub show_focus_time: bool,
+    /// "Home"/"Office" entries, which Google adds as all-day events.
+    #[serde(default)]
+    pub show_working_location: bool,
+    /// Flights, reservations and the like that Gmail adds from your email,
+    /// which often duplicate entries made by hand.
+    #[serde(default = "default_true")]
+    pub show_from_gmail: bool,
+    /// Follow start times with the time in the event's own timezone when
+    /// it was scheduled in another one.
+    #[serde(default)]
+    pub show_event_timezone: bool,
+    /// Put a "— 2h free —" line between timed events with room between
+    /// them in the text formats.
+    #[serde(default)]
+    pub show_gaps: bool,
+    /// In colored output, style titles by your response: invitations
+    /// awaiting one highlighted, tentative ones dimmed, declined ones struck
+    /// through and events you organized in bold.
+    #[serde(default)]
+    pub color_by_response: bool,
+    /// Warn about events starting less than this many minutes after the
+    /// previous one ends; 0 warns only about ones that touch or overlap.
+    #[serde(default)]
+    pub back_to_back_minutes: Option<u32>,
+    /// Leave out events that are already over, even when they come from a
+    /// cache filled earlier; ones in progress stay.
+    #[serde(default)]
+    pub hide_past: bool,
+    /// Which events to show by their Google visibility. A calendar's own
+    /// `visibility` can only make this stricter.
+    #[serde(default)]
+    pub visibility: VisibilityPolicy,
+    /// `[[display.classes]]` rules adding Waybar classes for the next event.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<ClassRule>,
+}
+
+/// Adds `class` to the Waybar output when the next event matches every
+/// condition given; conditions left out match anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClassRule {
+    pub class: String,
+    /// Calendar name as shown in the agenda, ignoring case.
+    pub calendar: Option<String>,
+    /// Regex matched against the title.
+    pub title: Option<String>,
+    /// Whether the event has a video call link.
+    pub meeting: Option<bool>,
+    pub min_guests: Option<usize>,
+    pub max_guests: Option<usize>,
+}
+
+/// Ordered from most to least revealing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VisibilityPolicy {
+    #[default]
+    All,
+    /// Private and confidential events show as "Busy", without details.
+    Mask,
+    /// Private and confidential events are left out.
+    HidePrivate,
+    /// Only events explicitly marked public are shown.
+    OnlyPublic,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    /// ISO 8601 weeks and week numbers.
+    #[default]
+    Monday,
+    /// US-style weeks; week 1 is the one containing January 1st.
+    Sunday,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekNumberPlacement {
+    /// A "Week N" line before the first day of each week.
+    #[default]
+    Heading,
+    /// "Week N" after every day header, e.g. "Monday, June 02, 2025 · Week 23".
+    Day,
+}
+
+/// `"09:00-18:00"` for Monday to Friday, or a table such as
+/// `{ mon = "09:00-17:00", fri = "09:00-13:00" }` naming each working day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WorkingHoursConfig {
+    Daily(String),
+    Weekly(BTreeMap<String, String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Upper bound on result pages followed per calendar, as a safety net
+    /// against runaway pagination.
+    pub max_pages: u32,
+    /// Events asked for per result page (the API allows 1 to 2500).
+    pub max_results: u32,
+    /// Extra attempts for 429/5xx responses and dropped connections.
+    pub retry_attempts: u32,
+    pub retry_base_delay_ms: u64,
+    /// Minimum gap between consecutive API requests.
+    pub request_spacing_ms: u64,
+    /// Give up (and fall back to cached data) rather than wait longer than
+    /// this for a Retry-After.
+    pub max_retry_after_seconds: u64,
+    /// PEM file with extra root certificates trusted alongside the system's.
+    pub ca_bundle: Option<String>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            max_pages: 20,
+            max_results: 250,
+            retry_attempts: 3,
+            retry_base_delay_ms: 250,
+            request_spacing_ms: 100,
+            max_retry_after_seconds: 30,
+            ca_bundle: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CliConfig {
+    /// Arguments run when `callux` is invoked without a subcommand.
+    pub default_command: String,
+}
+
+impl Default for CliConfig {
+    fn default() -> Self {
+        Self {
+            default_command: "agenda".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Defaults to `$XDG_RUNTIME_DIR/callux/daemon.sock`.
+    pub socket_path: Option<String>,
+    /// How often `callux daemon` refetches; defaults to just under
+    /// `cache.ttl_seconds` so served entries never expire.
+    pub refresh_interval_seconds: Option<u64>,
+    /// Publish `org.callux.Calendar` on the session bus (needs the `dbus`
+    /// feature).
+    pub dbus: bool,
+    /// Public HTTPS address Google sends change notifications to; it must
+    /// forward to `push_listen`. Without it the daemon only polls.
+    pub push_url: Option<String>,
+    pub push_listen: String,
+    /// Send `SIGRTMIN+N` to Waybar when the agenda changes, for a module
+    /// configured with `"signal": N`.
+    pub waybar_signal: Option<u8>,
+    /// Shell command run when the agenda changes, for other bars.
+    pub refresh_command: Option<String>,
+    /// Serve Prometheus metrics at `http://<metrics_listen>/metrics`.
+    pub metrics_listen: Option<String>,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            socket_path: None,
+            refresh_interval_seconds: None,
+            dbus: false,
+            push_url: None,
+            push_listen: "127.0.0.1:8787".to_string(),
+            waybar_signal: None,
+            refresh_command: None,
+            metrics_listen: None,
+        }
+    }
+}
+
+/// Reminders shown by `callux daemon` at each event's `reminder_minutes`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// Needs the `notifications` feature.
+    pub enabled: bool,
+    pub snooze_minutes: u32,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            snooze_minutes: 5,
+        }
+    }
+}
+
+/// Shell commands `callux daemon` runs on event lifecycle changes. Each gets
+/// the event (or, for `agenda_changed`, the event list) as JSON on stdin and
+/// `CALLUX_HOOK`/`CALLUX_EVENT_*` in the environment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    pub starting_soon: Option<String>,
+    pub starting_soon_minutes: u32,
+    pub started: Option<String>,
+    pub ended: Option<String>,
+    pub agenda_changed: Option<String>,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            starting_soon: None,
+            starting_soon_minutes: 5,
+            started: None,
+            ended: None,
+            agenda_changed: None,
+        }
+    }
+}
+
+impl HooksConfig {
+    pub fn any(&self) -> bool {
+        self.starting_soon.is_some()
+            || self.started.is_some()
+            || self.ended.is_some()
+            || self.agenda_changed.is_some()
+    }
+}
+
+/// `callux serve`, the read-only HTTP API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServeConfig {
+    pub listen: String,
+    /// Bearer token required on every request when set.
+    pub token: Option<String>,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        Self {
+            listen: "127.0.0.1:8686".to_string(),
+            token: None,
+        }
+    }
+}
+
+/// MQTT publishing from `callux daemon`; off until `host` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    /// Needs the `mqtt` feature.
+    pub host: Option<String>,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub client_id: String,
+    /// Prefix of the `status`, `next` and `agenda` topics.
+    pub topic: String,
+    /// Publish Home Assistant discovery configs under `discovery_prefix`.
+    pub discovery: bool,
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: None,
+            port: 1883,
+            username: None,
+            password: None,
+            client_id: "callux".to_string(),
+            topic: "callux".to_string(),
+            discovery: true,
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+/// Automatic time tracking of meetings by `callux daemon`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackingConfig {
+    /// Off when unset.
+    pub backend: Option<TrackingBackend>,
+    /// Calendar names to track; all calendars when empty.
+    pub calendars: Vec<String>,
+    /// Extra tags on every entry, after the event title.
+    pub tags: Vec<String>,
+    pub activitywatch_url: String,
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        Self {
+            backend: None,
+            calendars: Vec::new(),
+            tags: vec!["meeting".to_string()],
+            activitywatch_url: "http://localhost:5600".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackingBackend {
+    /// `timew start <title> <tags>` / `timew stop <title>`.
+    Timewarrior,
+    /// An interval in the `callux-meetings_<host>` bucket when the meeting ends.
+    Activitywatch,
+}
+
+/// Do-not-disturb during calls, switched by `callux daemon`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DndConfig {
+    /// Notification daemon to switch; off when unset.
+    pub backend: Option<DndBackend>,
+    /// Calendar names whose calls silence notifications; all calendars when
+    /// empty.
+    pub calendars: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DndBackend {
+    /// The `do-not-disturb` mode via `makoctl mode`.
+    Mako,
+    /// `dunstctl set-paused`.
+    Dunst,
+    /// `swaync-client --dnd-on`/`--dnd-off`.
+    Swaync,
+}
+
+/// Idle/sleep inhibition during calls by `callux daemon`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InhibitConfig {
+    pub enabled: bool,
+    /// Calendar names whose calls hold the lock; all calendars when empty.
+    pub calendars: Vec<String>,
+    /// `systemd-inhibit --what` locks to take.
+    pub what: String,
+}
+
+impl Default for InhibitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            calendars: Vec::new(),
+            what: "idle:sleep".to_string(),
+        }
+    }
+}
+
+/// Window shown by `callux popup`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PopupConfig {
+    pub position: PopupPosition,
+    /// Distance in pixels from the anchored screen edges.
+    pub margin: i32,
+    pub width: i32,
+    /// The list scrolls beyond this height.
+    pub max_height: i32,
+    /// Close automatically after this long; 0 keeps it open until Escape.
+    pub timeout_seconds: u32,
+    pub days: i64,
+}
+
+impl Default for PopupConfig {
+    fn default() -> Self {
+        Self {
+            position: PopupPosition::TopRight,
+            margin: 8,
+            width: 360,
+            max_height: 480,
+            timeout_seconds: 15,
+            days: 7,
+        }
+    }
+}
+
+/// A file `callux prefetch` and `callux daemon` keep up to date with the
+/// formatted agenda, for widgets that read a file instead of running a
+/// command (eww, conky).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    /// Nothing is written while this is unset.
+    pub path: Option<String>,
+    pub format: OutputFormat,
+    pub days: i64,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            format: OutputFormat::Json,
+            days: 7,
+        }
+    }
+}
+
+/// A `[[templates]]` preset for `callux add --template NAME`. Flags given
+/// on the command line win; guests from both are invited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemplateConfig {
+    pub name: String,
+    /// Used when `add` isn't given a title.
+    pub title: Option<String>,
+    pub duration: Option<String>,
+    /// Calendar name or id.
+    pub calendar: Option<String>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub meet: bool,
+    pub invite: Vec<String>,
+    pub optional: Vec<String>,
+    pub remind: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PopupPosition {
+    TopLeft,
+    Top,
+    TopRight,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+/// Rules for dropping events, globally (`[filters]`) or per calendar
+/// (`[calendars.filters]`). Patterns are matched against title and location.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// Regexes; when any are given, only matching events are kept.
+    pub include: Vec<String>,
+    /// Regexes; matching events are dropped.
+    pub exclude: Vec<String>,
+    /// Case-insensitive substrings; events containing any are dropped.
+    pub exclude_keywords: Vec<String>,
+    /// Timed events shorter than this ("10m", "1h30m") are dropped.
+    pub min_duration: Option<String>,
+    /// Timed events longer than this are dropped.
+    pub max_duration: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,54 +638,161 @@ pub struct CalendarConfig {
     pub name: String,
     pub color: String,
     pub enabled: bool,
+    /// Shorter look-ahead for this calendar when the agenda goes by days;
+    /// `--days` still bounds it.
+    #[serde(default)]
+    pub days_ahead: Option<i64>,
+    /// Furthest ahead this calendar is ever fetched, whatever the agenda
+    /// asks for; keeps huge shared calendars cheap.
+    #[serde(default)]
+    pub max_days_ahead: Option<i64>,
+    /// Events per result page for this calendar instead of
+    /// `network.max_results`.
+    #[serde(default)]
+    pub max_results: Option<u32>,
+    /// Most events taken from this calendar per agenda.
+    #[serde(default)]
+    pub max_events: Option<usize>,
+    #[serde(default = "default_true")]
+    pub show_all_day: bool,
+    /// Lead times (minutes before start) carried on each event for reminders,
+    /// instead of the popup reminders set in Google Calendar.
+    #[serde(default)]
+    pub reminder_minutes: Vec<u32>,
+    #[serde(default)]
+    pub filters: FilterConfig,
+    #[serde(default)]
+    pub visibility: Option<VisibilityPolicy>,
+}
+
+impl CalendarConfig {
+    /// An entry with no per-calendar overrides.
+    pub fn new(id: String, name: String, color: String, enabled: bool) -> Self {
+        Self {
+            id,
+            name,
+            color,
+            enabled,
+            days_ahead: None,
+            max_days_ahead: None,
+            max_results: None,
+            max_events: None,
+            show_all_day: true,
+            reminder_minutes: Vec::new(),
+            filters: FilterConfig::default(),
+            visibility: None,
+        }
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: migrate::CURRENT_VERSION,
             auth: AuthConfig {
                 credentials_path: "~/.config/callux/credentials.json".to_string(),
-                token_cache_path: "~/.config/callux/token.json".to_string(),
+                token_cache_path: default_token_cache_path(),
+                timeout_seconds: default_auth_timeout_seconds(),
+                allow_insecure_permissions: false,
             },
             cache: CacheConfig {
                 ttl_seconds: 300,
                 max_entries: 1000,
+                persistent: true,
+                prefetch_days: default_prefetch_days(),
+                today_ttl_seconds: None,
+                ttl_jitter_seconds: 30,
+                strategy: CacheStrategy::Strict,
+                path: None,
             },
             display: DisplayConfig {
                 max_events: 10,
                 date_format: "%Y-%m-%d %H:%M".to_string(),
                 timezone: "local".to_string(),
+                working_hours: None,
+                hide_outside_working_hours: false,
+                week_start: WeekStart::Monday,
+                show_week_numbers: false,
+                week_number_placement: WeekNumberPlacement::Heading,
+                show_cancelled: false,
+                hide_declined: false,
+                show_free: true,
+                show_out_of_office: true,
+                show_focus_time: true,
+                show_working_location: false,
+                show_from_gmail: true,
+                show_event_timezone: false,
+                show_gaps: false,
+                color_by_response: false,
+                back_to_back_minutes: None,
+                hide_past: false,
+                visibility: VisibilityPolicy::All,
+                classes: Vec::new(),
             },
-            calendars: vec![CalendarConfig {
-                id: "primary".to_string(),
-                name: "Personal".to_string(),
-                color: "#1976d2".to_string(),
-                enabled: true,
-            }],
+            network: NetworkConfig::default(),
+            calendars: vec![CalendarConfig::new(
+                "primary".to_string(),
+                "Personal".to_string(),
+                DEFAULT_CALENDAR_COLOR.to_string(),
+                true,
+            )],
+            aliases: BTreeMap::new(),
+            filters: FilterConfig::default(),
+            cli: CliConfig::default(),
+            daemon: DaemonConfig::default(),
+            notifications: NotificationConfig::default(),
+            hooks: HooksConfig::default(),
+            serve: ServeConfig::default(),
+            mqtt: MqttConfig::default(),
+            tracking: TrackingConfig::default(),
+            dnd: DndConfig::default(),
+            inhibit: InhibitConfig::default(),
+            popup: PopupConfig::default(),
+            snapshot: SnapshotConfig::default(),
+            templates: Vec::new(),
+            profile: BTreeMap::new(),
         }
     }
 }
 
 impl Config {
     pub fn load() -> Result<Self> {
-        let config_path = Self::get_config_path()?;
+        let config_path = Self::path()?;
 
         if !config_path.exists() {
-            let default_config = Self::default();
-            default_config.save()?;
-            return Ok(default_config);
+            Self::default().save()?;
+        } else {
+            migrate::upgrade(&config_path)?;
         }
 
         let config_str = std::fs::read_to_string(&config_path)?;
-        let config: Config = toml::from_str(&config_str)?;
-        Ok(config)
+        let mut raw = Self::merge_files(&config_str)?;
+        apply_env_overrides(&mut raw, std::env::vars())?;
+
+        Ok(raw.try_into()?)
+    }
+
+    /// `text`, the config file, with the `config.d` drop-ins and the selected
+    /// profile merged in: what `load` reads apart from the environment.
+    pub fn merge_files(text: &str) -> Result<toml::Value> {
+        let mut raw: toml::Value = toml::from_str(text)?;
+        for fragment in Self::drop_ins()? {
+            let text = std::fs::read_to_string(&fragment)?;
+            let overlay: toml::Value = toml::from_str(&text)
+                .map_err(|e| anyhow::anyhow!("Invalid {}: {}", fragment.display(), e))?;
+            merge_drop_in(&mut raw, overlay);
+        }
+        if let Some(name) = Self::profile() {
+            apply_profile(&mut raw, name)?;
+        }
+        Ok(raw)
     }
 
     pub fn save(&self) -> Result<()> {
-        let config_path = Self::get_config_path()?;
+        let config_path = Self::path()?;
 
         if let Some(parent) = config_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            security::create_private_dir(parent)?;
         }
 
         let config_str = toml::to_string_pretty(self)?;
@@ -90,18 +800,265 @@ impl Config {
         Ok(())
     }
 
-    fn get_config_path() -> Result<PathBuf> {
+    pub fn path() -> Result<PathBuf> {
+        if let Some(path) = PATH_OVERRIDE.get() {
+            return Ok(path.clone());
+        }
+
         let config_dir =
             dirs::config_dir().ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
         Ok(config_dir.join("callux").join("config.toml"))
     }
 
+    /// `*.toml` fragments in `config.d/` next to the config file, in the
+    /// order they are merged (by file name).
+    pub fn drop_ins() -> Result<Vec<PathBuf>> {
+        let config_path = Self::path()?;
+        let Some(dir) = config_path.parent().map(|parent| parent.join("config.d")) else {
+            return Ok(Vec::new());
+        };
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut fragments: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        fragments.sort();
+        Ok(fragments)
+    }
+
+    /// Points every later `load`/`save` at `path` instead of the XDG default.
+    pub fn set_path_override(path: &str) {
+        let _ = PATH_OVERRIDE.set(PathBuf::from(expand_home(path)));
+    }
+
+    pub fn path_override() -> Option<&'static Path> {
+        PATH_OVERRIDE.get().map(PathBuf::as_path)
+    }
+
+    pub fn set_profile(name: &str) {
+        let _ = PROFILE.set(name.to_string());
+    }
+
+    pub fn profile() -> Option<&'static str> {
+        PROFILE.get().map(String::as_str)
+    }
+
+    /// A configured calendar by id, or by name as `fuzzy::find` matches it,
+    /// e.g. "work", "wo" or "wrk" for "Work". Fails listing the candidates
+    /// when several fit.
+    pub fn find_calendar(&self, name_or_id: &str) -> Result<&CalendarConfig> {
+        let name_or_id = self
+            .aliases
+            .get(name_or_id)
+            .map_or(name_or_id, String::as_str);
+        if let Some(calendar) = self.calendars.iter().find(|cal| cal.id == name_or_id) {
+            return Ok(calendar);
+        }
+        let names: Vec<&str> = self.calendars.iter().map(|cal| cal.name.as_str()).collect();
+        match fuzzy::find(name_or_id, &names) {
+            Ok(index) => Ok(&self.calendars[index]),
+            Err(matches) if matches.is_empty() => {
+                anyhow::bail!("Calendar '{}' is not configured", name_or_id)
+            }
+            Err(matches) => anyhow::bail!(
+                "'{}' matches several calendars: {}",
+                name_or_id,
+                matches
+                    .iter()
+                    .map(|&i| names[i])
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    /// What to call `calendar` in output: its name, or its alias when the
+    /// name is just the id.
+    pub fn calendar_label<'a>(&'a self, calendar: &'a CalendarConfig) -> &'a str {
+        if !calendar.name.is_empty() && calendar.name != calendar.id {
+            return &calendar.name;
+        }
+        self.aliases
+            .iter()
+            .find(|(_, id)| **id == calendar.id)
+            .map_or(&calendar.id, |(alias, _)| alias)
+    }
+
+    /// A `[[templates]]` entry by (case-insensitive) name.
+    pub fn find_template(&self, name: &str) -> Option<&TemplateConfig> {
+        self.templates
+            .iter()
+            .find(|template| template.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Stable hash of every setting that shapes cached results, so changing
+    /// one of them invalidates the cache instead of serving stale output.
+    pub fn cache_fingerprint(&self) -> String {
+        let relevant = serde_json::json!({
+            "calendars": self.calendars,
+            "aliases": self.aliases,
+            "filters": self.filters,
+            "timezone": self.display.timezone,
+            "working_hours": self.display.working_hours,
+            "hide_outside_working_hours": self.display.hide_outside_working_hours,
+            "show_cancelled": self.display.show_cancelled,
+            "hide_declined": self.display.hide_declined,
+            "show_free": self.display.show_free,
+            "show_out_of_office": self.display.show_out_of_office,
+            "show_focus_time": self.display.show_focus_time,
+            "show_working_location": self.display.show_working_location,
+            "show_from_gmail": self.display.show_from_gmail,
+            "visibility": self.display.visibility,
+        });
+
+        // FNV-1a: stable across builds, unlike `DefaultHasher`.
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in relevant.to_string().bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{:016x}", hash)
+    }
+
     pub fn expand_path(&self, path: &str) -> String {
-        if path.starts_with("~/") {
-            if let Some(home_dir) = dirs::home_dir() {
-                return home_dir.join(&path[2..]).to_string_lossy().to_string();
+        expand_home(path)
+    }
+}
+
+/// Merges `[profile.<name>]` over the rest of the document. Tables merge
+/// key by key; anything else, `calendars` included, is replaced outright.
+fn apply_profile(raw: &mut toml::Value, name: &str) -> Result<()> {
+    let overlay = raw
+        .get("profile")
+        .and_then(|profiles| profiles.get(name))
+        .cloned()
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown profile '{}': add a [profile.{}] section",
+                name,
+                name
+            )
+        })?;
+
+    merge(raw, overlay);
+    Ok(())
+}
+
+/// Applies `CALLUX_SECTION__KEY=value` variables; `__` separates the
+/// segments of a dotted key, so `CALLUX_CALENDARS__0__ENABLED=false` sets
+/// `calendars.0.enabled`. Values are parsed as TOML where the current value
+/// isn't a string, and taken literally otherwise.
+fn apply_env_overrides(
+    raw: &mut toml::Value,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<()> {
+    for (name, value) in vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        if !key.contains("__") {
+            // CALLUX_PROFILE and friends, not config keys.
+            continue;
+        }
+
+        let segments: Vec<String> = key.split("__").map(str::to_ascii_lowercase).collect();
+        if segments.iter().any(|s| s.is_empty()) {
+            anyhow::bail!("Invalid override variable {}", name);
+        }
+
+        let (last, parents) = segments.split_last().unwrap();
+        let mut node = &mut *raw;
+        for segment in parents {
+            node = match node {
+                toml::Value::Array(items) => segment
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| items.get_mut(index))
+                    .ok_or_else(|| anyhow::anyhow!("{}: no entry '{}'", name, segment))?,
+                toml::Value::Table(table) => table
+                    .entry(segment.clone())
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new())),
+                _ => anyhow::bail!("{}: '{}' is not a table", name, segment),
+            };
+        }
+
+        let toml::Value::Table(table) = node else {
+            anyhow::bail!("{}: cannot set '{}' here", name, last);
+        };
+        let parsed = match table.get(last.as_str()) {
+            Some(toml::Value::String(_)) => toml::Value::String(value),
+            _ => parse_scalar(&value),
+        };
+        table.insert(last.clone(), parsed);
+    }
+
+    Ok(())
+}
+
+fn parse_scalar(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("v = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+/// Like `merge`, except `[[calendars]]` entries are combined by `id`: a
+/// fragment can adjust a shared calendar or add machine-specific ones.
+fn merge_drop_in(base: &mut toml::Value, mut overlay: toml::Value) {
+    let calendars = overlay
+        .as_table_mut()
+        .and_then(|table| table.remove("calendars"));
+    merge(base, overlay);
+
+    let Some(toml::Value::Array(calendars)) = calendars else {
+        return;
+    };
+    let Some(table) = base.as_table_mut() else {
+        return;
+    };
+    let toml::Value::Array(existing) = table
+        .entry("calendars")
+        .or_insert_with(|| toml::Value::Array(Vec::new()))
+    else {
+        return;
+    };
+
+    for calendar in calendars {
+        let id = calendar.get("id").cloned();
+        let same = existing
+            .iter_mut()
+            .find(|c| id.is_some() && c.get("id") == id.as_ref());
+        match same {
+            Some(current) => merge(current, calendar),
+            None => existing.push(calendar),
+        }
+    }
+}
+
+fn merge(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
             }
         }
-        path.to_string()
+        (base, overlay) => *base = overlay,
+    }
+}
+
+pub fn expand_home(path: &str) -> String {
+    if path.starts_with("~/") {
+        if let Some(home_dir) = dirs::home_dir() {
+            return home_dir.join(&path[2..]).to_string_lossy().to_string();
+        }
     }
+    path.to_string()
 }
@@ -0,0 +1,326 @@
+use crate::config::{CalendarConfig, Config};
+use anyhow::{Result, anyhow, bail};
+use std::path::{Path, PathBuf};
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, TableLike, Value};
+
+/// Edits the config file in place through `toml_edit`, so comments and
+/// formatting the user added survive `config set`.
+pub struct ConfigEditor {
+    path: PathBuf,
+    doc: DocumentMut,
+}
+
+impl ConfigEditor {
+    pub fn open() -> Result<Self> {
+        // Loading first writes the default file if there is none yet.
+        Config::load()?;
+        let path = Config::path()?;
+        let text = std::fs::read_to_string(&path)?;
+        let doc = text
+            .parse::<DocumentMut>()
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+
+        Ok(Self { path, doc })
+    }
+
+    /// Sets a dotted key such as `display.max_events` or
+    /// `calendars.0.enabled`. The value keeps the type of the current one
+    /// where there is one, and the result must still be a valid config.
+    pub fn set(&mut self, key: &str, raw: &str) -> Result<()> {
+        let segments = split_key(key)?;
+        let (last, parents) = segments.split_last().unwrap();
+        if last.parse::<usize>().is_ok() {
+            bail!(
+                "Cannot set a single array element; set '{}' to a whole array instead",
+                key
+            );
+        }
+
+        let table = navigate(self.doc.as_table_mut(), parents)?;
+        let mut value = match table.get(last) {
+            Some(Item::Value(Value::String(_))) => Value::from(raw),
+            _ => raw.parse::<Value>().unwrap_or_else(|_| Value::from(raw)),
+        };
+
+        if let Some(Item::Value(existing)) = table.get(last) {
+            *value.decor_mut() = existing.decor().clone();
+        }
+        table.insert(last, Item::Value(value));
+
+        self.validate(&segments)
+    }
+
+    /// Resets a dotted key to its built-in default, or removes it from the
+    /// file when there is no default (optional settings, extra calendars).
+    /// Returns the default that now applies, if any.
+    pub fn unset(&mut self, key: &str) -> Result<Option<toml::Value>> {
+        let segments = split_key(key)?;
+        let (last, parents) = segments.split_last().unwrap();
+
+        // Reject typos up front rather than silently doing nothing.
+        let current = Config::load()?;
+        let default = lookup(&Config::default(), &segments).ok();
+        if default.is_none() && lookup(&current, &segments).is_err() {
+            bail!("Unknown configuration key '{}'", key);
+        }
+
+        let table = navigate(self.doc.as_table_mut(), parents)?;
+        match &default {
+            Some(value) => {
+                let mut item = to_item(value)?;
+                if let (Some(Item::Value(existing)), Item::Value(new)) =
+                    (table.get(last), &mut item)
+                {
+                    *new.decor_mut() = existing.decor().clone();
+                }
+                table.insert(last, item);
+            }
+            None => {
+                table.remove(last);
+            }
+        }
+
+        toml::from_str::<Config>(&self.doc.to_string())
+            .map_err(|e| anyhow!("Cannot unset '{}': {}", key, e.message()))?;
+
+        Ok(default)
+    }
+
+    /// Appends a `[[calendars]]` entry (to the active profile's list when
+    /// that profile defines its own calendars).
+    pub fn add_calendar(&mut self, calendar: &CalendarConfig) -> Result<()> {
+        if self.find_calendar(&calendar.id).is_some() {
+            bail!("Calendar '{}' is already configured", calendar.id);
+        }
+
+        let mut table = Table::new();
+        table.insert("id", toml_edit::value(calendar.id.as_str()));
+        table.insert("name", toml_edit::value(calendar.name.as_str()));
+        table.insert("color", toml_edit::value(calendar.color.as_str()));
+        table.insert("enabled", toml_edit::value(calendar.enabled));
+        self.calendars_mut()?.push(table);
+
+        self.check()
+    }
+
+    pub fn remove_calendar(&mut self, id: &str) -> Result<()> {
+        let index = self
+            .find_calendar(id)
+            .ok_or_else(|| anyhow!("Calendar '{}' is not configured", id))?;
+        self.calendars_mut()?.remove(index);
+        self.check()
+    }
+
+    /// Sets one field (`name`, `enabled`, ...) of the calendar with `id`.
+    pub fn update_calendar(
+        &mut self,
+        id: &str,
+        field: &str,
+        value: impl Into<Value>,
+    ) -> Result<()> {
+        let index = self
+            .find_calendar(id)
+            .ok_or_else(|| anyhow!("Calendar '{}' is not configured", id))?;
+        let entry = self.calendars_mut()?.get_mut(index).unwrap();
+
+        let mut value = value.into();
+        if let Some(Item::Value(existing)) = entry.get(field) {
+            *value.decor_mut() = existing.decor().clone();
+        }
+        entry.insert(field, Item::Value(value));
+        self.check()
+    }
+
+    fn find_calendar(&self, id: &str) -> Option<usize> {
+        self.calendars()?
+            .iter()
+            .position(|entry| entry.get("id").and_then(Item::as_str) == Some(id))
+    }
+
+    fn calendars(&self) -> Option<&ArrayOfTables> {
+        let profile =
+            Config::profile().and_then(|name| self.doc.get("profile")?.get(name)?.get("calendars"));
+        profile
+            .or_else(|| self.doc.get("calendars"))?
+            .as_array_of_tables()
+    }
+
+    fn calendars_mut(&mut self) -> Result<&mut ArrayOfTables> {
+        let in_profile = Config::profile().filter(|name| {
+            self.doc
+                .get("profile")
+                .and_then(|profiles| profiles.get(name))
+                .and_then(|profile| profile.get("calendars"))
+                .is_some()
+        });
+
+        let item = match in_profile {
+            Some(name) => &mut self.doc["profile"][name]["calendars"],
+            None => self
+                .doc
+                .entry("calendars")
+                .or_insert(Item::ArrayOfTables(ArrayOfTables::new())),
+        };
+        item.as_array_of_tables_mut()
+            .ok_or_else(|| anyhow!("'calendars' is not a list of [[calendars]] tables"))
+    }
+
+    fn check(&self) -> Result<()> {
+        toml::from_str::<Config>(&self.doc.to_string())
+            .map(|_| ())
+            .map_err(|e| anyhow!("Invalid configuration: {}", e.message()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::write(&self.path, self.doc.to_string())?;
+        Ok(())
+    }
+
+    /// Round-trips the edited document through `Config` so type mismatches
+    /// and misspelled keys (which serde would silently drop) are rejected.
+    fn validate(&self, segments: &[&str]) -> Result<()> {
+        let config: Config = toml::from_str(&self.doc.to_string())
+            .map_err(|e| anyhow!("Invalid value: {}", e.message()))?;
+        lookup(&config, segments).map(|_| ())
+    }
+}
+
+/// Opens the config file in `$VISUAL`/`$EDITOR` via a scratch copy, and only
+/// replaces the real file once the edited copy parses as a valid config.
+pub fn edit_interactively() -> Result<()> {
+    Config::load()?;
+    let path = Config::path()?;
+    let scratch = path.with_extension("toml.edit");
+    std::fs::copy(&path, &scratch)?;
+
+    loop {
+        let status = launch_editor(&scratch)?;
+        if !status.success() {
+            let _ = std::fs::remove_file(&scratch);
+            bail!(
+                "Editor exited with {}; configuration left unchanged",
+                status
+            );
+        }
+
+        let text = std::fs::read_to_string(&scratch)?;
+        match toml::from_str::<Config>(&text) {
+            Ok(_) => {
+                std::fs::rename(&scratch, &path)?;
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("Invalid configuration: {}", e);
+                eprint!("Edit again? [Y/n] ");
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                if answer.trim().eq_ignore_ascii_case("n") {
+                    let _ = std::fs::remove_file(&scratch);
+                    bail!("Configuration left unchanged");
+                }
+            }
+        }
+    }
+}
+
+/// Runs `$VISUAL`/`$EDITOR` (falling back to vi) on `path` and waits for it.
+pub fn launch_editor(path: &Path) -> Result<std::process::ExitStatus> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or_else(|| anyhow!("$EDITOR is empty"))?;
+
+    std::process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| anyhow!("Failed to launch editor '{}': {}", program, e))
+}
+
+/// Returns the effective value at a dotted key, defaults included.
+pub fn get(config: &Config, key: &str) -> Result<toml::Value> {
+    lookup(config, &split_key(key)?)
+}
+
+/// Renders a value the way `config get` prints it: scalars bare, tables and
+/// arrays as TOML.
+pub fn render(value: &toml::Value) -> Result<String> {
+    Ok(match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Table(_) => toml::to_string_pretty(value)?.trim_end().to_string(),
+        other => other.to_string(),
+    })
+}
+
+fn to_item(value: &toml::Value) -> Result<Item> {
+    match value {
+        toml::Value::Table(_) => {
+            let doc = toml::to_string(value)?.parse::<DocumentMut>()?;
+            Ok(Item::Table(doc.as_table().clone()))
+        }
+        other => Ok(Item::Value(other.to_string().parse::<Value>()?)),
+    }
+}
+
+fn lookup(config: &Config, segments: &[&str]) -> Result<toml::Value> {
+    let tree = toml::Value::try_from(config)?;
+
+    let mut node = &tree;
+    for segment in segments {
+        let next = match (segment.parse::<usize>(), node) {
+            (Ok(index), toml::Value::Array(items)) => items.get(index),
+            (_, toml::Value::Table(table)) => table.get(*segment),
+            _ => None,
+        };
+        node = next.ok_or_else(|| anyhow!("Unknown configuration key '{}'", segments.join(".")))?;
+    }
+
+    Ok(node.clone())
+}
+
+fn split_key(key: &str) -> Result<Vec<&str>> {
+    let segments: Vec<&str> = key.split('.').collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        bail!("Invalid configuration key '{}'", key);
+    }
+    Ok(segments)
+}
+
+/// Walks `segments` from `root`, creating missing tables on the way. A
+/// numeric segment after an array of tables (`calendars.0`) selects an entry.
+fn navigate<'a>(root: &'a mut dyn TableLike, segments: &[&str]) -> Result<&'a mut dyn TableLike> {
+    let mut table = root;
+    let mut i = 0;
+
+    while i < segments.len() {
+        let segment = segments[i];
+        let item = table.entry(segment).or_insert(toml_edit::table());
+
+        match segments.get(i + 1).and_then(|s| s.parse::<usize>().ok()) {
+            Some(index) => {
+                let entries = item
+                    .as_array_of_tables_mut()
+                    .ok_or_else(|| anyhow!("'{}' is not a list of tables", segment))?;
+                let len = entries.len();
+                table = entries.get_mut(index).ok_or_else(|| {
+                    anyhow!(
+                        "'{}' has {} entries; index {} is out of range",
+                        segment,
+                        len,
+                        index
+                    )
+                })?;
+                i += 2;
+            }
+            None => {
+                table = item
+                    .as_table_like_mut()
+                    .ok_or_else(|| anyhow!("'{}' is not a table", segment))?;
+                i += 1;
+            }
+        }
+    }
+
+    Ok(table)
+}
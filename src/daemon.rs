@@ -0,0 +1,499 @@
+use crate::calendar::{Agenda, CalendarClient};
+use crate::config::{Config, DaemonConfig, MqttConfig, NotificationConfig, expand_home};
+use crate::dnd;
+use crate::hooks;
+use crate::inhibit;
+use crate::metrics;
+use crate::output::CalendarEvent;
+use crate::push::PushChannels;
+use crate::rpc;
+use crate::security;
+use crate::shutdown;
+use crate::snapshot;
+use crate::tracking;
+use anyhow::{Context, anyhow};
+use std::collections::{BTreeMap, BTreeSet};
+use std::os::fd::FromRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{Notify, watch};
+
+/// A daemon that doesn't accept within this long is treated as not running.
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Wait after a push notification so a burst of them causes one refetch.
+const PUSH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// How soon a refresh that failed for a passing reason (network, quota) is
+/// tried again, when that's sooner than the next regular one.
+const REFRESH_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Range of the agenda published to watchers after each refresh; the same
+/// default `callux agenda` uses.
+const PUBLISHED_DAYS: i64 = 7;
+
+/// Ranges clients asked for stop being refreshed once none has asked for
+/// them in this long.
+const RANGE_IDLE: Duration = Duration::from_secs(60 * 60);
+
+/// Most ranges kept refreshed on clients' behalf; asking for another drops
+/// the one asked for least recently.
+const MAX_ASKED_RANGES: usize = 8;
+
+/// How long the call integrations get on shutdown to undo what they did,
+/// such as turning do-not-disturb back off; within the signal grace period.
+const CLEANUP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Look-ahead ranges the daemon keeps refreshed: the configured ones for
+/// good, and the ones clients ask for while they keep asking.
+pub struct Ranges {
+    configured: BTreeSet<i64>,
+    asked: BTreeMap<i64, Instant>,
+}
+
+impl Ranges {
+    fn new(configured: BTreeSet<i64>) -> Self {
+        Self {
+            configured,
+            asked: BTreeMap::new(),
+        }
+    }
+
+    /// Notes that a client asked for `days`.
+    pub fn ask(&mut self, days: i64) {
+        if self.configured.contains(&days) {
+            return;
+        }
+        if !self.asked.contains_key(&days) && self.asked.len() >= MAX_ASKED_RANGES {
+            let oldest = self
+                .asked
+                .iter()
+                .min_by_key(|(_, at)| **at)
+                .map(|(days, _)| *days);
+            if let Some(oldest) = oldest {
+                self.asked.remove(&oldest);
+            }
+        }
+        self.asked.insert(days, Instant::now());
+    }
+
+    /// The ranges to refresh, after dropping those gone idle.
+    fn current(&mut self) -> Vec<i64> {
+        self.asked.retain(|_, at| at.elapsed() < RANGE_IDLE);
+        let mut current = self.configured.clone();
+        current.extend(self.asked.keys());
+        current.into_iter().collect()
+    }
+}
+
+/// `daemon.socket_path` if set, otherwise a per-profile socket under the XDG
+/// runtime directory (or the cache directory where there is none).
+pub fn socket_path(config: &DaemonConfig) -> Option<PathBuf> {
+    if let Some(path) = &config.socket_path {
+        return Some(PathBuf::from(expand_home(path)));
+    }
+
+    let file = match Config::profile() {
+        Some(profile) => format!("daemon-{}.sock", profile),
+        None => "daemon.sock".to_string(),
+    };
+    dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .map(|dir| dir.join("callux").join(file))
+}
+
+/// Asks a running daemon for the agenda. `None` means there is no usable
+/// daemon and the caller should fetch the events itself.
+pub async fn query(
+    config: &Config,
+    days_ahead: i64,
+    limit: Option<usize>,
+    offline: bool,
+) -> Option<anyhow::Result<Agenda>> {
+    let path = socket_path(&config.daemon)?;
+    let stream = tokio::time::timeout(CONNECT_TIMEOUT, UnixStream::connect(&path))
+        .await
+        .ok()?
+        .ok()?;
+
+    let request = rpc::Request::new(
+        1,
+        "get_agenda",
+        serde_json::json!({
+            "days": days_ahead,
+            "limit": limit,
+            "offline": offline,
+            "fingerprint": config.cache_fingerprint(),
+        }),
+    );
+    let response = match exchange(stream, &request).await {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("Warning: Daemon at {} failed: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    match (response.result, response.error) {
+        (_, Some(error)) if error.code == rpc::CONFIG_MISMATCH => None,
+        (_, Some(error)) => Some(Err(anyhow!(error.message))),
+        (Some(result), None) => match serde_json::from_value::<rpc::AgendaResult>(result) {
+            Ok(result) => Some(Ok(Agenda {
+                events: result.events,
+                stale: result.stale,
+                warnings: result.warnings,
+            })),
+            Err(e) => Some(Err(anyhow!("Invalid daemon response: {}", e))),
+        },
+        (None, None) => Some(Err(anyhow!("Empty daemon response"))),
+    }
+}
+
+/// Tells a running daemon to refetch now, e.g. after callux changed an
+/// event. Does nothing when no daemon is running.
+pub async fn request_refresh(config: &Config) {
+    let Some(path) = socket_path(&config.daemon) else {
+        return;
+    };
+    let Ok(Ok(stream)) = tokio::time::timeout(CONNECT_TIMEOUT, UnixStream::connect(&path)).await
+    else {
+        return;
+    };
+    let request = rpc::Request::new(1, "refresh", serde_json::Value::Null);
+    if let Err(e) = exchange(stream, &request).await {
+        eprintln!("Warning: Daemon at {} failed: {}", path.display(), e);
+    }
+}
+
+async fn exchange(stream: UnixStream, request: &rpc::Request) -> std::io::Result<rpc::Response> {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+
+    let mut response = String::new();
+    BufReader::new(reader).read_line(&mut response).await?;
+    Ok(serde_json::from_str(&response)?)
+}
+
+/// Serves JSON-RPC agenda queries on the Unix socket and keeps the cache warm by
+/// refetching every range it has been asked for (immediately when Google
+/// pushes a change, if configured), until SIGINT/SIGTERM.
+pub async fn run(config: Config) -> anyhow::Result<()> {
+    let path = socket_path(&config.daemon)
+        .ok_or_else(|| anyhow!("No runtime directory for the daemon socket"))?;
+    let (listener, activated) = match activated_listener() {
+        Some(listener) => (listener?, true),
+        None => (bind(&path)?, false),
+    };
+    println!("Listening on {}", path.display());
+
+    let client = Arc::new(CalendarClient::new(config.clone()));
+    let ranges = Arc::new(Mutex::new(Ranges::new(
+        config
+            .cache
+            .prefetch_days
+            .iter()
+            .copied()
+            // Keeps the snapshot's range cached, so writing it never fetches.
+            .chain(config.snapshot.path.as_ref().map(|_| config.snapshot.days))
+            .collect(),
+    )));
+
+    let interval = config
+        .daemon
+        .refresh_interval_seconds
+        .unwrap_or(config.cache.ttl_seconds * 4 / 5)
+        .max(1);
+    let (published, _) = watch::channel(Vec::new());
+    if config.daemon.dbus {
+        start_dbus(client.clone(), published.subscribe());
+    }
+    if config.daemon.waybar_signal.is_some() || config.daemon.refresh_command.is_some() {
+        tokio::spawn(signal_bar(config.daemon.clone(), published.subscribe()));
+    }
+    if config.hooks.any() {
+        tokio::spawn(hooks::run(config.hooks.clone(), published.subscribe()));
+    }
+    if config.notifications.enabled {
+        start_notifications(config.notifications.clone(), published.subscribe());
+    }
+    // These clean up once `published` is dropped, so shutdown waits for them.
+    let mut integrations = Vec::new();
+    if config.tracking.backend.is_some() {
+        integrations.push(tokio::spawn(tracking::run(
+            config.tracking.clone(),
+            published.subscribe(),
+        )));
+    }
+    if config.dnd.backend.is_some() {
+        integrations.push(tokio::spawn(dnd::run(
+            config.dnd.clone(),
+            published.subscribe(),
+        )));
+    }
+    if config.inhibit.enabled {
+        integrations.push(tokio::spawn(inhibit::run(
+            config.inhibit.clone(),
+            published.subscribe(),
+        )));
+    }
+    if config.mqtt.host.is_some() {
+        start_mqtt(config.mqtt.clone(), published.subscribe());
+    }
+    if let Some(listen) = &config.daemon.metrics_listen {
+        let listen = listen
+            .parse()
+            .with_context(|| format!("Invalid daemon.metrics_listen '{}'", listen))?;
+        let agenda = published.subscribe();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(listen, agenda).await {
+                eprintln!("Warning: {:#}", e);
+            }
+        });
+    }
+
+    let changed = Arc::new(Notify::new());
+    let push = match &config.daemon.push_url {
+        Some(url) => {
+            let listen = config.daemon.push_listen.parse().with_context(|| {
+                format!("Invalid daemon.push_listen '{}'", config.daemon.push_listen)
+            })?;
+            let push = Arc::new(PushChannels::new(client.clone(), url.clone())?);
+            let runner = push.clone();
+            let changed = changed.clone();
+            tokio::spawn(async move {
+                if let Err(e) = runner.run(listen, changed).await {
+                    eprintln!("Warning: {}; falling back to polling", e);
+                }
+            });
+            Some(push)
+        }
+        None => None,
+    };
+
+    let server = Arc::new(rpc::Server {
+        client: client.clone(),
+        fingerprint: config.cache_fingerprint(),
+        ranges: ranges.clone(),
+        refresh: changed.clone(),
+        published: published.subscribe(),
+    });
+    let refresher = tokio::spawn(refresh_loop(
+        client.clone(),
+        ranges,
+        Duration::from_secs(interval),
+        changed,
+        published,
+        config.clone(),
+    ));
+
+    let stop = shutdown::signalled();
+    tokio::pin!(stop);
+    let result = loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    let server = server.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = server.serve(stream).await {
+                            eprintln!("Warning: Daemon connection failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => break Err(anyhow!("Accepting connections failed: {}", e)),
+            },
+            () = &mut stop => break Ok(()),
+        }
+    };
+
+    // Drops a refresh in flight; its cache writes are all or nothing. Once it
+    // has stopped, `published` is gone and the integrations wind down.
+    refresher.abort();
+    let _ = refresher.await;
+    let cleanup = async {
+        for integration in integrations {
+            let _ = integration.await;
+        }
+    };
+    if tokio::time::timeout(CLEANUP_TIMEOUT, cleanup)
+        .await
+        .is_err()
+    {
+        eprintln!("Warning: Call integrations did not finish cleaning up");
+    }
+
+    if let Some(push) = push {
+        push.stop().await;
+    }
+    // systemd owns an activated socket and keeps listening for the next start.
+    if !activated {
+        let _ = std::fs::remove_file(&path);
+    }
+    result
+}
+
+/// The listening socket handed over by systemd socket activation, if any.
+fn activated_listener() -> Option<std::io::Result<UnixListener>> {
+    let pid: u32 = std::env::var("LISTEN_PID").ok()?.parse().ok()?;
+    let fds: u32 = std::env::var("LISTEN_FDS").ok()?.parse().ok()?;
+    if pid != std::process::id() || fds < 1 {
+        return None;
+    }
+
+    // SAFETY: systemd passes the first socket as fd 3 (SD_LISTEN_FDS_START)
+    // and nothing else in this process owns it.
+    let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(3) };
+    Some(
+        listener
+            .set_nonblocking(true)
+            .and_then(|()| UnixListener::from_std(listener)),
+    )
+}
+
+/// Pokes the bar each time a refresh changes the agenda, so it re-runs
+/// `callux agenda` now instead of at its next polling interval.
+async fn signal_bar(config: DaemonConfig, mut agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    while agenda.changed().await.is_ok() {
+        let mut commands = Vec::new();
+        if let Some(signal) = config.waybar_signal {
+            commands.push(format!("pkill -RTMIN+{} waybar", signal));
+        }
+        commands.extend(config.refresh_command.clone());
+
+        for command in commands {
+            let status = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .stdin(std::process::Stdio::null())
+                .status()
+                .await;
+            // Exit codes are ignored: pkill fails whenever Waybar isn't running.
+            if let Err(e) = status {
+                eprintln!("Warning: Failed to run '{}': {}", command, e);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "dbus")]
+fn start_dbus(client: Arc<CalendarClient>, agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    tokio::spawn(async move {
+        if let Err(e) = crate::dbus::serve(client, agenda).await {
+            eprintln!("Warning: D-Bus service unavailable: {}", e);
+        }
+    });
+}
+
+#[cfg(not(feature = "dbus"))]
+fn start_dbus(_client: Arc<CalendarClient>, _agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    eprintln!("Warning: daemon.dbus is set but callux was built without D-Bus support");
+}
+
+#[cfg(feature = "notifications")]
+fn start_notifications(config: NotificationConfig, agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    tokio::spawn(crate::notify::run(config, agenda));
+}
+
+#[cfg(not(feature = "notifications"))]
+fn start_notifications(_config: NotificationConfig, _agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    eprintln!(
+        "Warning: notifications.enabled is set but callux was built without notification support"
+    );
+}
+
+#[cfg(feature = "mqtt")]
+fn start_mqtt(config: MqttConfig, agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    tokio::spawn(crate::mqtt::run(config, agenda));
+}
+
+#[cfg(not(feature = "mqtt"))]
+fn start_mqtt(_config: MqttConfig, _agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    eprintln!("Warning: mqtt.host is set but callux was built without MQTT support");
+}
+
+/// Binds the socket, replacing one left behind by a daemon that died but
+/// refusing to start twice.
+fn bind(path: &Path) -> anyhow::Result<UnixListener> {
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(anyhow!(
+                "A daemon is already listening on {}",
+                path.display()
+            ));
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+
+    // Only a directory callux creates is made private; an existing one such
+    // as /tmp is left as it is, and the socket's own mode protects it.
+    if let Some(parent) = path.parent().filter(|parent| !parent.exists()) {
+        security::create_private_dir(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to listen on {}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+async fn refresh_loop(
+    client: Arc<CalendarClient>,
+    ranges: Arc<Mutex<Ranges>>,
+    interval: Duration,
+    changed: Arc<Notify>,
+    published: watch::Sender<Vec<CalendarEvent>>,
+    config: Config,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = changed.notified() => {
+                // One edit tends to produce a burst of notifications.
+                tokio::time::sleep(PUSH_DEBOUNCE).await;
+                ticker.reset();
+            }
+        }
+        let current = match ranges.lock() {
+            Ok(mut ranges) => ranges.current(),
+            Err(_) => continue,
+        };
+
+        for days_ahead in current {
+            if let Err(e) = client.prefetch(days_ahead).await {
+                eprintln!("Warning: Refresh of {} days failed: {}", days_ahead, e);
+                if e.is_retryable() {
+                    ticker.reset_after(REFRESH_RETRY_DELAY.min(interval));
+                    continue;
+                }
+                // Other ranges would fail the same way until the user acts.
+                if let Some(hint) = e.class().hint() {
+                    eprintln!("Hint: {}", hint);
+                }
+                break;
+            }
+        }
+
+        if let Ok(agenda) = client.get_events(PUBLISHED_DAYS, None, false).await {
+            published.send_if_modified(|current| {
+                if *current == agenda.events {
+                    return false;
+                }
+                *current = agenda.events;
+                true
+            });
+        }
+        // Rewritten every round, not only on changes, so relative times and
+        // the staleness note stay current.
+        if let Err(e) = snapshot::refresh(&client, &config).await {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+}
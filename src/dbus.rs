@@ -0,0 +1,81 @@
+use crate::calendar::CalendarClient;
+use crate::output::CalendarEvent;
+use anyhow::Result;
+use chrono::Utc;
+use std::sync::Arc;
+use tokio::sync::watch;
+use zbus::fdo;
+use zbus::interface;
+
+pub const BUS_NAME: &str = "org.callux.Calendar";
+pub const OBJECT_PATH: &str = "/org/callux/Calendar";
+
+/// `org.callux.Calendar`: events are exchanged as JSON strings in the same
+/// shape as `CalendarEvent`, which widgets can parse without a schema.
+struct CalendarService {
+    client: Arc<CalendarClient>,
+    agenda: watch::Receiver<Vec<CalendarEvent>>,
+}
+
+#[interface(name = "org.callux.Calendar")]
+impl CalendarService {
+    /// The next event that hasn't ended yet, or an empty string.
+    async fn next_event(&self) -> fdo::Result<String> {
+        let now = Utc::now();
+        let agenda = self.agenda.borrow();
+        match agenda.iter().find(|e| e.end_time.with_timezone(&Utc) > now) {
+            Some(event) => to_json(event),
+            None => Ok(String::new()),
+        }
+    }
+
+    /// Events for the next `days` days, served from the daemon's cache.
+    async fn agenda(&self, days: u32) -> fdo::Result<String> {
+        let agenda = self
+            .client
+            .get_events(days as i64, None, false)
+            .await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+        to_json(&agenda.events)
+    }
+
+    /// The daemon's current agenda; PropertiesChanged fires when it changes.
+    #[zbus(property)]
+    async fn events(&self) -> fdo::Result<String> {
+        to_json(&*self.agenda.borrow())
+    }
+}
+
+fn to_json<T: serde::Serialize + ?Sized>(value: &T) -> fdo::Result<String> {
+    serde_json::to_string(value).map_err(|e| fdo::Error::Failed(e.to_string()))
+}
+
+/// Claims the bus name on the session bus and emits PropertiesChanged for
+/// `Events` every time the daemon publishes a new agenda.
+pub async fn serve(
+    client: Arc<CalendarClient>,
+    mut agenda: watch::Receiver<Vec<CalendarEvent>>,
+) -> Result<()> {
+    let service = CalendarService {
+        client,
+        agenda: agenda.clone(),
+    };
+    let connection = zbus::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, service)?
+        .build()
+        .await?;
+    let interface = connection
+        .object_server()
+        .interface::<_, CalendarService>(OBJECT_PATH)
+        .await?;
+
+    while agenda.changed().await.is_ok() {
+        interface
+            .get()
+            .await
+            .events_changed(interface.signal_emitter())
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use crate::calendar::FoundEvent;
+use crate::output::CalendarEvent;
+use crate::when;
+use crate::zone::Zone;
+
+/// `callux show`: everything known about one event, a field per line and
+/// the description last.
+pub fn render(found: &FoundEvent) -> String {
+    let event = &found.event;
+    let mut lines = vec![event.display_title()];
+
+    let when = if event.all_day {
+        format!(
+            "{}, {}",
+            event.start_time.format("%a %b %d"),
+            event.all_day_label()
+        )
+    } else {
+        let end_format = if event.end_time.date_naive() == event.start_time.date_naive() {
+            "%H:%M"
+        } else {
+            "%a %b %d %H:%M"
+        };
+        format!(
+            "{}–{} ({})",
+            event.start_time.format("%a %b %d %H:%M"),
+            event.end_time.format(end_format),
+            when::format_duration(event.end_time - event.start_time)
+        )
+    };
+    field(&mut lines, "When", when);
+    if let Some(source) = event.source_time() {
+        field(&mut lines, "Scheduled", source);
+    }
+    if let Some(repeat) = event.repeat_label() {
+        field(&mut lines, "Repeats", repeat);
+    }
+    field(&mut lines, "Calendar", event.calendar_name.clone());
+    match (&event.organizer, event.is_organizer) {
+        (Some(organizer), true) => field(&mut lines, "Organizer", format!("{} (you)", organizer)),
+        (Some(organizer), false) => field(&mut lines, "Organizer", organizer.clone()),
+        (None, true) => field(&mut lines, "Organizer", "you".to_string()),
+        (None, false) => {}
+    }
+    if let Some(response) = &event.response {
+        field(&mut lines, "Response", response.clone());
+    }
+    if let Some(location) = &event.location {
+        field(&mut lines, "Location", location.clone());
+    }
+    if let Some(url) = &event.meeting_url {
+        field(&mut lines, "Meeting", url.clone());
+    }
+
+    let guests: Vec<String> = found
+        .attendees
+        .iter()
+        .filter(|attendee| attendee.self_ != Some(true) && attendee.resource != Some(true))
+        .map(|attendee| {
+            let name = attendee
+                .display_name
+                .as_deref()
+                .or(attendee.email.as_deref())
+                .unwrap_or("?");
+            let mut guest = format!(
+                "{} ({})",
+                name,
+                attendee.response_status.as_deref().unwrap_or("needsAction")
+            );
+            if attendee.optional == Some(true) {
+                guest.push_str(", optional");
+            }
+            guest
+        })
+        .collect();
+    for (i, guest) in guests.into_iter().enumerate() {
+        let name = if i == 0 { "Guests" } else { "" };
+        field(&mut lines, name, guest);
+    }
+
+    for (i, (key, value)) in event.properties.iter().enumerate() {
+        let name = if i == 0 { "Properties" } else { "" };
+        field(&mut lines, name, format!("{}={}", key, value));
+    }
+    for (i, attachment) in found.attachments.iter().enumerate() {
+        let name = if i == 0 { "Attached" } else { "" };
+        let title = attachment.title.as_deref().unwrap_or("attachment");
+        let value = match &attachment.file_url {
+            Some(url) => format!("{} ({})", title, url),
+            None => title.to_string(),
+        };
+        field(&mut lines, name, value);
+    }
+    if let Some(link) = &event.html_link {
+        field(&mut lines, "Link", link.clone());
+    }
+    if let Some(description) = event.description.as_deref().map(str::trim) {
+        if !description.is_empty() {
+            lines.push(String::new());
+            lines.extend(description.lines().map(|line| format!("  {}", line)));
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// `callux tz`: when `event` happens in the `home` zone and in each of
+/// `zones`, with their UTC offsets and any change of day.
+pub fn render_zones(event: &CalendarEvent, home: (&str, Zone), zones: &[(String, Zone)]) -> String {
+    let mut lines = vec![event.display_title()];
+    if event.all_day {
+        // All-day events sit on the same dates in every zone.
+        lines.push(format!(
+            "  {}, {}",
+            event.start_time.format("%a %b %d"),
+            event.all_day_label()
+        ));
+        return lines.join("\n");
+    }
+
+    let rows: Vec<(&str, Zone)> = std::iter::once(home)
+        .chain(zones.iter().map(|(name, zone)| (name.as_str(), *zone)))
+        .collect();
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let home_day = event.start_time.date_naive();
+    for (name, zone) in rows {
+        let start = zone.convert(&event.start_time);
+        let end = zone.convert(&event.end_time);
+        let end_format = if end.date_naive() == start.date_naive() {
+            "%H:%M"
+        } else {
+            "%a %b %d %H:%M"
+        };
+        let mut line = format!(
+            "  {:<width$}  {}–{}  UTC{}",
+            name,
+            start.format("%a %b %d %H:%M"),
+            end.format(end_format),
+            start.format("%:z"),
+            width = width
+        );
+        let shift = (start.date_naive() - home_day).num_days();
+        if shift != 0 {
+            line.push_str(&format!("  ({:+} day)", shift));
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+/// "  Location:  Room 4", with the values lined up.
+fn field(lines: &mut Vec<String>, name: &str, value: String) {
+    let label = if name.is_empty() {
+        String::new()
+    } else {
+        format!("{}:", name)
+    };
+    lines.push(format!("  {:<12}{}", label, value));
+}
@@ -0,0 +1,232 @@
+use crate::cache::CacheStats;
+use crate::config::{Config, expand_home};
+use crate::error::CalendarError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+
+/// Config keys whose values identify people, calendars or events, let
+/// someone else in, or run commands and name files on this machine.
+/// Matched at any depth, including inside profiles.
+const PRIVATE_KEYS: &[&str] = &[
+    "id",
+    "name",
+    "calendar",
+    "aliases",
+    "title",
+    "description",
+    "location",
+    "invite",
+    "optional",
+    "include",
+    "exclude",
+    "exclude_keywords",
+    "calendars",
+    "tags",
+    "token",
+    "username",
+    "password",
+    "push_url",
+    "host",
+    "hooks",
+    "refresh_command",
+    "path",
+    "credentials_path",
+    "token_cache_path",
+    "socket_path",
+    "ca_bundle",
+];
+
+/// The most recent Google Calendar error a command failed with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastError {
+    pub at: DateTime<Utc>,
+    pub kind: String,
+    pub code: Option<u16>,
+    pub message: String,
+}
+
+/// `$XDG_DATA_HOME/callux/last-error.json`, next to the undo journal.
+pub fn last_error_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("callux")
+        .join("last-error.json")
+}
+
+/// Keeps `error` for `callux debug bundle`. Failing to is not worth a
+/// second error.
+pub fn record_error(error: &CalendarError) {
+    let entry = LastError {
+        at: Utc::now(),
+        kind: error.kind().to_string(),
+        code: error.status(),
+        message: summary(error),
+    };
+    let path = last_error_path();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Ok(text) = serde_json::to_string_pretty(&entry) {
+        let _ = std::fs::write(path, text);
+    }
+}
+
+/// Google's answers are recorded by status alone: their messages carry the
+/// request URL, whose path names the calendar and event and whose query holds
+/// the page token, and the response body.
+fn summary(error: &CalendarError) -> String {
+    match error {
+        CalendarError::ApiError {
+            status: Some(status),
+            ..
+        } => format!("API error: Google returned {}", status),
+        CalendarError::NotFound(_) => "Not found: Google returned 404 or 410".to_string(),
+        _ => scrub_quoted(&error.to_string()),
+    }
+}
+
+fn last_error() -> Option<LastError> {
+    let text = std::fs::read_to_string(last_error_path()).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Everything a bug report usually needs, with secrets, calendar ids and
+/// event details left out so it can be attached as is.
+pub fn bundle(config: &Config, cache: &CacheStats) -> Value {
+    let mut settings = serde_json::to_value(config).unwrap_or(Value::Null);
+    redact(&mut settings);
+
+    let config_path = Config::path().ok();
+    let drop_ins: Vec<String> = Config::drop_ins()
+        .unwrap_or_default()
+        .iter()
+        .map(|path| display_path(path))
+        .collect();
+    let credentials = PathBuf::from(expand_home(&config.auth.credentials_path));
+    let token_cache = PathBuf::from(expand_home(&config.auth.token_cache_path));
+    let socket = crate::daemon::socket_path(&config.daemon);
+    let paths = json!({
+        "config": config_path.as_deref().map(display_path),
+        "drop_ins": drop_ins,
+        "credentials": display_path(&credentials),
+        "token_cache": display_path(&token_cache),
+        "daemon_socket": socket.as_deref().map(display_path),
+        "undo_journal": display_path(&crate::journal::path()),
+        "last_error": display_path(&last_error_path()),
+    });
+
+    let persistent = cache.persistent.as_ref().map(|persistent| {
+        json!({
+            "path": display_path(&persistent.path),
+            "file_bytes": persistent.file_bytes,
+            "hits": persistent.hits,
+            "misses": persistent.misses,
+            "entries": persistent.entries.len(),
+            "etag_entries": persistent.etag_entries,
+        })
+    });
+
+    json!({
+        "generated_at": Utc::now(),
+        "version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "profile": Config::profile(),
+        "features": {
+            "persistent-cache": cfg!(feature = "persistent-cache"),
+            "compression": cfg!(feature = "compression"),
+        },
+        "paths": paths,
+        "config": settings,
+        "calendars": {
+            "configured": config.calendars.len(),
+            "enabled": config.calendars.iter().filter(|c| c.enabled).count(),
+        },
+        "cache": {
+            "ttl_seconds": cache.ttl_seconds,
+            "memory_entries": cache.memory_entries,
+            "persistent": persistent,
+        },
+        "last_error": last_error(),
+    })
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if PRIVATE_KEYS.contains(&key.as_str()) {
+                    redact_all(value);
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Replaces every string under `value`, keeping the shape so a report
+/// still shows how many calendars or patterns there are.
+fn redact_all(value: &mut Value) {
+    match value {
+        Value::String(text) if !text.is_empty() => *text = "[redacted]".to_string(),
+        Value::Object(map) => map.values_mut().for_each(redact_all),
+        Value::Array(items) => items.iter_mut().for_each(redact_all),
+        _ => {}
+    }
+}
+
+/// Error messages quote event titles and calendar ids, e.g.
+/// "Event 'Dentist' not found", and may name calendars by address or in a
+/// request URL.
+pub fn scrub_quoted(message: &str) -> String {
+    let mut out = String::new();
+    let mut rest = message;
+    while let Some(open) = rest.find('\'') {
+        let (before, after) = rest.split_at(open);
+        out.push_str(before);
+        // Apostrophes, as in "couldn't" or "Bob's", don't open or close.
+        let opens = !out.ends_with(char::is_alphanumeric);
+        let close = after
+            .match_indices('\'')
+            .map(|(i, _)| i)
+            .skip(1)
+            .find(|&i| !after[i + 1..].starts_with(char::is_alphanumeric));
+        match close {
+            Some(close) if opens => {
+                out.push_str("'[redacted]'");
+                rest = &after[close + 1..];
+            }
+            _ => {
+                out.push('\'');
+                rest = &after[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out.split(' ')
+        .map(|word| {
+            // Percent-encoded ids in URLs hide their '@'.
+            if word.contains('@') || word.contains("://") {
+                "[redacted]"
+            } else {
+                word
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `path` with the home directory shown as `~`, which would otherwise give
+/// away the user name.
+fn display_path(path: &Path) -> String {
+    match dirs::home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.display().to_string(),
+    }
+}
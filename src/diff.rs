@@ -0,0 +1,154 @@
+use crate::cache::PersistedEntry;
+use crate::cli::OutputFormat;
+use crate::output::CalendarEvent;
+use crate::zone::Zone;
+use chrono::{DateTime, Utc};
+use colored::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub before: CalendarEvent,
+    pub after: CalendarEvent,
+}
+
+/// What changed between two snapshots of the same agenda range. An event
+/// that moved and was renamed appears in both `rescheduled` and `retitled`.
+#[derive(Debug, Default, Serialize)]
+pub struct EventDiff {
+    pub added: Vec<CalendarEvent>,
+    pub removed: Vec<CalendarEvent>,
+    pub rescheduled: Vec<Change>,
+    pub retitled: Vec<Change>,
+}
+
+impl EventDiff {
+    /// Compares `current` with `previous`, ignoring events that merely ended
+    /// or scrolled into the `days_ahead` window since `previous` was taken.
+    pub fn between(
+        previous: &PersistedEntry,
+        current: &[CalendarEvent],
+        days_ahead: i64,
+        zone: Zone,
+    ) -> Self {
+        let now = Utc::now();
+        let fetched_at = DateTime::from_timestamp(previous.fetched_at as i64, 0).unwrap_or(now);
+        let previous_end = zone.add_days(&fetched_at, days_ahead).with_timezone(&Utc);
+        let current_end = zone.add_days(&now, days_ahead).with_timezone(&Utc);
+
+        let before: HashMap<&str, &CalendarEvent> = previous
+            .events
+            .iter()
+            .map(|event| (event.id.as_str(), event))
+            .collect();
+        let after: HashMap<&str, &CalendarEvent> = current
+            .iter()
+            .map(|event| (event.id.as_str(), event))
+            .collect();
+
+        let mut diff = EventDiff::default();
+        for event in current {
+            match before.get(event.id.as_str()) {
+                Some(old) => {
+                    if old.start_time != event.start_time || old.end_time != event.end_time {
+                        diff.rescheduled.push(Change {
+                            before: (*old).clone(),
+                            after: event.clone(),
+                        });
+                    }
+                    if old.title != event.title {
+                        diff.retitled.push(Change {
+                            before: (*old).clone(),
+                            after: event.clone(),
+                        });
+                    }
+                }
+                None if event.start_time.with_timezone(&Utc) < previous_end => {
+                    diff.added.push(event.clone())
+                }
+                None => {}
+            }
+        }
+
+        for event in &previous.events {
+            let in_window = event.end_time.with_timezone(&Utc) > now
+                && event.start_time.with_timezone(&Utc) < current_end;
+            if in_window && !after.contains_key(event.id.as_str()) {
+                diff.removed.push(event.clone());
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.rescheduled.is_empty()
+            && self.retitled.is_empty()
+    }
+
+    pub fn format(&self, format: &OutputFormat) -> String {
+        match format {
+            OutputFormat::Json | OutputFormat::JsonRaw => {
+                serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+            }
+            OutputFormat::Human => self.format_text(false),
+            OutputFormat::Colored => self.format_text(true),
+        }
+    }
+
+    fn format_text(&self, colored: bool) -> String {
+        if self.is_empty() {
+            return "No changes".to_string();
+        }
+
+        let paint = |marker: &str, color: Color| {
+            if colored {
+                marker.color(color).bold().to_string()
+            } else {
+                marker.to_string()
+            }
+        };
+
+        let mut lines = Vec::new();
+        for event in &self.added {
+            lines.push(format!("{} {}", paint("+", Color::Green), describe(event)));
+        }
+        for event in &self.removed {
+            lines.push(format!("{} {}", paint("-", Color::Red), describe(event)));
+        }
+        for change in &self.rescheduled {
+            lines.push(format!(
+                "{} {} moved from {} to {}",
+                paint("~", Color::Yellow),
+                change.after.title,
+                when(&change.before),
+                when(&change.after)
+            ));
+        }
+        for change in &self.retitled {
+            lines.push(format!(
+                "{} \"{}\" renamed to \"{}\"",
+                paint("~", Color::Yellow),
+                change.before.title,
+                change.after.title
+            ));
+        }
+
+        lines.join("\n")
+    }
+}
+
+fn describe(event: &CalendarEvent) -> String {
+    format!("{} {} ({})", when(event), event.title, event.calendar_name)
+}
+
+fn when(event: &CalendarEvent) -> String {
+    if event.all_day {
+        event.start_time.format("%a %b %d").to_string()
+    } else {
+        event.start_time.format("%a %b %d %H:%M").to_string()
+    }
+}
@@ -0,0 +1,112 @@
+use crate::config::{DndBackend, DndConfig};
+use crate::meeting;
+use crate::output::CalendarEvent;
+use tokio::process::Command;
+use tokio::sync::watch;
+
+/// How often to check whether a call is on, so do-not-disturb follows
+/// its start and end rather than the next agenda refresh.
+const TICK: std::time::Duration = std::time::Duration::from_secs(15);
+const MAKO_MODE: &str = "do-not-disturb";
+
+/// Turns on the notification daemon's do-not-disturb mode while a call (an
+/// event with a meeting link) is running and restores the previous state
+/// afterwards, until the daemon exits.
+pub async fn run(config: DndConfig, mut agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    let Some(backend) = config.backend else {
+        return;
+    };
+    // Whether DND was already on when the meeting began; `None` outside
+    // meetings.
+    let mut was_on: Option<bool> = None;
+    let mut ticker = tokio::time::interval(TICK);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            changed = agenda.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+            }
+        }
+
+        let in_meeting = meeting::current(&agenda.borrow(), &config.calendars, true).is_some();
+        match (in_meeting, was_on) {
+            (true, None) => {
+                let on = is_on(backend).await;
+                if !on {
+                    set(backend, true).await;
+                }
+                was_on = Some(on);
+            }
+            (false, Some(on)) => {
+                if !on {
+                    set(backend, false).await;
+                }
+                was_on = None;
+            }
+            _ => {}
+        }
+    }
+
+    if was_on == Some(false) {
+        set(backend, false).await;
+    }
+}
+
+/// Unknown (the tool failed) counts as off, so it gets switched on and
+/// back off again.
+async fn is_on(backend: DndBackend) -> bool {
+    let (program, args): (&str, &[&str]) = match backend {
+        DndBackend::Mako => ("makoctl", &["mode"]),
+        DndBackend::Dunst => ("dunstctl", &["is-paused"]),
+        DndBackend::Swaync => ("swaync-client", &["--get-dnd", "--skip-wait"]),
+    };
+    let output = match Command::new(program).args(args).output().await {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            eprintln!("Warning: {} exited with {}", program, output.status);
+            return false;
+        }
+        Err(e) => {
+            eprintln!("Warning: Failed to run {}: {}", program, e);
+            return false;
+        }
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match backend {
+        DndBackend::Mako => stdout.lines().any(|mode| mode.trim() == MAKO_MODE),
+        DndBackend::Dunst | DndBackend::Swaync => stdout.trim() == "true",
+    }
+}
+
+async fn set(backend: DndBackend, on: bool) {
+    let (program, args): (&str, Vec<&str>) = match backend {
+        DndBackend::Mako => (
+            "makoctl",
+            vec!["mode", if on { "-a" } else { "-r" }, MAKO_MODE],
+        ),
+        DndBackend::Dunst => (
+            "dunstctl",
+            vec!["set-paused", if on { "true" } else { "false" }],
+        ),
+        DndBackend::Swaync => (
+            "swaync-client",
+            vec![if on { "--dnd-on" } else { "--dnd-off" }, "--skip-wait"],
+        ),
+    };
+    let status = Command::new(program)
+        .args(&args)
+        .stdout(std::process::Stdio::null())
+        .status()
+        .await;
+    match status {
+        Ok(status) if !status.success() => {
+            eprintln!("Warning: {} exited with {}", program, status)
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Warning: Failed to run {}: {}", program, e),
+    }
+}
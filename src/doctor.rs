@@ -0,0 +1,286 @@
+use crate::auth::AuthManager;
+use crate::cache::PersistentStore;
+use crate::calendar::{self, CalendarClient};
+use crate::config::Config;
+use crate::error::CalendarError;
+use crate::validate::{self, Severity};
+use colored::*;
+use std::path::Path;
+
+/// One line of the report.
+pub struct Check {
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+pub enum Outcome {
+    Pass(String),
+    Fail {
+        problem: String,
+        fix: String,
+    },
+    /// Not run because an earlier check it depends on failed.
+    Skip(String),
+}
+
+impl Check {
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            outcome: Outcome::Pass(detail.into()),
+        }
+    }
+
+    fn fail(name: impl Into<String>, problem: impl ToString, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            outcome: Outcome::Fail {
+                problem: problem.to_string(),
+                fix: fix.into(),
+            },
+        }
+    }
+
+    fn skip(name: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            outcome: Outcome::Skip(reason.into()),
+        }
+    }
+
+    pub fn failed(&self) -> bool {
+        matches!(self.outcome, Outcome::Fail { .. })
+    }
+}
+
+/// Runs every check in the order callux itself depends on them: config,
+/// credentials, sign-in, each enabled calendar, then the cache. Checks whose
+/// prerequisite failed are skipped rather than failing for the same reason.
+pub async fn run(insecure_ok: bool) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let Some(mut config) = check_config(&mut checks) else {
+        for name in ["Credentials", "Sign-in", "Calendars", "Cache"] {
+            checks.push(Check::skip(name, "needs a valid configuration"));
+        }
+        return checks;
+    };
+    config.auth.allow_insecure_permissions |= insecure_ok;
+
+    let has_credentials = check_credentials(&config, &mut checks);
+    let client = CalendarClient::new(config.clone());
+    let signed_in = has_credentials && check_sign_in(&config, &client, &mut checks).await;
+    if !has_credentials {
+        checks.push(Check::skip("Sign-in", "needs valid credentials"));
+    }
+    if signed_in {
+        check_calendars(&config, &client, &mut checks).await;
+    } else {
+        checks.push(Check::skip("Calendars", "needs a working sign-in"));
+    }
+    checks.push(check_cache(&config));
+    checks
+}
+
+fn check_config(checks: &mut Vec<Check>) -> Option<Config> {
+    const NAME: &str = "Configuration";
+    let path = match Config::path() {
+        Ok(path) => path,
+        Err(e) => {
+            checks.push(Check::fail(NAME, e, "set XDG_CONFIG_HOME or pass --config"));
+            return None;
+        }
+    };
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        checks.push(Check::fail(
+            NAME,
+            format!("No configuration at {}", path.display()),
+            "run `callux config init`",
+        ));
+        return None;
+    };
+
+    let (_, diagnostics) = validate::validate_text(&text);
+    if let Some(error) = diagnostics.iter().find(|d| d.severity == Severity::Error) {
+        let problem = if error.key.is_empty() {
+            error.message.clone()
+        } else {
+            format!("{}: {}", error.key, error.message)
+        };
+        checks.push(Check::fail(
+            NAME,
+            problem,
+            "run `callux config validate` to see every problem",
+        ));
+        return None;
+    }
+
+    // Drop-ins, the profile and environment overrides only apply here.
+    match Config::load() {
+        Ok(config) => {
+            let warnings = diagnostics.len();
+            let detail = if warnings == 0 {
+                path.display().to_string()
+            } else {
+                format!(
+                    "{} ({} warning(s), see `callux config validate`)",
+                    path.display(),
+                    warnings
+                )
+            };
+            checks.push(Check::pass(NAME, detail));
+            Some(config)
+        }
+        Err(e) => {
+            checks.push(Check::fail(
+                NAME,
+                e,
+                "check the drop-ins in config.d, --profile and CALLUX_* variables",
+            ));
+            None
+        }
+    }
+}
+
+fn check_credentials(config: &Config, checks: &mut Vec<Check>) -> bool {
+    if calendar::fixed_access_token().is_some() {
+        checks.push(Check::skip("Credentials", "CALLUX_ACCESS_TOKEN is set"));
+        return true;
+    }
+    match AuthManager::new(config.clone()).check_credentials() {
+        Ok(()) => {
+            checks.push(Check::pass(
+                "Credentials",
+                config.expand_path(&config.auth.credentials_path),
+            ));
+            true
+        }
+        Err(e) => {
+            checks.push(Check::fail(
+                "Credentials",
+                e,
+                "download an OAuth client for a desktop app from the Google Cloud console \
+                 and install it with `callux config init`",
+            ));
+            false
+        }
+    }
+}
+
+async fn check_sign_in(config: &Config, client: &CalendarClient, checks: &mut Vec<Check>) -> bool {
+    let token_cache = config.expand_path(&config.auth.token_cache_path);
+    if calendar::fixed_access_token().is_none() && !Path::new(&token_cache).exists() {
+        checks.push(Check::fail(
+            "Sign-in",
+            "Not signed in yet",
+            "run `callux auth`",
+        ));
+        return false;
+    }
+
+    match client.check_token().await {
+        Ok(()) => {
+            checks.push(Check::pass("Sign-in", "token refreshed"));
+            true
+        }
+        Err(e) => {
+            let fix = fix_for(&e, "run `callux auth` to sign in again");
+            checks.push(Check::fail("Sign-in", e, fix));
+            false
+        }
+    }
+}
+
+async fn check_calendars(config: &Config, client: &CalendarClient, checks: &mut Vec<Check>) {
+    let enabled: Vec<_> = config.calendars.iter().filter(|c| c.enabled).collect();
+    if enabled.is_empty() {
+        checks.push(Check::fail(
+            "Calendars",
+            "No calendars are enabled",
+            "run `callux calendars add <name>` or `callux calendars enable <name>`",
+        ));
+        return;
+    }
+
+    for calendar in enabled {
+        let name = format!("Calendar {}", config.calendar_label(calendar));
+        match client.check_calendar(&calendar.id).await {
+            Ok(()) => checks.push(Check::pass(name, "reachable")),
+            Err(e) => {
+                let fix = match &e {
+                    CalendarError::NotFound(_) => format!(
+                        "check the id against `callux list-calendars`, or run \
+                         `callux calendars remove {}`",
+                        calendar.id
+                    ),
+                    CalendarError::PermissionDenied(_) => {
+                        "ask the calendar's owner to share it with this account".to_string()
+                    }
+                    _ => fix_for(
+                        &e,
+                        "try again, or run `callux debug bundle` for a bug report",
+                    ),
+                };
+                checks.push(Check::fail(name, e, fix));
+            }
+        }
+    }
+}
+
+fn check_cache(config: &Config) -> Check {
+    const NAME: &str = "Cache";
+    if !config.cache.persistent || !cfg!(feature = "persistent-cache") {
+        return Check::pass(NAME, "persistent cache disabled, nothing to write");
+    }
+    let Some(database) = PersistentStore::path_for(&config.cache) else {
+        return Check::fail(
+            NAME,
+            "No XDG cache directory",
+            "set cache.path to a writable file",
+        );
+    };
+
+    let probe = database.with_extension("doctor");
+    let writable = || -> std::io::Result<()> {
+        if let Some(dir) = database.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&probe, b"")?;
+        std::fs::remove_file(&probe)?;
+        if database.exists() {
+            std::fs::OpenOptions::new().append(true).open(&database)?;
+        }
+        Ok(())
+    };
+    match writable() {
+        Ok(()) => Check::pass(NAME, database.display().to_string()),
+        Err(e) => Check::fail(
+            NAME,
+            format!("{} is not writable: {}", database.display(), e),
+            "set cache.path to a writable file, or `callux config set cache.persistent false`",
+        ),
+    }
+}
+
+/// The generic hint for `error`'s class, or `fallback` when there is none.
+fn fix_for(error: &CalendarError, fallback: &str) -> String {
+    error.class().hint().unwrap_or(fallback).to_string()
+}
+
+pub fn print(checks: &[Check]) {
+    let width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    for check in checks {
+        let name = format!("{:width$}", check.name);
+        match &check.outcome {
+            Outcome::Pass(detail) => {
+                println!("{} {}  {}", "✓".bright_green(), name, detail.dimmed())
+            }
+            Outcome::Fail { problem, fix } => {
+                println!("{} {}  {}", "✗".red().bold(), name.bold(), problem);
+                println!("  {:width$}  {} {}", "", "Fix:".bright_yellow(), fix);
+            }
+            Outcome::Skip(reason) => {
+                println!("{} {}  {}", "-".dimmed(), name.dimmed(), reason.dimmed())
+            }
+        }
+    }
+}
@@ -1,24 +1,255 @@
-use std::fmt;
+use thiserror::Error;
 
-#[derive(Debug)]
+/// An underlying error kept as the `source` of a `CalendarError`.
+pub type Source = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Error)]
 pub enum CalendarError {
-    AuthenticationFailed(String),
-    ApiError(String),
-    ConfigError(String),
-    ParseError(String),
+    #[error("Authentication failed: {message}{}", detail(.source))]
+    AuthenticationFailed {
+        message: String,
+        #[source]
+        source: Option<Source>,
+    },
+    /// `status` is set when Google answered with an HTTP error.
+    #[error("API error: {message}{}", detail(.source))]
+    ApiError {
+        message: String,
+        status: Option<u16>,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("Configuration error: {message}{}", detail(.source))]
+    ConfigError {
+        message: String,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("Parse error: {message}{}", detail(.source))]
+    ParseError {
+        message: String,
+        #[source]
+        source: Option<Source>,
+    },
+    #[error("I/O error: {message}: {source}")]
+    Io {
+        message: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+    #[error("Cache error: {message}{}", detail(.source))]
+    CacheError {
+        message: String,
+        #[source]
+        source: Option<Source>,
+    },
+    /// 404/410 from the API: the event or calendar doesn't exist (any more).
+    #[error("Not found: {0}")]
+    NotFound(String),
+    /// 403 other than rate limiting, e.g. writing to a read-only calendar.
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+}
+
+/// The source appended to the message, so it still reads in full where
+/// only the top-level error is printed.
+fn detail(source: &Option<Source>) -> String {
+    match source {
+        Some(source) => format!(": {}", source),
+        None => String::new(),
+    }
+}
+
+impl CalendarError {
+    pub fn auth(message: impl Into<String>) -> Self {
+        CalendarError::AuthenticationFailed {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn auth_from(message: impl Into<String>, source: impl Into<Source>) -> Self {
+        CalendarError::AuthenticationFailed {
+            message: message.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn api(message: impl Into<String>) -> Self {
+        CalendarError::ApiError {
+            message: message.into(),
+            status: None,
+            source: None,
+        }
+    }
+
+    pub fn api_from(message: impl Into<String>, source: impl Into<Source>) -> Self {
+        CalendarError::ApiError {
+            message: message.into(),
+            status: None,
+            source: Some(source.into()),
+        }
+    }
+
+    /// An error response from Google.
+    pub fn http(status: u16, message: impl Into<String>) -> Self {
+        CalendarError::ApiError {
+            message: message.into(),
+            status: Some(status),
+            source: None,
+        }
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        CalendarError::ConfigError {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn config_from(message: impl Into<String>, source: impl Into<Source>) -> Self {
+        CalendarError::ConfigError {
+            message: message.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        CalendarError::ParseError {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn parse_from(message: impl Into<String>, source: impl Into<Source>) -> Self {
+        CalendarError::ParseError {
+            message: message.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn io(message: impl Into<String>, source: std::io::Error) -> Self {
+        CalendarError::Io {
+            message: message.into(),
+            source,
+        }
+    }
+
+    pub fn cache(message: impl Into<String>) -> Self {
+        CalendarError::CacheError {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn cache_from(message: impl Into<String>, source: impl Into<Source>) -> Self {
+        CalendarError::CacheError {
+            message: message.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    /// A stable name for the variant, for scripts reading JSON errors.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CalendarError::AuthenticationFailed { .. } => "authentication",
+            CalendarError::ApiError { .. } => "api",
+            CalendarError::ConfigError { .. } => "config",
+            CalendarError::ParseError { .. } => "parse",
+            CalendarError::Io { .. } => "io",
+            CalendarError::RateLimited(_) => "rate_limited",
+            CalendarError::CacheError { .. } => "cache",
+            CalendarError::NotFound(_) => "not_found",
+            CalendarError::PermissionDenied(_) => "permission_denied",
+        }
+    }
+
+    /// The HTTP status Google answered with, when it's known.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            CalendarError::ApiError { status, .. } => *status,
+            _ => None,
+        }
+    }
+
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            CalendarError::AuthenticationFailed {
+                source: Some(source),
+                ..
+            } if is_transport_error(source.as_ref()) => ErrorClass::Network,
+            CalendarError::AuthenticationFailed { .. } => ErrorClass::Auth,
+            CalendarError::ApiError {
+                status: Some(401), ..
+            } => ErrorClass::Auth,
+            CalendarError::ApiError {
+                status: Some(status),
+                ..
+            } if *status >= 500 => ErrorClass::Network,
+            CalendarError::ApiError {
+                status: None,
+                source: Some(source),
+                ..
+            } if is_transport_error(source.as_ref()) => ErrorClass::Network,
+            CalendarError::RateLimited(_) => ErrorClass::Quota,
+            CalendarError::ConfigError { .. } => ErrorClass::Config,
+            _ => ErrorClass::Other,
+        }
+    }
+
+    /// Whether trying again later could succeed without the user doing
+    /// anything.
+    pub fn is_retryable(&self) -> bool {
+        self.class().is_retryable()
+    }
 }
 
-impl fmt::Display for CalendarError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+/// What went wrong as far as the user is concerned, which decides whether
+/// to retry and what to suggest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Not signed in, or the sign-in expired or was revoked.
+    Auth,
+    /// Google asked us to slow down or the quota ran out.
+    Quota,
+    /// Google couldn't be reached or failed on its side.
+    Network,
+    /// The configuration or credentials need fixing.
+    Config,
+    Other,
+}
+
+impl ErrorClass {
+    pub fn is_retryable(self) -> bool {
+        matches!(self, ErrorClass::Quota | ErrorClass::Network)
+    }
+
+    pub fn hint(self) -> Option<&'static str> {
         match self {
-            CalendarError::AuthenticationFailed(msg) => write!(f, "Authentication failed: {}", msg),
-            CalendarError::ApiError(msg) => write!(f, "API error: {}", msg),
-            CalendarError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
-            CalendarError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            ErrorClass::Auth => Some("run `callux auth` to sign in again"),
+            ErrorClass::Quota => Some("wait a few minutes and try again"),
+            ErrorClass::Network => {
+                Some("check your connection, or pass --offline for cached events")
+            }
+            ErrorClass::Config => Some("run `callux config validate` to find the problem"),
+            ErrorClass::Other => None,
         }
     }
 }
 
-impl std::error::Error for CalendarError {}
+/// The request never got an answer: DNS, connect, TLS or a dropped
+/// connection, possibly wrapped by the OAuth library.
+fn is_transport_error(source: &(dyn std::error::Error + 'static)) -> bool {
+    let mut cause = Some(source);
+    while let Some(error) = cause {
+        if error.is::<hyper_util::client::legacy::Error>() || error.is::<hyper::Error>() {
+            return true;
+        }
+        cause = error.source();
+    }
+    false
+}
 
 pub type Result<T> = std::result::Result<T, CalendarError>;
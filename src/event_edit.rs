@@ -0,0 +1,145 @@
+use crate::calendar::{EventChanges, FoundEvent};
+use crate::config_edit::launch_editor;
+use crate::when::{self, Start};
+use crate::zone::Zone;
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+/// The editable part of an event, as written to the `$EDITOR` form.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventForm {
+    pub title: String,
+    /// `"2024-06-03 14:00"`, or a date alone for an all-day event.
+    pub start: String,
+    /// `45m`, `1h30m`, `2d`.
+    pub duration: String,
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl EventForm {
+    pub fn from_event(found: &FoundEvent) -> Self {
+        let event = &found.event;
+        Self {
+            title: event.title.clone(),
+            start: when::format_start(start_of(found)),
+            duration: when::format_duration(event.end_time - event.start_time),
+            location: found.location.clone().unwrap_or_default(),
+            description: event.description.clone().unwrap_or_default(),
+        }
+    }
+
+    /// What differs from `before`. Times are compared as parsed values, so
+    /// reformatting `start` alone changes nothing.
+    pub fn changes_from(&self, before: &FoundEvent, zone: Zone) -> Result<EventChanges> {
+        let original = Self::from_event(before);
+        let start = when::parse_start(&self.start, zone).map_err(|e| anyhow!(e))?;
+        let duration = when::parse_duration(&self.duration).map_err(|e| anyhow!(e))?;
+        let event = &before.event;
+
+        let moved = match (start, start_of(before)) {
+            (Start::Timed(a), Start::Timed(b)) => a != b,
+            (Start::AllDay(a), Start::AllDay(b)) => a != b,
+            _ => true,
+        } || duration != event.end_time - event.start_time;
+
+        Ok(EventChanges {
+            title: (self.title != original.title).then(|| self.title.clone()),
+            times: moved.then_some((start, duration)),
+            location: (self.location != original.location).then(|| self.location.clone()),
+            description: (self.description != original.description)
+                .then(|| self.description.clone()),
+            ..Default::default()
+        })
+    }
+}
+
+fn start_of(found: &FoundEvent) -> Start {
+    if found.event.all_day {
+        Start::AllDay(found.event.start_time.date_naive())
+    } else {
+        Start::Timed(found.event.start_time)
+    }
+}
+
+/// Opens `form` as TOML in `$VISUAL`/`$EDITOR` and returns the edited
+/// version, offering another go while it doesn't parse.
+pub fn edit_in_editor(form: &EventForm, zone: Zone) -> Result<EventForm> {
+    let path = std::env::temp_dir().join(format!("callux-event-{}.toml", std::process::id()));
+    std::fs::write(&path, toml::to_string(form)?)?;
+
+    let result = loop {
+        let status = launch_editor(&path)?;
+        if !status.success() {
+            break Err(anyhow!(
+                "Editor exited with {}; event left unchanged",
+                status
+            ));
+        }
+
+        let text = std::fs::read_to_string(&path)?;
+        let parsed = toml::from_str::<EventForm>(&text)
+            .map_err(|e| anyhow!("{}", e.message()))
+            .and_then(|edited| {
+                when::parse_start(&edited.start, zone).map_err(|e| anyhow!(e))?;
+                when::parse_duration(&edited.duration).map_err(|e| anyhow!(e))?;
+                Ok(edited)
+            });
+        match parsed {
+            Ok(edited) => break Ok(edited),
+            Err(e) => {
+                eprintln!("Invalid event: {}", e);
+                eprint!("Edit again? [Y/n] ");
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                if answer.trim().eq_ignore_ascii_case("n") {
+                    break Err(anyhow!("Event left unchanged"));
+                }
+            }
+        }
+    };
+
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// `--title`/`--at`/... flags applied over `found`; a new start keeps the
+/// old duration and vice versa.
+pub fn changes_from_flags(
+    found: &FoundEvent,
+    zone: Zone,
+    title: Option<String>,
+    at: Option<String>,
+    duration: Option<String>,
+    location: Option<String>,
+    description: Option<String>,
+) -> Result<EventChanges> {
+    let times = match (at, duration) {
+        (None, None) => None,
+        (at, duration) => {
+            let start = match at {
+                Some(at) => when::parse_start(&at, zone).map_err(|e| anyhow!(e))?,
+                None => start_of(found),
+            };
+            let duration = match duration {
+                Some(duration) => when::parse_duration(&duration).map_err(|e| anyhow!(e))?,
+                None => found.event.end_time - found.event.start_time,
+            };
+            Some((start, duration))
+        }
+    };
+
+    let changes = EventChanges {
+        title,
+        times,
+        location,
+        description,
+        ..Default::default()
+    };
+    if changes.is_empty() {
+        bail!("Nothing to change");
+    }
+    Ok(changes)
+}
@@ -0,0 +1,134 @@
+use crate::config::{DisplayConfig, FilterConfig, VisibilityPolicy};
+use crate::error::{CalendarError, Result};
+use crate::when;
+use chrono::Duration;
+use regex::Regex;
+
+/// Compiled form of the global filters combined with one calendar's.
+pub struct EventFilter {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    keywords: Vec<String>,
+    min_duration: Option<Duration>,
+    max_duration: Option<Duration>,
+    visibility: VisibilityPolicy,
+    display: Option<DisplayConfig>,
+}
+
+impl EventFilter {
+    pub fn new(global: &FilterConfig, calendar: &FilterConfig) -> Result<Self> {
+        let sources = [global, calendar];
+        Ok(Self {
+            include: compile(sources.iter().flat_map(|f| &f.include))?,
+            exclude: compile(sources.iter().flat_map(|f| &f.exclude))?,
+            keywords: sources
+                .iter()
+                .flat_map(|f| &f.exclude_keywords)
+                .map(|k| k.to_lowercase())
+                .collect(),
+            // A calendar's own bounds replace the global ones.
+            min_duration: duration(
+                calendar
+                    .min_duration
+                    .as_ref()
+                    .or(global.min_duration.as_ref()),
+            )?,
+            max_duration: duration(
+                calendar
+                    .max_duration
+                    .as_ref()
+                    .or(global.max_duration.as_ref()),
+            )?,
+            visibility: VisibilityPolicy::All,
+            display: None,
+        })
+    }
+
+    pub fn with_visibility(mut self, visibility: VisibilityPolicy) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    pub fn visibility(&self) -> VisibilityPolicy {
+        self.visibility
+    }
+
+    /// Also leaves out what `[display]` hides from the agenda: cancelled,
+    /// declined and free events and the optional event types. Without it
+    /// they all pass, so lookups and edits still find them.
+    pub fn with_display(mut self, display: &DisplayConfig) -> Self {
+        self.display = Some(display.clone());
+        self
+    }
+
+    pub fn display(&self) -> Option<&DisplayConfig> {
+        self.display.as_ref()
+    }
+
+    /// One-off patterns from `agenda --filter` and `--exclude`.
+    pub fn from_patterns(include: Vec<String>, exclude: Vec<String>) -> Result<Self> {
+        let patterns = FilterConfig {
+            include,
+            exclude,
+            ..FilterConfig::default()
+        };
+        Self::new(&patterns, &FilterConfig::default())
+    }
+
+    pub fn allows(&self, title: &str, location: Option<&str>) -> bool {
+        let fields = [Some(title), location];
+        let matches = |re: &Regex| fields.iter().flatten().any(|field| re.is_match(field));
+
+        if !self.include.is_empty() && !self.include.iter().any(matches) {
+            return false;
+        }
+        if self.exclude.iter().any(matches) {
+            return false;
+        }
+
+        !fields.iter().flatten().any(|field| {
+            let field = field.to_lowercase();
+            self.keywords.iter().any(|k| field.contains(k.as_str()))
+        })
+    }
+
+    pub fn allows_duration(&self, duration: Duration) -> bool {
+        self.min_duration.is_none_or(|min| duration >= min)
+            && self.max_duration.is_none_or(|max| duration <= max)
+    }
+}
+
+/// Describes each pattern or duration that doesn't parse, for
+/// `config validate`.
+pub fn check(filters: &FilterConfig) -> Vec<String> {
+    let patterns = filters
+        .include
+        .iter()
+        .chain(&filters.exclude)
+        .filter_map(|pattern| {
+            Regex::new(pattern)
+                .err()
+                .map(|e| format!("Invalid pattern '{}': {}", pattern, e))
+        });
+    let durations = [&filters.min_duration, &filters.max_duration]
+        .into_iter()
+        .flatten()
+        .filter_map(|value| when::parse_duration(value).err());
+    patterns.chain(durations).collect()
+}
+
+fn compile<'a>(patterns: impl Iterator<Item = &'a String>) -> Result<Vec<Regex>> {
+    patterns
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|e| {
+                CalendarError::config_from(format!("Invalid filter pattern '{}'", pattern), e)
+            })
+        })
+        .collect()
+}
+
+fn duration(value: Option<&String>) -> Result<Option<Duration>> {
+    value
+        .map(|value| when::parse_duration(value).map_err(CalendarError::config))
+        .transpose()
+}
@@ -0,0 +1,41 @@
+/// The candidate `query` picks out, ignoring case: the one equal to it,
+/// else the only one it's a prefix of, else the only one containing its
+/// letters in order ("wrk" for "Work"). Otherwise the candidates that fit
+/// equally well, which is empty when none do.
+pub fn find(query: &str, candidates: &[&str]) -> Result<usize, Vec<usize>> {
+    let query = query.to_lowercase();
+    let lowered: Vec<String> = candidates.iter().map(|c| c.to_lowercase()).collect();
+
+    for stage in [Stage::Exact, Stage::Prefix, Stage::Letters] {
+        let matches: Vec<usize> = (0..lowered.len())
+            .filter(|&i| {
+                let candidate = lowered[i].as_str();
+                match stage {
+                    Stage::Exact => candidate == query,
+                    Stage::Prefix => candidate.starts_with(&query),
+                    Stage::Letters => is_subsequence(&query, candidate),
+                }
+            })
+            .collect();
+        match matches.len() {
+            0 => continue,
+            1 => return Ok(matches[0]),
+            _ => return Err(matches),
+        }
+    }
+    Err(Vec::new())
+}
+
+#[derive(Clone, Copy)]
+enum Stage {
+    Exact,
+    Prefix,
+    Letters,
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut rest = haystack.chars();
+    needle
+        .chars()
+        .all(|wanted| rest.by_ref().any(|c| c == wanted))
+}
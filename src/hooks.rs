@@ -0,0 +1,157 @@
+use crate::config::HooksConfig;
+use crate::output::CalendarEvent;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::watch;
+
+/// How often event transitions are checked for between agenda updates.
+const TICK: std::time::Duration = std::time::Duration::from_secs(15);
+/// Transitions older than this (e.g. a meeting already running when the
+/// daemon starts) are skipped rather than reported late.
+const GRACE_MINUTES: i64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Moment {
+    StartingSoon,
+    Started,
+    Ended,
+}
+
+impl Moment {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Moment::StartingSoon => "starting_soon",
+            Moment::Started => "started",
+            Moment::Ended => "ended",
+        }
+    }
+}
+
+/// Runs the `[hooks]` commands as events start and end and whenever the
+/// daemon's agenda changes, until the daemon exits.
+pub async fn run(config: HooksConfig, mut agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    // Events are remembered past their removal from the agenda (which only
+    // holds upcoming and running events) so `ended` can still fire.
+    let mut tracked: HashMap<(String, i64), CalendarEvent> = HashMap::new();
+    let mut fired: HashSet<(Moment, String, i64)> = HashSet::new();
+    let mut ticker = tokio::time::interval(TICK);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            changed = agenda.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                let events = agenda.borrow_and_update().clone();
+                track(&mut tracked, &events);
+                if let Some(command) = &config.agenda_changed {
+                    let payload = serde_json::to_string(&events).unwrap_or_default();
+                    spawn(command, "agenda_changed", payload, Vec::new());
+                }
+            }
+        }
+
+        let now = Utc::now();
+        let lead = Duration::minutes(config.starting_soon_minutes as i64);
+        for ((id, start), event) in &tracked {
+            let start_time = event.start_time.with_timezone(&Utc);
+            let end_time = event.end_time.with_timezone(&Utc);
+            let moments = [
+                (
+                    Moment::StartingSoon,
+                    start_time - lead,
+                    &config.starting_soon,
+                ),
+                (Moment::Started, start_time, &config.started),
+                (Moment::Ended, end_time, &config.ended),
+            ];
+
+            for (moment, at, command) in moments {
+                let Some(command) = command else {
+                    continue;
+                };
+                if !is_due(at, now) || (moment == Moment::StartingSoon && start_time <= now) {
+                    continue;
+                }
+                if fired.insert((moment, id.clone(), *start)) {
+                    let payload = serde_json::to_string(event).unwrap_or_default();
+                    spawn(command, moment.as_str(), payload, event_env(event));
+                }
+            }
+        }
+
+        let horizon = now - Duration::minutes(GRACE_MINUTES);
+        tracked.retain(|_, event| event.end_time.with_timezone(&Utc) > horizon);
+        fired.retain(|(_, id, start)| tracked.contains_key(&(id.clone(), *start)));
+    }
+}
+
+/// Adds new events and forgets upcoming ones that vanished (cancelled or
+/// moved; a moved event comes back under its new start).
+fn track(tracked: &mut HashMap<(String, i64), CalendarEvent>, events: &[CalendarEvent]) {
+    let now = Utc::now();
+    let current: HashSet<(String, i64)> = events.iter().map(key).collect();
+    tracked.retain(|k, event| event.start_time.with_timezone(&Utc) <= now || current.contains(k));
+
+    for event in events.iter().filter(|e| !e.all_day) {
+        tracked.insert(key(event), event.clone());
+    }
+}
+
+fn key(event: &CalendarEvent) -> (String, i64) {
+    (event.id.clone(), event.start_time.timestamp())
+}
+
+fn is_due(at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    at <= now && now - at < Duration::minutes(GRACE_MINUTES)
+}
+
+/// Describes `event` to a command, which may not want to parse JSON.
+pub fn event_env(event: &CalendarEvent) -> Vec<(&'static str, String)> {
+    vec![
+        ("CALLUX_EVENT_ID", event.id.clone()),
+        ("CALLUX_EVENT_TITLE", event.title.clone()),
+        ("CALLUX_EVENT_START", event.start_time.to_rfc3339()),
+        ("CALLUX_EVENT_END", event.end_time.to_rfc3339()),
+        ("CALLUX_EVENT_CALENDAR", event.calendar_name.clone()),
+        (
+            "CALLUX_EVENT_MEETING_URL",
+            event.meeting_url.clone().unwrap_or_default(),
+        ),
+    ]
+}
+
+/// Runs `command` with `sh -c`, `payload` on stdin and `CALLUX_HOOK` naming
+/// what happened, without waiting for it.
+fn spawn(command: &str, hook: &'static str, payload: String, env: Vec<(&'static str, String)>) {
+    let mut child = match tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("CALLUX_HOOK", hook)
+        .envs(env)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Warning: Failed to run {} hook: {}", hook, e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        if let Some(mut stdin) = child.stdin.take() {
+            // Hooks that ignore stdin close it early; that's not an error.
+            let _ = stdin.write_all(payload.as_bytes()).await;
+        }
+        match child.wait().await {
+            Ok(status) if !status.success() => {
+                eprintln!("Warning: {} hook exited with {}", hook, status)
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: {} hook failed: {}", hook, e),
+        }
+    });
+}
@@ -0,0 +1,81 @@
+use crate::config::WorkingHoursConfig;
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime};
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Parsed `display.working_hours`: one optional range per weekday, Monday
+/// first.
+#[derive(Debug, Clone)]
+pub struct WorkingHours {
+    days: [Option<(NaiveTime, NaiveTime)>; 7],
+}
+
+impl WorkingHours {
+    pub fn parse(config: &WorkingHoursConfig) -> Result<Self, String> {
+        let mut days = [None; 7];
+        match config {
+            WorkingHoursConfig::Daily(range) => {
+                let range = parse_range(range)?;
+                // A single range means the working week.
+                for day in days.iter_mut().take(5) {
+                    *day = range;
+                }
+            }
+            WorkingHoursConfig::Weekly(ranges) => {
+                for (key, range) in ranges {
+                    let index = WEEKDAYS
+                        .iter()
+                        .position(|day| key.eq_ignore_ascii_case(day))
+                        .ok_or_else(|| {
+                            format!("Unknown weekday '{}' (use {})", key, WEEKDAYS.join(", "))
+                        })?;
+                    days[index] = parse_range(range)?;
+                }
+            }
+        }
+        Ok(Self { days })
+    }
+
+    /// Working time on `date`, if it is a working day.
+    pub fn window(&self, date: NaiveDate) -> Option<(NaiveTime, NaiveTime)> {
+        self.days[date.weekday().num_days_from_monday() as usize]
+    }
+
+    /// Whether any part of the span falls within working hours, on any of
+    /// the days it covers.
+    pub fn overlaps(&self, start: &DateTime<FixedOffset>, end: &DateTime<FixedOffset>) -> bool {
+        let (first, last) = (start.date_naive(), end.date_naive());
+        first
+            .iter_days()
+            .take_while(|date| *date <= last)
+            .any(|date| {
+                let Some((open, close)) = self.window(date) else {
+                    return false;
+                };
+                let from = if date == first {
+                    start.time()
+                } else {
+                    NaiveTime::MIN
+                };
+                from < close && (date < last || end.time() > open)
+            })
+    }
+}
+
+/// `"09:00-18:00"`; an empty string or `"off"` marks a day off.
+fn parse_range(range: &str) -> Result<Option<(NaiveTime, NaiveTime)>, String> {
+    let range = range.trim();
+    if range.is_empty() || range.eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+
+    let invalid = || format!("Invalid working hours '{}' (expected HH:MM-HH:MM)", range);
+    let (open, close) = range.split_once('-').ok_or_else(invalid)?;
+    let open = NaiveTime::parse_from_str(open.trim(), "%H:%M").map_err(|_| invalid())?;
+    let close = NaiveTime::parse_from_str(close.trim(), "%H:%M").map_err(|_| invalid())?;
+    if close <= open {
+        return Err(format!("Working hours '{}' end before they start", range));
+    }
+
+    Ok(Some((open, close)))
+}
@@ -0,0 +1,105 @@
+use colored::*;
+use hyper::{Method, StatusCode};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Query parameters whose values could let someone else read the calendar.
+const SECRET_PARAMS: &[&str] = &["access_token", "key", "pageToken", "syncToken"];
+
+/// Turns on tracing for `--debug-http`, or for `RUST_LOG` naming
+/// `callux::http` at trace level.
+pub fn init(flag: bool) {
+    let from_env = std::env::var("RUST_LOG").is_ok_and(|filter| wants_trace(&filter));
+    if flag || from_env {
+        ENABLED.store(true, Ordering::Relaxed);
+    }
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Accepts the `env_logger` spellings that would include `callux::http`
+/// traces: `trace`, `callux=trace` and `callux::http=trace`, or the target
+/// on its own.
+fn wants_trace(filter: &str) -> bool {
+    filter.split(',').map(str::trim).any(|directive| {
+        let (target, level) = match directive.split_once('=') {
+            Some((target, level)) => (target, Some(level)),
+            None if directive.eq_ignore_ascii_case("trace") => ("", Some("trace")),
+            None => (directive, None),
+        };
+        let covers_http = matches!(target, "" | "callux" | "callux::http");
+        covers_http && level.is_none_or(|level| level.eq_ignore_ascii_case("trace"))
+    })
+}
+
+pub fn request(method: &Method, url: &str, attempt: u32) {
+    if !enabled() {
+        return;
+    }
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let retry = if attempt > 0 {
+        format!(" (retry {})", attempt)
+    } else {
+        String::new()
+    };
+    eprintln!("{} {} {}{}", label(), method, path, retry);
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        eprintln!("{}   {} = {}", label(), name, redact(name, value));
+    }
+}
+
+pub fn response(status: StatusCode, bytes: usize, etag: Option<&str>, elapsed: Duration) {
+    if !enabled() {
+        return;
+    }
+    let mut line = format!(
+        "{} {} in {:.0}ms, {} bytes",
+        label(),
+        status,
+        ms(elapsed),
+        bytes
+    );
+    if let Some(etag) = etag {
+        line.push_str(&format!(", etag {}", etag));
+    }
+    eprintln!("{}", line);
+}
+
+pub fn failure(error: &dyn std::fmt::Display, retry_after: Option<Duration>, elapsed: Duration) {
+    if !enabled() {
+        return;
+    }
+    let mut line = format!("{} failed after {:.0}ms: {}", label(), ms(elapsed), error);
+    if let Some(delay) = retry_after {
+        line.push_str(&format!(" (Retry-After {}s)", delay.as_secs()));
+    }
+    eprintln!("{}", line);
+}
+
+/// What callux made of a response, e.g. how many events a page held.
+pub fn note(message: &str) {
+    if enabled() {
+        eprintln!("{} {}", label(), message);
+    }
+}
+
+fn redact<'a>(name: &str, value: &'a str) -> &'a str {
+    if SECRET_PARAMS.contains(&name) {
+        "[redacted]"
+    } else {
+        value
+    }
+}
+
+fn label() -> ColoredString {
+    "http:".bright_blue()
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,328 @@
+use crate::calendar::NewEvent;
+use crate::when::{self, Start};
+use crate::zone::Zone;
+use anyhow::{Context, Result, bail};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use std::path::Path;
+
+/// One event from the file, or why it can't be imported.
+pub struct Row {
+    /// Line (CSV) or event number (ICS), for error reports.
+    pub position: usize,
+    pub event: std::result::Result<NewEvent, String>,
+}
+
+/// Reads `path` as CSV or iCalendar, by extension.
+pub fn read(path: &Path, zone: Zone) -> Result<Vec<Row>> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("csv") => read_csv(path, zone),
+        Some("ics") | Some("ical") => {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            Ok(read_ics(&text, zone))
+        }
+        _ => bail!(
+            "Don't know how to import {}; use a .csv or .ics file",
+            path.display()
+        ),
+    }
+}
+
+/// Columns by header name: `title` and `start` are required; `end` or
+/// `duration` (default 1h), `location` and `description` are optional.
+/// Times use the same formats as `callux add --at`.
+fn read_csv(path: &Path, zone: Zone) -> Result<Vec<Row>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let headers: Vec<String> = reader
+        .headers()?
+        .iter()
+        .map(|header| header.to_ascii_lowercase())
+        .collect();
+    let column = |name: &str| headers.iter().position(|header| header == name);
+    let (Some(title), Some(start)) = (column("title"), column("start")) else {
+        bail!("The CSV needs 'title' and 'start' columns");
+    };
+    let (end, duration) = (column("end"), column("duration"));
+    let (location, description) = (column("location"), column("description"));
+
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        // Line 1 is the header.
+        let position = i + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                rows.push(Row {
+                    position,
+                    event: Err(e.to_string()),
+                });
+                continue;
+            }
+        };
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|i| record.get(i))
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+
+        let event = (|| -> std::result::Result<NewEvent, String> {
+            let title = field(Some(title)).ok_or("No title")?;
+            let start_at = field(Some(start)).ok_or("No start")?;
+            let start = when::parse_start(&start_at, zone)?;
+            let duration = match (field(end), field(duration)) {
+                (Some(end), _) => length(start, when::parse_start(&end, zone)?)?,
+                (None, Some(duration)) => when::parse_duration(&duration)?,
+                (None, None) => Duration::hours(1),
+            };
+            Ok(new_event(
+                title,
+                start,
+                duration,
+                field(location),
+                field(description),
+                None,
+            ))
+        })();
+        rows.push(Row { position, event });
+    }
+    Ok(rows)
+}
+
+/// Every `VEVENT`, with its start, end or duration, location, description
+/// and recurrence rule. Changed occurrences of recurring events
+/// (`RECURRENCE-ID`) are reported rather than imported.
+fn read_ics(text: &str, zone: Zone) -> Vec<Row> {
+    let mut rows = Vec::new();
+    let mut properties: Option<Vec<Property>> = None;
+
+    for line in unfold(text) {
+        if line.eq_ignore_ascii_case("BEGIN:VEVENT") {
+            properties = Some(Vec::new());
+        } else if line.eq_ignore_ascii_case("END:VEVENT") {
+            if let Some(properties) = properties.take() {
+                rows.push(Row {
+                    position: rows.len() + 1,
+                    event: ics_event(&properties, zone),
+                });
+            }
+        } else if let Some(properties) = properties.as_mut() {
+            if let Some(property) = Property::parse(&line) {
+                properties.push(property);
+            }
+        }
+    }
+    rows
+}
+
+fn ics_event(properties: &[Property], zone: Zone) -> std::result::Result<NewEvent, String> {
+    let get = |name: &str| properties.iter().find(|p| p.name == name);
+
+    if get("RECURRENCE-ID").is_some() {
+        return Err("Changed occurrence of a recurring event; skipped".to_string());
+    }
+    let title = get("SUMMARY")
+        .map(|p| unescape(&p.value))
+        .unwrap_or_else(|| "Untitled".to_string());
+    let start = ics_time(get("DTSTART").ok_or("No DTSTART")?, zone)?;
+    let duration = match (get("DTEND"), get("DURATION")) {
+        (Some(end), _) => length(start, ics_time(end, zone)?)?,
+        (None, Some(duration)) => iso_duration(&duration.value)?,
+        // RFC 5545: a date alone lasts the day, a time alone is instant.
+        (None, None) => match start {
+            Start::AllDay(_) => Duration::days(1),
+            Start::Timed(_) => Duration::minutes(30),
+        },
+    };
+    let recurrence = match get("RRULE") {
+        Some(rule) => Some(when::normalize_rrule(&rule.value)?),
+        None => None,
+    };
+
+    Ok(new_event(
+        title,
+        start,
+        duration,
+        get("LOCATION").map(|p| unescape(&p.value)),
+        get("DESCRIPTION").map(|p| unescape(&p.value)),
+        recurrence,
+    ))
+}
+
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    /// `NAME;PARAM=VALUE;...:value`, where quoted parameter values may
+    /// contain `:`.
+    fn parse(line: &str) -> Option<Self> {
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(i, c)| {
+            match c {
+                '"' => quoted = !quoted,
+                ':' if !quoted => return Some(i),
+                _ => {}
+            }
+            None
+        })?;
+        let (head, value) = (&line[..colon], &line[colon + 1..]);
+        let mut parts = head.split(';');
+        let name = parts.next()?.to_ascii_uppercase();
+        let params = parts
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| {
+                (
+                    key.to_ascii_uppercase(),
+                    value.trim_matches('"').to_string(),
+                )
+            })
+            .collect();
+        Some(Self {
+            name,
+            params,
+            value: value.to_string(),
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// `DTSTART`/`DTEND`: a date, a UTC time (`...Z`), a time in `TZID`, or a
+/// floating time read in `zone`.
+fn ics_time(property: &Property, zone: Zone) -> std::result::Result<Start, String> {
+    let value = property.value.trim();
+    let invalid = || format!("Invalid {} '{}'", property.name, value);
+
+    if property.param("VALUE") == Some("DATE") || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map(Start::AllDay)
+            .map_err(|_| invalid());
+    }
+
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+        return Ok(Start::Timed(time.and_utc().fixed_offset()));
+    }
+
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+    let zone = match property.param("TZID") {
+        Some(tzid) => Zone::parse(tzid)?,
+        None => zone,
+    };
+    zone.at(time)
+        .map(Start::Timed)
+        .ok_or_else(|| format!("{} doesn't exist in its timezone (DST change)", value))
+}
+
+/// `end - start`, in whole days for all-day events.
+fn length(start: Start, end: Start) -> std::result::Result<Duration, String> {
+    let duration = match (start, end) {
+        (Start::Timed(start), Start::Timed(end)) => end - start,
+        (Start::AllDay(start), Start::AllDay(end)) => end - start,
+        _ => return Err("Start and end mix a date with a time".to_string()),
+    };
+    if duration <= Duration::zero() {
+        return Err("Ends before it starts".to_string());
+    }
+    Ok(duration)
+}
+
+/// ISO 8601 durations as iCalendar uses them: `PT1H30M`, `P1D`, `P2W`.
+fn iso_duration(input: &str) -> std::result::Result<Duration, String> {
+    let invalid = || format!("Invalid DURATION '{}'", input);
+    let rest = input.trim().strip_prefix('P').ok_or_else(invalid)?;
+
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+        match c {
+            'T' => in_time = true,
+            c if c.is_ascii_digit() => number.push(c),
+            unit => {
+                let value: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                total += match (unit, in_time) {
+                    ('W', false) => Duration::weeks(value),
+                    ('D', false) => Duration::days(value),
+                    ('H', true) => Duration::hours(value),
+                    ('M', true) => Duration::minutes(value),
+                    ('S', true) => Duration::seconds(value),
+                    _ => return Err(invalid()),
+                };
+            }
+        }
+    }
+    if !number.is_empty() || total <= Duration::zero() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// Joins continuation lines (starting with a space or tab) onto the line
+/// before them.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn new_event(
+    title: String,
+    start: Start,
+    duration: Duration,
+    location: Option<String>,
+    description: Option<String>,
+    recurrence: Option<String>,
+) -> NewEvent {
+    NewEvent {
+        title,
+        start,
+        duration,
+        location,
+        description,
+        meet: false,
+        invitees: Vec::new(),
+        reminders: Vec::new(),
+        recurrence,
+        send_updates: None,
+    }
+}
@@ -0,0 +1,83 @@
+use crate::config::InhibitConfig;
+use crate::meeting;
+use crate::output::CalendarEvent;
+use chrono::Utc;
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+
+/// How often the lock is checked against the current call, so it is taken
+/// and released when calls start and end, not only when the agenda refreshes.
+const TICK: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Holds a logind inhibitor lock (via `systemd-inhibit`) while a call (an
+/// event with a meeting link) is running, so the screen doesn't lock or the
+/// machine suspend mid-call, until the daemon exits. hypridle and swayidle
+/// honour these locks.
+pub async fn run(config: InhibitConfig, mut agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    // The lock lives exactly as long as this child. It is keyed by event and
+    // end time, so a meeting that gets extended renews it.
+    let mut lock: Option<((String, i64), Child)> = None;
+    let mut ticker = tokio::time::interval(TICK);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            changed = agenda.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+            }
+        }
+
+        let current = meeting::current(&agenda.borrow(), &config.calendars, true).cloned();
+        let unchanged = match (&lock, &current) {
+            (Some((key, _)), Some(event)) => *key == lock_key(event),
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            continue;
+        }
+
+        release(lock.take());
+        if let Some(event) = current {
+            lock = acquire(&config.what, &event).map(|child| (lock_key(&event), child));
+        }
+    }
+
+    release(lock);
+}
+
+/// The lock's `sleep` runs out when the event ends, so a daemon that dies
+/// without cleaning up can't leave the machine awake for good.
+fn acquire(what: &str, event: &CalendarEvent) -> Option<Child> {
+    let remaining = (event.end_time.with_timezone(&Utc) - Utc::now())
+        .num_seconds()
+        .max(1);
+    let child = Command::new("systemd-inhibit")
+        .arg(format!("--what={}", what))
+        .arg("--who=callux")
+        .arg(format!("--why=In a meeting: {}", event.title))
+        .arg("--mode=block")
+        .args(["sleep", &remaining.to_string()])
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .spawn();
+    match child {
+        Ok(child) => Some(child),
+        Err(e) => {
+            eprintln!("Warning: Failed to run systemd-inhibit: {}", e);
+            None
+        }
+    }
+}
+
+fn lock_key(event: &CalendarEvent) -> (String, i64) {
+    (event.id.clone(), event.end_time.timestamp())
+}
+
+fn release(lock: Option<((String, i64), Child)>) {
+    if let Some((_, mut child)) = lock {
+        let _ = child.start_kill();
+    }
+}
@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Entries kept for `callux undo`; older ones are dropped.
+const MAX_ENTRIES: usize = 20;
+
+/// A write callux made, with what's needed to reverse it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    pub operation: Operation,
+    /// Where the event is now.
+    pub calendar_id: String,
+    pub event_id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Created,
+    /// `restore` holds the patched fields as they were, `null` where unset.
+    Updated {
+        restore: serde_json::Value,
+    },
+    Deleted,
+    Moved {
+        from: String,
+    },
+}
+
+impl Operation {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Operation::Created => "created",
+            Operation::Updated { .. } => "changed",
+            Operation::Deleted => "deleted",
+            Operation::Moved { .. } => "moved",
+        }
+    }
+}
+
+/// `$XDG_DATA_HOME/callux/undo.json`, next to the token.
+pub fn path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("callux")
+        .join("undo.json")
+}
+
+/// Oldest first. A missing or unreadable journal is empty.
+pub fn load() -> Vec<Entry> {
+    let Ok(text) = std::fs::read_to_string(path()) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        eprintln!("Warning: Ignoring corrupt undo journal: {}", e);
+        Vec::new()
+    })
+}
+
+pub fn save(entries: &[Entry]) -> std::io::Result<()> {
+    let path = path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let start = entries.len().saturating_sub(MAX_ENTRIES);
+    std::fs::write(&path, serde_json::to_string_pretty(&entries[start..])?)
+}
+
+/// Appends an entry. The write already happened, so failing to journal it
+/// only warns.
+pub fn record(operation: Operation, calendar_id: &str, event_id: &str, title: &str) {
+    let mut entries = load();
+    entries.push(Entry {
+        at: Utc::now(),
+        operation,
+        calendar_id: calendar_id.to_string(),
+        event_id: event_id.to_string(),
+        title: title.to_string(),
+    });
+    if let Err(e) = save(&entries) {
+        eprintln!("Warning: Failed to update undo journal: {}", e);
+    }
+}
@@ -0,0 +1,39 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An exclusive advisory lock shared by every callux process, held until
+/// dropped. Several bars starting at once would otherwise each open a
+/// browser to sign in, or write the same file at the same moment.
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// Waits until no other process holds the lock at `path`, creating the
+    /// lock file if needed.
+    pub fn acquire(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        file.lock()?;
+        Ok(Self { _file: file })
+    }
+
+    /// `acquire` off the async runtime, for locks that may be held for as
+    /// long as someone takes to finish signing in.
+    pub async fn acquire_async(path: PathBuf) -> io::Result<Self> {
+        tokio::task::spawn_blocking(move || Self::acquire(&path))
+            .await
+            .map_err(io::Error::other)?
+    }
+}
+
+/// `path` with `.lock` appended, next to the file it protects.
+pub fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".lock");
+    PathBuf::from(name)
+}
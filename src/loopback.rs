@@ -0,0 +1,229 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use yup_oauth2::authenticator_delegate::InstalledFlowDelegate;
+
+const SUCCESS_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>Callux - Authorized</title>
+  <style>
+    body { font-family: sans-serif; background: #1e1e2e; color: #cdd6f4; text-align: center; padding-top: 15vh; }
+    h1 { color: #a6e3a1; }
+  </style>
+</head>
+<body>
+  <h1>Callux is connected to Google Calendar</h1>
+  <p>You can close this tab and return to your terminal.</p>
+  <script>setTimeout(function () { window.close(); }, 1500);</script>
+</body>
+</html>
+"#;
+
+const FAILURE_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>Callux - Authorization failed</title>
+  <style>
+    body { font-family: sans-serif; background: #1e1e2e; color: #cdd6f4; text-align: center; padding-top: 15vh; }
+    h1 { color: #f38ba8; }
+  </style>
+</head>
+<body>
+  <h1>Callux was not authorized</h1>
+  <p>{reason}</p>
+  <p>Close this tab and run <code>callux auth</code> again.</p>
+</body>
+</html>
+"#;
+
+/// Runs the OAuth redirect listener on a loopback port so we control the
+/// page the browser lands on and how long we wait for it.
+pub struct LoopbackFlowDelegate {
+    redirect_uri: String,
+    listener: Mutex<Option<std::net::TcpListener>>,
+    timeout: Duration,
+}
+
+impl LoopbackFlowDelegate {
+    pub fn bind(timeout: Duration) -> std::io::Result<Self> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+
+        Ok(Self {
+            redirect_uri: format!("http://127.0.0.1:{}", port),
+            listener: Mutex::new(Some(listener)),
+            timeout,
+        })
+    }
+
+    async fn wait_for_code(&self, url: &str) -> Result<String, String> {
+        let listener = self
+            .listener
+            .lock()
+            .map_err(|_| "Loopback listener lock poisoned".to_string())?
+            .take()
+            .ok_or_else(|| "Loopback listener already used".to_string())?;
+        let listener = TcpListener::from_std(listener)
+            .map_err(|e| format!("Failed to start loopback listener: {}", e))?;
+
+        println!(
+            "Please direct your browser to {} and follow the instructions displayed there.",
+            url
+        );
+
+        tokio::time::timeout(self.timeout, accept_redirect(&listener))
+            .await
+            .map_err(|_| {
+                format!(
+                    "Timed out after {}s waiting for authorization in the browser",
+                    self.timeout.as_secs()
+                )
+            })?
+    }
+}
+
+impl InstalledFlowDelegate for LoopbackFlowDelegate {
+    fn redirect_uri(&self) -> Option<&str> {
+        Some(&self.redirect_uri)
+    }
+
+    fn present_user_url<'a>(
+        &'a self,
+        url: &'a str,
+        _need_code: bool,
+    ) -> Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>> {
+        Box::pin(self.wait_for_code(url))
+    }
+}
+
+async fn accept_redirect(listener: &TcpListener) -> Result<String, String> {
+    loop {
+        let (mut stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| format!("Failed to accept redirect connection: {}", e))?;
+
+        let Some(target) = read_request_target(&mut stream).await else {
+            respond(&mut stream, "400 Bad Request", "").await;
+            continue;
+        };
+
+        let params = parse_query(&target);
+        if let Some(code) = params.iter().find(|(k, _)| k == "code").map(|(_, v)| v) {
+            respond(&mut stream, "200 OK", SUCCESS_PAGE).await;
+            return Ok(code.clone());
+        }
+
+        if let Some(error) = params.iter().find(|(k, _)| k == "error").map(|(_, v)| v) {
+            let page = FAILURE_PAGE.replace(
+                "{reason}",
+                &format!("Google returned: {}", escape_html(error)),
+            );
+            respond(&mut stream, "200 OK", &page).await;
+            return Err(format!(
+                "Authorization was denied in the browser: {}",
+                error
+            ));
+        }
+
+        // Browsers also ask for things like /favicon.ico; keep waiting.
+        respond(&mut stream, "404 Not Found", "").await;
+    }
+}
+
+async fn read_request_target(stream: &mut TcpStream) -> Option<String> {
+    let mut buf = vec![0u8; 8192];
+    let mut len = 0;
+
+    while len < buf.len() {
+        let n = stream.read(&mut buf[len..]).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        len += n;
+        if buf[..len].windows(2).any(|w| w == b"\r\n") {
+            break;
+        }
+    }
+
+    let head = String::from_utf8_lossy(&buf[..len]);
+    let request_line = head.lines().next()?;
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => Some(target.to_string()),
+        _ => None,
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+fn parse_query(target: &str) -> Vec<(String, String)> {
+    let Some((_, query)) = target.split_once('?') else {
+        return Vec::new();
+    };
+
+    query
+        .split('&')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            Some((percent_decode(key), percent_decode(value)))
+        })
+        .collect()
+}
+
+fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 3;
+                    }
+                    None => {
+                        out.push(b'%');
+                        i += 1;
+                    }
+                }
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&out).to_string()
+}
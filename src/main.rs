@@ -1,70 +1,840 @@
+mod anonymize;
+mod api;
 mod auth;
+mod availability;
+mod bench;
 mod cache;
 mod calendar;
+mod classes;
 mod cli;
+mod completions;
 mod config;
+mod config_edit;
+mod daemon;
+#[cfg(feature = "dbus")]
+mod dbus;
+mod details;
+mod diagnostics;
+mod diff;
+mod dnd;
+mod doctor;
 mod error;
+mod event_edit;
+mod filter;
+mod fuzzy;
+mod hooks;
+mod hours;
+mod http_trace;
+mod import;
+mod inhibit;
+mod journal;
+mod lock;
+mod loopback;
+mod meeting;
+mod metrics;
+mod migrate;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "notifications")]
+mod notify;
 mod output;
+#[cfg(feature = "popup")]
+mod popup;
+mod push;
+mod quota;
+mod remind;
+mod rpc;
+mod sanitize;
+mod schema;
+mod security;
+mod self_update;
+mod serve;
+mod service;
+mod shutdown;
+mod snapshot;
+mod timings;
+mod tracking;
+mod urgency;
+mod validate;
+mod when;
+mod wizard;
+mod zone;
 
 use crate::auth::AuthManager;
-use crate::calendar::CalendarClient;
-use crate::cli::{Cli, Commands, ConfigAction};
-use crate::config::Config;
-use crate::output::OutputFormatter;
+use crate::cache::CacheStats;
+use crate::calendar::{Agenda, CalendarClient, EventChanges, Invitee, Lookahead, NewEvent};
+use crate::cli::{
+    CacheAction, CalendarsAction, Cli, Commands, CompleteKind, ConfigAction, CountdownFormat,
+    DebugAction, GroupBy, OrganizerFilter, RsvpResponse, SendUpdates, ServiceAction,
+};
+use crate::config::{
+    CalendarConfig, Config, DEFAULT_CALENDAR_COLOR, TemplateConfig, VisibilityPolicy,
+};
+use crate::config_edit::ConfigEditor;
+use crate::error::CalendarError;
+use crate::event_edit::EventForm;
+use crate::filter::EventFilter;
+use crate::output::{CalendarEvent, ErrorOutput, EventKind, OutputFormatter};
+use crate::when::Recurrence;
+use crate::zone::Zone;
+use anyhow::Context;
+use chrono::Utc;
 use clap::Parser;
 use colored::*;
+use google_calendar3::api::CalendarListEntry;
 use rustls::crypto::ring::default_provider;
+use std::io::{IsTerminal, Read};
 
 #[tokio::main]
 async fn main() {
     default_provider()
         .install_default()
         .expect("Failed to install crypto provider");
+    let start = std::time::Instant::now();
     let cli = Cli::parse();
+    if cli.timings {
+        timings::enable();
+    }
+    http_trace::init(cli.debug_http);
+    if let Some(path) = &cli.config {
+        Config::set_path_override(path);
+    }
+    let profile = cli
+        .profile
+        .clone()
+        .or_else(|| std::env::var("CALLUX_PROFILE").ok())
+        .filter(|name| !name.is_empty());
+    if let Some(name) = &profile {
+        Config::set_profile(name);
+    }
 
-    match run(cli).await {
+    let command = match cli.command {
+        Some(command) => Ok(command),
+        None => default_command(),
+    };
+    let json_errors = command.as_ref().is_ok_and(Commands::wants_json);
+    shutdown::exit_when_stuck();
+    // Dropping `run` on a signal cancels requests in flight; cache writes
+    // don't await, so they finish first.
+    let outcome = match command {
+        Ok(command) if command.handles_signals() => Some(run(command, cli.insecure_ok).await),
+        Ok(command) => tokio::select! {
+            result = run(command, cli.insecure_ok) => Some(result),
+            () = shutdown::signalled() => None,
+        },
+        Err(e) => Some(Err(e)),
+    };
+    timings::report(start.elapsed());
+    let Some(result) = outcome else {
+        eprintln!("Interrupted");
+        std::process::exit(shutdown::EXIT_CODE);
+    };
+
+    match result {
         Ok(()) => {}
         Err(e) => {
-            eprintln!("{}: {}", "Error".red().bold(), e);
+            let message = error_message(&e);
+            let cause = e
+                .chain()
+                .find_map(|cause| cause.downcast_ref::<CalendarError>());
+            if let Some(cause) = cause {
+                diagnostics::record_error(cause);
+            }
+            if json_errors {
+                println!("{}", ErrorOutput::new(message, cause).to_json());
+            } else {
+                eprintln!("{}: {}", "Error".red().bold(), message);
+                if let Some(hint) = cause.and_then(|e| e.class().hint()) {
+                    eprintln!("{}: {}", "Hint".bright_yellow(), hint);
+                }
+                if let Some(CalendarError::RateLimited(reason)) = cause {
+                    if let Ok(config) = Config::load() {
+                        for line in quota::advice(&config, reason) {
+                            eprintln!("  {}", line);
+                        }
+                    }
+                }
+            }
             std::process::exit(1);
         }
     }
 }
 
-async fn run(cli: Cli) -> anyhow::Result<()> {
-    match cli.command {
+/// The error with its context. A `CalendarError` already spells out its own
+/// source, so the chain stops there.
+fn error_message(e: &anyhow::Error) -> String {
+    let mut parts = Vec::new();
+    for cause in e.chain() {
+        parts.push(cause.to_string());
+        if cause.is::<CalendarError>() {
+            break;
+        }
+    }
+    parts.join(": ")
+}
+
+fn load_config(insecure_ok: bool) -> anyhow::Result<Config> {
+    let mut config = timings::time("config", Config::load)?;
+    config.auth.allow_insecure_permissions |= insecure_ok;
+    Ok(config)
+}
+
+/// Parses `cli.default_command` from the config as if it had been typed
+/// after `callux`.
+fn default_command() -> anyhow::Result<Commands> {
+    let config = Config::load()?;
+    let line = config.cli.default_command.trim();
+    let args = std::iter::once("callux").chain(line.split_whitespace());
+
+    Cli::try_parse_from(args)
+        .map_err(|e| anyhow::anyhow!("Invalid cli.default_command '{}': {}", line, e))?
+        .command
+        .ok_or_else(|| anyhow::anyhow!("cli.default_command is empty"))
+}
+
+async fn run(command: Commands, insecure_ok: bool) -> anyhow::Result<()> {
+    match command {
         Commands::Agenda {
             format,
             limit,
             days,
+            hours,
+            until,
+            past,
+            offline,
+            no_daemon,
+            show_cancelled,
+            include_declined,
+            hide_gmail,
+            week_numbers,
+            group_by,
+            gaps,
+            color_by_response,
+            strict,
+            min_duration,
+            max_duration,
+            filter,
+            exclude,
+            property,
+            meetings_only,
+            blocks_only,
+            organizer,
+            hide_private,
+            only_public,
+            anonymize,
+            output,
         } => {
-            let config = Config::load()?;
-            let client = CalendarClient::new(config.clone());
-            let days_ahead = days.unwrap_or(7);
+            let mut config = load_config(insecure_ok)?;
+            config.display.show_cancelled |= show_cancelled;
+            config.display.hide_declined &= !include_declined;
+            config.display.show_from_gmail &= !hide_gmail;
+            config.display.show_week_numbers |= week_numbers;
+            config.display.show_gaps |= gaps;
+            config.display.color_by_response |= color_by_response;
+            let week_groups = group_by == GroupBy::Week;
+            let visibility = &mut config.display.visibility;
+            if hide_private {
+                *visibility = (*visibility).max(VisibilityPolicy::HidePrivate);
+            }
+            if only_public {
+                *visibility = VisibilityPolicy::OnlyPublic;
+            }
+            if min_duration.is_some() {
+                config.filters.min_duration = min_duration;
+            }
+            if max_duration.is_some() {
+                config.filters.max_duration = max_duration;
+            }
+            let lookahead = match (hours, &until) {
+                (Some(hours), _) => Lookahead::Hours(hours),
+                (None, Some(until)) => Lookahead::Until(
+                    when::parse_until(until, Zone::from_config(&config.display.timezone))
+                        .map_err(|e| anyhow::anyhow!(e))?,
+                ),
+                (None, None) => Lookahead::Days(days.unwrap_or(7)),
+            };
             let event_limit = limit.or(Some(config.display.max_events));
+            let query = if filter.is_empty() && exclude.is_empty() {
+                None
+            } else {
+                Some(EventFilter::from_patterns(filter, exclude)?)
+            };
+
+            let keep = |event: &CalendarEvent| {
+                let solo = event.guests == 0;
+                query
+                    .as_ref()
+                    .is_none_or(|query| query.allows(&event.title, event.location.as_deref()))
+                    && !(meetings_only && solo)
+                    && !(blocks_only && !solo)
+                    && property.iter().all(|wanted| match wanted.split_once('=') {
+                        Some((key, value)) => event.properties.get(key).is_some_and(|v| v == value),
+                        None => event.properties.contains_key(wanted.as_str()),
+                    })
+                    && organizer.is_none_or(|organizer| match organizer {
+                        OrganizerFilter::Me => event.is_organizer,
+                        OrganizerFilter::Others => !event.is_organizer,
+                    })
+            };
 
-            let events = client
-                .get_events(days_ahead, event_limit)
+            // One-off filters apply to the fetched agenda, so cached results
+            // and the daemon still serve it; the limit has to come after.
+            let post_filter = query.is_some()
+                || !property.is_empty()
+                || meetings_only
+                || blocks_only
+                || organizer.is_some();
+            let fetch_limit = if post_filter { None } else { event_limit };
+            let mut agenda =
+                fetch_agenda_within(&config, lookahead, past, fetch_limit, offline, no_daemon)
+                    .await?;
+            if post_filter {
+                agenda.events.retain(keep);
+                agenda.events.truncate(event_limit.unwrap_or(usize::MAX));
+            }
+            if strict && !agenda.warnings.is_empty() {
+                anyhow::bail!("{}", agenda.warnings.join("; "));
+            }
+
+            if anonymize {
+                anonymize::events(&mut agenda.events);
+                for warning in &mut agenda.warnings {
+                    *warning = diagnostics::scrub_quoted(warning);
+                }
+            }
+
+            let formatter = OutputFormatter::for_display(format, &config.display, week_groups)
+                .with_staleness(agenda.stale)
+                .with_warnings(agenda.warnings);
+
+            let text = timings::time("format", || formatter.format_events(&agenda.events));
+            emit(&text, output.as_deref())?;
+        }
+        Commands::Fmt {
+            format,
+            limit,
+            group_by,
+            anonymize,
+            output,
+        } => {
+            let mut config = load_config(insecure_ok)?;
+            if let Some(limit) = limit {
+                config.display.max_events = limit;
+            }
+
+            let mut input = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .context("Failed to read stdin")?;
+            let mut events: Vec<CalendarEvent> = serde_json::from_str(&input)
+                .context("Expected a JSON array of events on stdin (see `callux schema events`)")?;
+            // Merged sources needn't be in order; day grouping expects it.
+            events.sort_by_key(|event| event.start_time);
+            if anonymize {
+                anonymize::events(&mut events);
+            }
+
+            let formatter =
+                OutputFormatter::for_display(format, &config.display, group_by == GroupBy::Week);
+            emit(&formatter.format_events(&events), output.as_deref())?;
+        }
+        Commands::Add {
+            title,
+            template,
+            at,
+            duration,
+            calendar,
+            location,
+            description,
+            meet,
+            remind,
+            repeat,
+            every,
+            on,
+            until,
+            count,
+            rrule,
+            invite,
+            optional,
+            send_updates,
+            dry_run,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let zone = Zone::from_config(&config.display.timezone);
+            let template = match &template {
+                Some(name) => config
+                    .find_template(name)
+                    .ok_or_else(|| anyhow::anyhow!("No template named '{}'", name))?
+                    .clone(),
+                None => TemplateConfig::default(),
+            };
+            let title = title
+                .or(template.title)
+                .ok_or_else(|| anyhow::anyhow!("Give the event a title"))?;
+            let duration = duration
+                .or(template.duration)
+                .unwrap_or_else(|| "1h".to_string());
+            let calendar = calendar.or(template.calendar);
+            let location = location.or(template.location);
+            let description = description.or(template.description);
+            let meet = meet || template.meet;
+            let remind = if remind.is_empty() {
+                template.remind
+            } else {
+                remind
+            };
+
+            let start = when::parse_start(&at, zone).map_err(|e| anyhow::anyhow!(e))?;
+            let recurrence = match (repeat, rrule) {
+                (Some(repeat), _) => Some(
+                    Recurrence {
+                        frequency: repeat.as_frequency(),
+                        interval: every,
+                        on: match &on {
+                            Some(days) => {
+                                when::parse_weekdays(days).map_err(|e| anyhow::anyhow!(e))?
+                            }
+                            None => Vec::new(),
+                        },
+                        until: match &until {
+                            Some(date) => Some(
+                                when::parse_date(date, zone.now().date_naive())
+                                    .map_err(|e| anyhow::anyhow!(e))?,
+                            ),
+                            None => None,
+                        },
+                        count,
+                    }
+                    .rrule(start, zone),
+                ),
+                (None, Some(rule)) => {
+                    Some(when::normalize_rrule(&rule).map_err(|e| anyhow::anyhow!(e))?)
+                }
+                (None, None) => None,
+            };
+            let mut invitees = parse_invitees(invite.as_deref(), optional.as_deref())?;
+            invitees.extend(parse_invitees(
+                Some(&template.invite.join(",")),
+                Some(&template.optional.join(",")),
+            )?);
+            let reminders = parse_reminders(&remind)?;
+            let new_event = NewEvent {
+                title,
+                start,
+                duration: when::parse_duration(&duration).map_err(|e| anyhow::anyhow!(e))?,
+                location,
+                description,
+                meet,
+                reminders,
+                recurrence,
+                send_updates: send_updates_param(send_updates, &invitees),
+                invitees,
+            };
+            let target = target_calendar(&config, calendar.as_deref())?;
+
+            let client = CalendarClient::new(config.clone()).with_dry_run(dry_run);
+            let created = client
+                .create_event(&target, &new_event)
                 .await
-                .map_err(|e| anyhow::anyhow!("Failed to get events: {}", e))?;
+                .context("Failed to create event")?;
+            if dry_run {
+                return Ok(());
+            }
+            daemon::request_refresh(&config).await;
 
-            let formatter = OutputFormatter::new(
-                format,
-                config.display.date_format,
-                config.display.max_events,
+            println!(
+                "Added {} to {} ({})",
+                created.title,
+                target.name,
+                created.id.bright_green()
             );
+            if meet {
+                print_meet_link(&created);
+            }
+        }
+        Commands::Edit {
+            event,
+            calendar,
+            title,
+            at,
+            duration,
+            location,
+            description,
+            invite,
+            optional,
+            send_updates,
+            dry_run,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let zone = Zone::from_config(&config.display.timezone);
+            let invitees = parse_invitees(invite.as_deref(), optional.as_deref())?;
+            let client = CalendarClient::new(config.clone()).with_dry_run(dry_run);
+            let found = client
+                .find_event(&event, calendar.as_deref())
+                .await
+                .context("Failed to look up event")?;
+
+            let flags_given = [&title, &at, &duration, &location, &description]
+                .iter()
+                .any(|flag| flag.is_some());
+            let mut changes = if flags_given {
+                event_edit::changes_from_flags(
+                    &found,
+                    zone,
+                    title,
+                    at,
+                    duration,
+                    location,
+                    description,
+                )?
+            } else if invitees.is_empty() {
+                let form = EventForm::from_event(&found);
+                event_edit::edit_in_editor(&form, zone)?.changes_from(&found, zone)?
+            } else {
+                EventChanges::default()
+            };
+            if !invitees.is_empty() {
+                changes.attendees = Some(calendar::with_invitees(&found.attendees, &invitees));
+            }
+            changes.send_updates = send_updates_param(send_updates, &invitees);
+            if changes.is_empty() {
+                println!("No changes");
+                return Ok(());
+            }
+
+            let updated = client
+                .update_event(&found.calendar, &found.event.id, &changes)
+                .await
+                .context("Failed to update event")?;
+            if dry_run {
+                return Ok(());
+            }
+            daemon::request_refresh(&config).await;
+
+            println!("Updated {} ({})", updated.title, updated.id.bright_green());
+        }
+        Commands::Delete {
+            event,
+            calendar,
+            instance,
+            series,
+            yes,
+            dry_run,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let zone = Zone::from_config(&config.display.timezone);
+            let client = CalendarClient::new(config.clone()).with_dry_run(dry_run);
+            let found = client
+                .find_event(&event, calendar.as_deref())
+                .await
+                .context("Failed to look up event")?;
+
+            let when = zone
+                .convert(&found.event.start_time)
+                .format("%a %b %-d %H:%M");
+            // Recurring events without --instance/--series get one question
+            // that doubles as the confirmation.
+            let choose = found.series.is_some() && !instance && !series;
+            let whole_series = if choose {
+                if yes {
+                    anyhow::bail!(
+                        "'{}' is recurring; pass --instance or --series",
+                        found.event.title
+                    );
+                }
+                eprint!(
+                    "'{}' ({}) is recurring. Delete this (o)ccurrence or the whole (s)eries? [o/s/N] ",
+                    found.event.title, when
+                );
+                match read_answer()?.as_str() {
+                    "o" => false,
+                    "s" => true,
+                    _ => anyhow::bail!("Nothing deleted"),
+                }
+            } else {
+                series
+            };
+
+            if !yes && !dry_run && !choose {
+                let what = if whole_series {
+                    "every occurrence of "
+                } else {
+                    ""
+                };
+                eprint!(
+                    "Delete {}'{}' ({}) from {}? [y/N] ",
+                    what, found.event.title, when, found.calendar.name
+                );
+                if read_answer()? != "y" {
+                    anyhow::bail!("Nothing deleted");
+                }
+            }
+
+            let target = match (&found.series, whole_series) {
+                (Some(series), true) => series.clone(),
+                _ => found.event.id.clone(),
+            };
+            client
+                .delete_event(&found.calendar, &target)
+                .await
+                .context("Failed to delete event")?;
+            if dry_run {
+                return Ok(());
+            }
+            daemon::request_refresh(&config).await;
+
+            println!("Deleted {} ({})", found.event.title, target.bright_green());
+        }
+        Commands::Dup {
+            event,
+            at,
+            duration,
+            meet,
+            to,
+            calendar,
+            dry_run,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let zone = Zone::from_config(&config.display.timezone);
+            let start = when::parse_start(&at, zone).map_err(|e| anyhow::anyhow!(e))?;
+            let destination = match &to {
+                Some(name) => Some(config.find_calendar(name)?.clone()),
+                None => None,
+            };
+            let client = CalendarClient::new(config.clone()).with_dry_run(dry_run);
+            let found = client
+                .find_event(&event, calendar.as_deref())
+                .await
+                .context("Failed to look up event")?;
+
+            let duration = match duration {
+                Some(duration) => {
+                    when::parse_duration(&duration).map_err(|e| anyhow::anyhow!(e))?
+                }
+                None => found.event.end_time - found.event.start_time,
+            };
+            let destination = destination.unwrap_or_else(|| found.calendar.clone());
+            let copy = client
+                .duplicate_event(&found, start, duration, &destination, meet)
+                .await
+                .context("Failed to copy event")?;
+            if dry_run {
+                return Ok(());
+            }
+            daemon::request_refresh(&config).await;
 
-            let output = formatter.format_events(&events);
-            println!("{}", output);
+            println!(
+                "Copied {} to {} in {} ({})",
+                copy.title,
+                when::format_start(start),
+                destination.name,
+                copy.id.bright_green()
+            );
+            if meet {
+                print_meet_link(&copy);
+            }
+        }
+        Commands::Move {
+            event,
+            to,
+            calendar,
+            dry_run,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let destination = config.find_calendar(&to)?.clone();
+            let client = CalendarClient::new(config.clone()).with_dry_run(dry_run);
+            let found = client
+                .find_event(&event, calendar.as_deref())
+                .await
+                .context("Failed to look up event")?;
+            if found.calendar.id == destination.id {
+                anyhow::bail!(
+                    "'{}' is already in {}",
+                    found.event.title,
+                    config.calendar_label(&destination)
+                );
+            }
+
+            let moved = client
+                .move_event(&found.calendar, &found.event.id, &destination)
+                .await
+                .context("Failed to move event")?;
+            if dry_run {
+                return Ok(());
+            }
+            daemon::request_refresh(&config).await;
+
+            println!(
+                "Moved {} from {} to {} ({})",
+                moved.title,
+                config.calendar_label(&found.calendar),
+                config.calendar_label(&destination),
+                moved.id.bright_green()
+            );
+        }
+        Commands::Rsvp {
+            event,
+            response,
+            comment,
+            calendar,
+            dry_run,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let client = CalendarClient::new(config.clone()).with_dry_run(dry_run);
+            let found = client
+                .find_event(&event, calendar.as_deref())
+                .await
+                .context("Failed to look up event")?;
+
+            let (status, done) = match response {
+                RsvpResponse::Accept => ("accepted", "Accepted"),
+                RsvpResponse::Decline => ("declined", "Declined"),
+                RsvpResponse::Tentative => ("tentative", "Tentatively accepted"),
+            };
+            let updated = client
+                .respond(&found.calendar, &found.event.id, status, comment.as_deref())
+                .await
+                .context("Failed to respond")?;
+            if dry_run {
+                return Ok(());
+            }
+            daemon::request_refresh(&config).await;
+
+            println!("{} {} ({})", done, updated.title, updated.id.bright_green());
+        }
+        Commands::Import {
+            file,
+            calendar,
+            yes,
+            dry_run,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let zone = Zone::from_config(&config.display.timezone);
+            let target = target_calendar(&config, calendar.as_deref())?;
+            let rows = import::read(std::path::Path::new(&file), zone)?;
+
+            println!("{}", "Import Preview:".bright_blue().bold());
+            for row in &rows {
+                match &row.event {
+                    Ok(event) => println!(
+                        "  {:>4}  {:<16}  {:>6}  {}{}",
+                        row.position,
+                        when::format_start(event.start),
+                        when::format_duration(event.duration),
+                        event.title,
+                        if event.recurrence.is_some() {
+                            " (recurring)"
+                        } else {
+                            ""
+                        }
+                    ),
+                    Err(e) => println!("  {:>4}  {}  {}", row.position, "invalid".red(), e),
+                }
+            }
+
+            let valid: Vec<_> = rows
+                .iter()
+                .filter_map(|row| Some((row.position, row.event.as_ref().ok()?)))
+                .collect();
+            let invalid = rows.len() - valid.len();
+            if valid.is_empty() {
+                anyhow::bail!("Nothing to import");
+            }
+            if !yes && !dry_run {
+                let skipped = if invalid > 0 {
+                    format!(" ({} invalid skipped)", invalid)
+                } else {
+                    String::new()
+                };
+                eprint!(
+                    "Import {} events into {}{}? [y/N] ",
+                    valid.len(),
+                    target.name,
+                    skipped
+                );
+                if read_answer()? != "y" {
+                    anyhow::bail!("Nothing imported");
+                }
+            }
+
+            let client = CalendarClient::new(config.clone()).with_dry_run(dry_run);
+            let mut failures = Vec::new();
+            for (i, (position, event)) in valid.iter().enumerate() {
+                if !dry_run {
+                    eprint!("\rImporting {}/{}", i + 1, valid.len());
+                }
+                if let Err(e) = client.create_event(&target, event).await {
+                    failures.push((*position, &event.title, e));
+                }
+            }
+            if dry_run {
+                return Ok(());
+            }
+            eprintln!();
+            daemon::request_refresh(&config).await;
+
+            println!(
+                "Imported {} of {} events into {}",
+                valid.len() - failures.len(),
+                valid.len(),
+                target.name
+            );
+            for (position, title, e) in &failures {
+                eprintln!("  {} row {} ({}): {}", "failed".red(), position, title, e);
+            }
+            if !failures.is_empty() {
+                anyhow::bail!("{} events failed to import", failures.len());
+            }
+        }
+        Commands::Undo { list, dry_run } => {
+            let config = load_config(insecure_ok)?;
+            let zone = Zone::from_config(&config.display.timezone);
+            let mut entries = journal::load();
+
+            if list {
+                if entries.is_empty() {
+                    println!("Nothing to undo");
+                    return Ok(());
+                }
+                println!("{}", "Recent Changes:".bright_blue().bold());
+                for entry in entries.iter().rev() {
+                    println!(
+                        "  {} {} {} ({})",
+                        zone.convert(&entry.at).format("%Y-%m-%d %H:%M"),
+                        entry.operation.describe(),
+                        entry.title,
+                        entry.event_id.bright_green()
+                    );
+                }
+                return Ok(());
+            }
+
+            let Some(entry) = entries.pop() else {
+                anyhow::bail!("Nothing to undo");
+            };
+            let client = CalendarClient::new(config.clone()).with_dry_run(dry_run);
+            client.undo(&entry).await.context("Failed to undo")?;
+            if dry_run {
+                return Ok(());
+            }
+            journal::save(&entries)?;
+            daemon::request_refresh(&config).await;
+
+            println!(
+                "Undone: {} {} ({})",
+                entry.operation.describe(),
+                entry.title,
+                entry.event_id.bright_green()
+            );
         }
         Commands::ListCalendars => {
-            let config = Config::load()?;
+            let config = load_config(insecure_ok)?;
             let client = CalendarClient::new(config);
 
             let calendars = client
                 .list_calendars()
                 .await
-                .map_err(|e| anyhow::anyhow!("Failed to list calendars: {}", e))?;
+                .context("Failed to list calendars")?;
 
             println!("{}", "Available Calendars:".bright_blue().bold());
             for calendar in calendars {
@@ -83,17 +853,262 @@ async fn run(cli: Cli) -> anyhow::Result<()> {
                 );
             }
         }
+        Commands::Calendars { action } => match action {
+            CalendarsAction::List => {
+                let config = Config::load()?;
+                println!("{}", "Configured Calendars:".bright_blue().bold());
+                for calendar in &config.calendars {
+                    let state = if calendar.enabled { "" } else { " (disabled)" };
+                    let aliases: Vec<&str> = config
+                        .aliases
+                        .iter()
+                        .filter(|(_, id)| **id == calendar.id)
+                        .map(|(alias, _)| alias.as_str())
+                        .collect();
+                    let aliases = if aliases.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" [{}]", aliases.join(", "))
+                    };
+                    println!(
+                        "  {}: {}{} {}{}",
+                        calendar.id.bright_green(),
+                        calendar.name,
+                        aliases.bright_cyan(),
+                        calendar.color,
+                        state.bright_yellow()
+                    );
+                }
+            }
+            CalendarsAction::Add {
+                id,
+                name,
+                color,
+                disabled,
+            } => {
+                // Only ask Google when something has to be filled in.
+                let entry = if id.is_none() || name.is_none() || color.is_none() {
+                    let config = load_config(insecure_ok)?;
+                    let calendars = CalendarClient::new(config.clone())
+                        .list_calendars()
+                        .await
+                        .context("Failed to list calendars")?;
+                    match &id {
+                        Some(id) => find_listed_calendar(calendars, id)?,
+                        None => Some(pick_calendar(&config, calendars)?),
+                    }
+                } else {
+                    None
+                };
+
+                let id = entry
+                    .as_ref()
+                    .and_then(|e| e.id.clone())
+                    .or(id)
+                    .ok_or_else(|| anyhow::anyhow!("Calendar has no ID"))?;
+                let name = name
+                    .or_else(|| entry.as_ref().and_then(|e| e.summary.clone()))
+                    .unwrap_or_else(|| id.clone());
+                let color = color
+                    .or_else(|| entry.as_ref().and_then(|e| e.background_color.clone()))
+                    .unwrap_or_else(|| DEFAULT_CALENDAR_COLOR.to_string());
+                let calendar = CalendarConfig::new(id, name, color, !disabled);
+                if !validate::is_hex_color(&calendar.color) {
+                    anyhow::bail!("'{}' is not a #RGB or #RRGGBB color", calendar.color);
+                }
+
+                let mut editor = ConfigEditor::open()?;
+                editor.add_calendar(&calendar)?;
+                editor.save()?;
+                println!("Added {} ({})", calendar.id.bright_green(), calendar.name);
+            }
+            CalendarsAction::Sync => {
+                let config = load_config(insecure_ok)?;
+                let calendars = CalendarClient::new(config.clone())
+                    .list_calendars()
+                    .await
+                    .context("Failed to list calendars")?;
+
+                // "primary" in the config is Google's alias for the account's
+                // own calendar, which the list reports under its real ID.
+                let matches = |configured: &CalendarConfig, remote: &CalendarListEntry| {
+                    remote.id.as_deref() == Some(configured.id.as_str())
+                        || (configured.id == "primary" && remote.primary.unwrap_or(false))
+                };
+
+                let mut editor = ConfigEditor::open()?;
+                let mut added = 0;
+                for remote in &calendars {
+                    let Some(id) = remote.id.clone() else {
+                        continue;
+                    };
+                    if config.calendars.iter().any(|c| matches(c, remote)) {
+                        continue;
+                    }
+
+                    let name = remote.summary.clone().unwrap_or_else(|| id.clone());
+                    let color = remote
+                        .background_color
+                        .clone()
+                        .filter(|color| validate::is_hex_color(color))
+                        .unwrap_or_else(|| DEFAULT_CALENDAR_COLOR.to_string());
+                    let calendar = CalendarConfig::new(id, name, color, false);
+                    editor.add_calendar(&calendar)?;
+                    println!(
+                        "Added {} ({}, disabled)",
+                        calendar.id.bright_green(),
+                        calendar.name
+                    );
+                    added += 1;
+                }
+                if added > 0 {
+                    editor.save()?;
+                }
+
+                let missing: Vec<&CalendarConfig> = config
+                    .calendars
+                    .iter()
+                    .filter(|c| !calendars.iter().any(|remote| matches(c, remote)))
+                    .collect();
+                for calendar in &missing {
+                    println!(
+                        "{} {} ({}) no longer exists on this account; remove it with `callux calendars remove {}`",
+                        "Missing:".bright_yellow().bold(),
+                        calendar.id,
+                        calendar.name,
+                        calendar.id
+                    );
+                }
+
+                if added == 0 && missing.is_empty() {
+                    println!("{}", "Calendars are in sync.".bright_green().bold());
+                }
+            }
+            CalendarsAction::Remove { id } => {
+                let id = configured_calendar_id(&id)?;
+                let mut editor = ConfigEditor::open()?;
+                editor.remove_calendar(&id)?;
+                editor.save()?;
+                println!("Removed {}", id.bright_green());
+            }
+            CalendarsAction::Enable { id } => {
+                let id = configured_calendar_id(&id)?;
+                let mut editor = ConfigEditor::open()?;
+                editor.update_calendar(&id, "enabled", true)?;
+                editor.save()?;
+                println!("Enabled {}", id.bright_green());
+            }
+            CalendarsAction::Disable { id } => {
+                let id = configured_calendar_id(&id)?;
+                let mut editor = ConfigEditor::open()?;
+                editor.update_calendar(&id, "enabled", false)?;
+                editor.save()?;
+                println!("Disabled {}", id.bright_green());
+            }
+            CalendarsAction::Rename { id, name } => {
+                let id = configured_calendar_id(&id)?;
+                let mut editor = ConfigEditor::open()?;
+                editor.update_calendar(&id, "name", name.as_str())?;
+                editor.save()?;
+                println!("Renamed {} to {}", id.bright_green(), name);
+            }
+        },
         Commands::Config { action } => match action {
             ConfigAction::Show => {
                 let config = Config::load()?;
                 let config_str = toml::to_string_pretty(&config)?;
                 println!("{}", config_str);
             }
+            ConfigAction::Get { key } => {
+                let config = Config::load()?;
+                let value = config_edit::get(&config, &key)?;
+                println!("{}", config_edit::render(&value)?);
+            }
             ConfigAction::Set { key, value } => {
-                println!("Setting configuration is not yet implemented");
-                println!("Key: {}, Value: {}", key, value);
+                let mut editor = ConfigEditor::open()?;
+                editor.set(&key, &value)?;
+                editor.save()?;
+                println!("{} = {}", key.bright_green(), value);
+            }
+            ConfigAction::Unset { key } => {
+                let mut editor = ConfigEditor::open()?;
+                let default = editor.unset(&key)?;
+                editor.save()?;
+                match default {
+                    Some(value) => println!(
+                        "{} reset to {}",
+                        key.bright_green(),
+                        config_edit::render(&value)?
+                    ),
+                    None => println!("{} removed", key.bright_green()),
+                }
+            }
+            ConfigAction::Edit => {
+                config_edit::edit_interactively()?;
+                println!("{}", "Configuration saved.".bright_green().bold());
+            }
+            ConfigAction::Path => {
+                let config = Config::load()?;
+                let database = cache::PersistentStore::path_for(&config.cache)
+                    .filter(|_| config.cache.persistent)
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| "(disabled)".to_string());
+
+                println!("Config:      {}", Config::path()?.display());
+                for fragment in Config::drop_ins()? {
+                    println!("Drop-in:     {}", fragment.display());
+                }
+                if let Some(profile) = Config::profile() {
+                    println!("Profile:     {}", profile);
+                }
+                println!(
+                    "Credentials: {}",
+                    config.expand_path(&config.auth.credentials_path)
+                );
+                println!(
+                    "Token cache: {}",
+                    config.expand_path(&config.auth.token_cache_path)
+                );
+                println!("Cache:       {}", database);
+                if let Some(socket) = daemon::socket_path(&config.daemon) {
+                    println!("Socket:      {}", socket.display());
+                }
+            }
+            ConfigAction::Validate { online } => {
+                // The files, not `Config::load`: syntax and type errors are
+                // what this is for, and overrides aren't part of them.
+                let path = Config::path()?;
+                if !path.exists() {
+                    anyhow::bail!("No config file at {}", path.display());
+                }
+                let text = std::fs::read_to_string(&path)?;
+                let (config, mut diagnostics) = validate::validate_text(&text);
+                if let (true, Some(config)) = (online, &config) {
+                    validate::check_online(config, &mut diagnostics).await;
+                }
+
+                validate::print(&diagnostics);
+                if diagnostics
+                    .iter()
+                    .any(|d| d.severity == validate::Severity::Error)
+                {
+                    anyhow::bail!("{} is invalid", path.display());
+                }
+            }
+            ConfigAction::Init { non_interactive }
+                if !non_interactive && std::io::stdin().is_terminal() =>
+            {
+                let config = wizard::run(Config::default()).await?;
+                let enabled = config.calendars.iter().filter(|c| c.enabled).count();
+                println!(
+                    "\n{} {} calendar(s) enabled in {}",
+                    "Setup complete!".bright_green().bold(),
+                    enabled,
+                    Config::path()?.display()
+                );
+                println!("Try it with: callux agenda");
             }
-            ConfigAction::Init => {
+            ConfigAction::Init { .. } => {
                 let config = Config::default();
                 config.save()?;
 
@@ -104,9 +1119,7 @@ async fn run(cli: Cli) -> anyhow::Result<()> {
                 println!("Please edit the following files:");
                 println!(
                     "1. Configuration: {}",
-                    Config::load()?
-                        .expand_path("~/.config/callux/config.toml")
-                        .bright_yellow()
+                    Config::path()?.display().to_string().bright_yellow()
                 );
                 println!(
                     "2. Credentials: {}",
@@ -124,7 +1137,7 @@ async fn run(cli: Cli) -> anyhow::Result<()> {
             }
         },
         Commands::Auth => {
-            let config = Config::load()?;
+            let config = load_config(insecure_ok)?;
             let auth_manager = AuthManager::new(config);
 
             match auth_manager.get_token().await {
@@ -142,7 +1155,824 @@ async fn run(cli: Cli) -> anyhow::Result<()> {
                 }
             }
         }
+        Commands::Doctor => {
+            let checks = doctor::run(insecure_ok).await;
+            doctor::print(&checks);
+            let failed = checks.iter().filter(|check| check.failed()).count();
+            if failed > 0 {
+                anyhow::bail!("{} check(s) failed", failed);
+            }
+        }
+        Commands::Prefetch {
+            days,
+            hours,
+            until,
+            past,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let mut ranges: Vec<Lookahead> = days.into_iter().map(Lookahead::Days).collect();
+            ranges.extend(hours.into_iter().map(Lookahead::Hours));
+            if let Some(until) = &until {
+                let zone = Zone::from_config(&config.display.timezone);
+                ranges.push(Lookahead::Until(
+                    when::parse_until(until, zone).map_err(|e| anyhow::anyhow!(e))?,
+                ));
+            }
+            if ranges.is_empty() {
+                ranges = config
+                    .cache
+                    .prefetch_days
+                    .iter()
+                    .copied()
+                    .map(Lookahead::Days)
+                    .collect();
+            }
+            let client = CalendarClient::new(config.clone()).with_past(past);
+
+            let mut failed = 0;
+            for lookahead in ranges {
+                match client.prefetch_within(lookahead).await {
+                    Ok(count) => println!("Prefetched {} events for {}", count, lookahead),
+                    Err(e) => {
+                        eprintln!("{}: {}: {}", "Prefetch failed".red().bold(), lookahead, e);
+                        failed += 1;
+                    }
+                }
+            }
+
+            // The snapshot covers its own days ahead, whatever --past says.
+            let written = snapshot::refresh(&client.with_past(None), &config).await;
+
+            if failed > 0 {
+                anyhow::bail!("{} prefetch range(s) failed", failed);
+            }
+            written?;
+        }
+        Commands::Diff {
+            format,
+            days,
+            offline,
+            keep,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let client = CalendarClient::new(config);
+
+            match client.diff(days, offline, keep).await? {
+                Some(diff) => println!("{}", diff.format(&format)),
+                None => eprintln!("Snapshot saved; the next diff will report changes"),
+            }
+        }
+        Commands::Daemon => {
+            let config = load_config(insecure_ok)?;
+            daemon::run(config).await?;
+        }
+        Commands::Popup { days, timeout } => {
+            let mut config = load_config(insecure_ok)?;
+            if let Some(timeout) = timeout {
+                config.popup.timeout_seconds = timeout;
+            }
+            let days_ahead = days.unwrap_or(config.popup.days);
+            let agenda = fetch_agenda(&config, days_ahead, None, false, false).await?;
+            show_popup(&config, agenda.events)?;
+        }
+        Commands::CopyLink { event, days } => {
+            let config = load_config(insecure_ok)?;
+            let agenda = fetch_agenda(&config, days, None, false, false).await?;
+            let now = Utc::now();
+
+            let found = if event == "next" {
+                agenda
+                    .events
+                    .into_iter()
+                    .find(|e| e.end_time.with_timezone(&Utc) > now && e.meeting_url.is_some())
+            } else {
+                agenda.events.into_iter().find(|e| e.id == event)
+            };
+            let Some(found) = found else {
+                anyhow::bail!(
+                    "No {} in the next {} days",
+                    if event == "next" {
+                        "upcoming event with a meeting link".to_string()
+                    } else {
+                        format!("event '{}'", event)
+                    },
+                    days
+                );
+            };
+            let Some(link) = found.meeting_url.or(found.html_link) else {
+                anyhow::bail!("'{}' has no meeting or calendar link", found.title);
+            };
+
+            copy_to_clipboard(&link)?;
+            println!("Copied {} ({})", link.bright_green(), found.title);
+        }
+        Commands::Open {
+            event,
+            calendar,
+            days,
+            print,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let found = if event == "next" {
+                let now = Utc::now();
+                fetch_agenda(&config, days, None, false, false)
+                    .await?
+                    .events
+                    .into_iter()
+                    .find(|e| e.end_time.with_timezone(&Utc) > now)
+                    .ok_or_else(|| anyhow::anyhow!("No upcoming event in the next {} days", days))?
+            } else {
+                CalendarClient::new(config)
+                    .find_event(&event, calendar.as_deref())
+                    .await?
+                    .event
+            };
+            let Some(link) = found.html_link else {
+                anyhow::bail!("'{}' has no Google Calendar link", found.title);
+            };
+
+            if print {
+                println!("{}", link);
+            } else {
+                open_in_browser(&link)?;
+                println!("Opened {} ({})", link.bright_green(), found.title);
+            }
+        }
+        Commands::Show {
+            event,
+            calendar,
+            days,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let event = event_or_next(&config, event, days).await?;
+            let found = CalendarClient::new(config)
+                .find_event(&event, calendar.as_deref())
+                .await?;
+            println!("{}", details::render(&found));
+        }
+        Commands::Tz {
+            event,
+            to,
+            calendar,
+            days,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let mut zones = Vec::new();
+            for name in to
+                .iter()
+                .map(|name| name.trim())
+                .filter(|name| !name.is_empty())
+            {
+                let zone = Zone::parse(name).map_err(|e| anyhow::anyhow!(e))?;
+                zones.push((name.to_string(), zone));
+            }
+            let home = Zone::from_config(&config.display.timezone);
+            let home_name = home
+                .iana_name()
+                .unwrap_or_else(|| config.display.timezone.clone());
+
+            let event = event_or_next(&config, event, days).await?;
+            let found = CalendarClient::new(config)
+                .find_event(&event, calendar.as_deref())
+                .await?;
+            println!(
+                "{}",
+                details::render_zones(&found.event, (&home_name, home), &zones)
+            );
+        }
+        Commands::Attachments {
+            event,
+            calendar,
+            download,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let client = CalendarClient::new(config);
+            let found = client.find_event(&event, calendar.as_deref()).await?;
+            if found.attachments.is_empty() {
+                println!("'{}' has no attachments", found.event.title);
+                return Ok(());
+            }
+
+            let Some(dir) = download else {
+                for attachment in &found.attachments {
+                    println!(
+                        "{}  {}",
+                        attachment.title.as_deref().unwrap_or("attachment"),
+                        attachment.file_url.as_deref().unwrap_or_default()
+                    );
+                }
+                return Ok(());
+            };
+            let dir = std::path::PathBuf::from(config::expand_home(&dir));
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+            let mut failed = 0;
+            for attachment in &found.attachments {
+                match client.download_attachment(attachment).await {
+                    Ok((name, bytes)) => {
+                        // Titles come from other people; keep them in `dir`.
+                        let name = name.replace(['/', '\\'], "_");
+                        let path = match name.trim_matches('.') {
+                            "" => dir.join("attachment"),
+                            _ => dir.join(name),
+                        };
+                        std::fs::write(&path, bytes)
+                            .with_context(|| format!("Failed to write {}", path.display()))?;
+                        println!("Saved {}", path.display());
+                    }
+                    Err(e) => {
+                        eprintln!("{}: {}", "Download failed".red().bold(), e);
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                anyhow::bail!("{} attachment(s) failed", failed);
+            }
+        }
+        Commands::Birthdays { days } => {
+            let config = load_config(insecure_ok)?;
+            let agenda = fetch_agenda(&config, days, None, false, false).await?;
+            for warning in &agenda.warnings {
+                eprintln!("Warning: {}", warning);
+            }
+
+            let today = Zone::from_config(&config.display.timezone)
+                .now()
+                .date_naive();
+            let birthdays: Vec<&CalendarEvent> = agenda
+                .events
+                .iter()
+                .filter(|event| event.kind == EventKind::Birthday && !event.cancelled)
+                .collect();
+            if birthdays.is_empty() {
+                println!("No birthdays in the next {} days", days);
+            }
+            for event in birthdays {
+                let note = event.birthday_note(today).unwrap_or_default();
+                let line = format!(
+                    "{}  {} {}",
+                    event.start_time.format("%a %b %d"),
+                    event.title,
+                    note
+                );
+                println!("{}", line.trim_end());
+            }
+        }
+        Commands::Availability { weeks, ics } => {
+            let config = load_config(insecure_ok)?;
+            let days = weeks.max(1) * 7;
+            let agenda = fetch_agenda(&config, days, None, false, false).await?;
+            let blocks = availability::busy_blocks(&agenda.events);
+            for warning in &agenda.warnings {
+                eprintln!("Warning: {}", warning);
+            }
+
+            match ics.as_deref() {
+                Some("-") => print!("{}", availability::to_ics(&blocks)),
+                Some(path) => {
+                    std::fs::write(path, availability::to_ics(&blocks))
+                        .with_context(|| format!("Failed to write {}", path))?;
+                    println!("Wrote {} busy blocks to {}", blocks.len(), path);
+                }
+                None => {
+                    let zone = Zone::from_config(&config.display.timezone);
+                    let first = zone.now().date_naive();
+                    let last = first + chrono::Duration::days(days - 1);
+                    let name = zone
+                        .iana_name()
+                        .unwrap_or_else(|| config.display.timezone.clone());
+                    println!("{}", availability::to_text(&blocks, first, last, &name));
+                }
+            }
+        }
+        Commands::Remind {
+            event,
+            before,
+            calendar,
+            days,
+            command,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let lead = when::parse_duration(&before).map_err(|e| anyhow::anyhow!(e))?;
+            let now = Utc::now();
+            let found = if event == "next" {
+                fetch_agenda(&config, days, None, false, false)
+                    .await?
+                    .events
+                    .into_iter()
+                    .filter(|e| {
+                        !e.all_day && !e.cancelled && e.response.as_deref() != Some("declined")
+                    })
+                    .find(|e| e.start_time.with_timezone(&Utc) > now)
+                    .ok_or_else(|| anyhow::anyhow!("No upcoming event in the next {} days", days))?
+            } else {
+                CalendarClient::new(config)
+                    .find_event(&event, calendar.as_deref())
+                    .await?
+                    .event
+            };
+            if found.start_time.with_timezone(&Utc) <= now {
+                anyhow::bail!("'{}' has already started", found.title);
+            }
+
+            let at = found.start_time - lead;
+            println!(
+                "Reminding at {} about {} at {}",
+                at.format("%H:%M"),
+                found.title,
+                found.start_time.format("%H:%M")
+            );
+            remind::wait_until(at.with_timezone(&Utc)).await;
+            remind::fire(&found, command.as_deref()).await?;
+        }
+        Commands::UntilNext {
+            format,
+            days,
+            offline,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let now = Utc::now();
+            let next = fetch_agenda(&config, days, None, offline, false)
+                .await?
+                .events
+                .into_iter()
+                .filter(|e| !e.all_day && !e.cancelled && e.response.as_deref() != Some("declined"))
+                .map(|e| e.start_time.with_timezone(&Utc))
+                .filter(|start| *start > now)
+                .min()
+                .ok_or_else(|| anyhow::anyhow!("No upcoming event in the next {} days", days))?;
+
+            let remaining = next - now;
+            match format {
+                CountdownFormat::Seconds => println!("{}", remaining.num_seconds()),
+                CountdownFormat::Human => println!("{}", when::format_duration(remaining)),
+            }
+        }
+        Commands::Serve { listen, token } => {
+            let config = load_config(insecure_ok)?;
+            let listen = listen.unwrap_or_else(|| config.serve.listen.clone());
+            let address = listen
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid listen address '{}': {}", listen, e))?;
+            let token = token.or_else(|| config.serve.token.clone());
+            serve::run(config, address, token).await?;
+        }
+        Commands::Service { action } => match action {
+            ServiceAction::Install { user, print } => {
+                if !user {
+                    anyhow::bail!(
+                        "Only user units are supported; run `callux service install --user`"
+                    );
+                }
+                let config = load_config(insecure_ok)?;
+                let units = service::units(&config)?;
+
+                if print {
+                    for unit in &units {
+                        println!("# {}\n{}", unit.name, unit.content);
+                    }
+                    return Ok(());
+                }
+
+                let dir = service::unit_dir()?;
+                for path in service::install(&units, &dir)? {
+                    println!("Wrote {}", path.display().to_string().bright_green());
+                }
+
+                let systemctl = "systemctl --user";
+                let enable: Vec<&str> = units
+                    .iter()
+                    .filter(|unit| unit.name.ends_with(".socket") || unit.name.ends_with(".timer"))
+                    .map(|unit| unit.name.as_str())
+                    .collect();
+                println!("\nTo start them:");
+                println!("  {} daemon-reload", systemctl);
+                println!("  {} enable --now {}", systemctl, enable.join(" "));
+            }
+        },
+        Commands::Bench {
+            iterations,
+            days,
+            cached_only,
+        } => {
+            let config = load_config(insecure_ok)?;
+            let results = bench::run(&config, iterations, days, cached_only).await?;
+            bench::print(&results);
+        }
+        Commands::Cache { action } => {
+            let config = load_config(insecure_ok)?;
+            let client = CalendarClient::new(config);
+
+            match action {
+                CacheAction::Stats => print_cache_stats(&client.cache_stats()),
+                CacheAction::Clear => {
+                    client.clear_cache().await?;
+                    println!("{}", "Cache cleared.".bright_green().bold());
+                }
+                CacheAction::Prune => {
+                    let removed = client.prune_cache()?;
+                    println!("Removed {} expired cache entries.", removed);
+                }
+            }
+        }
+        Commands::SelfUpdate { check_only } => {
+            let current = env!("CARGO_PKG_VERSION");
+            let release = self_update::latest().await?;
+            if !self_update::is_newer(&release.version) {
+                println!("callux {} is up to date", current);
+                return Ok(());
+            }
+            if check_only {
+                println!(
+                    "callux {} is available (installed: {})",
+                    release.version.bright_green(),
+                    current
+                );
+                return Ok(());
+            }
+
+            let path = self_update::install(&release).await?;
+            println!(
+                "Updated {} from {} to {}",
+                path.display(),
+                current,
+                release.version.bright_green()
+            );
+        }
+        Commands::Debug { action } => match action {
+            DebugAction::Bundle { output } => {
+                let config = load_config(insecure_ok)?;
+                let cache = CalendarClient::new(config.clone()).cache_stats();
+                let bundle = diagnostics::bundle(&config, &cache);
+                let text = serde_json::to_string_pretty(&bundle)?;
+                match output {
+                    Some(path) => {
+                        std::fs::write(&path, text)
+                            .with_context(|| format!("Failed to write {}", path))?;
+                        println!("Wrote diagnostics to {}", path.bright_green());
+                        println!(
+                            "Calendar ids, event details and secrets are left out; have a look before sharing."
+                        );
+                    }
+                    None => println!("{}", text),
+                }
+            }
+        },
+        Commands::Completions { shell } => print!("{}", completions::script(shell)),
+        Commands::Complete { kind } => {
+            // Nothing to suggest is the only sensible failure while typing.
+            let Ok(config) = load_config(insecure_ok) else {
+                return Ok(());
+            };
+            let clean = |text: &str| text.replace(['\t', '\n'], " ");
+            match kind {
+                CompleteKind::Calendars => {
+                    for calendar in &config.calendars {
+                        println!("{}\t{}", clean(&calendar.name), calendar.id);
+                    }
+                }
+                CompleteKind::Templates => {
+                    for template in &config.templates {
+                        println!("{}", clean(&template.name));
+                    }
+                }
+                CompleteKind::Events => {
+                    if let Ok(agenda) = fetch_agenda(&config, 7, None, true, false).await {
+                        for event in &agenda.events {
+                            println!("{}\t{}", event.id, clean(&event.title));
+                        }
+                    }
+                }
+            }
+        }
+        Commands::Schema { output } => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&schema::document(output))?
+            );
+        }
     }
 
     Ok(())
 }
+
+/// Prints `text`, or replaces the file at `path` with it in one step so
+/// widgets reading the file never see it half-written.
+fn emit(text: &str, path: Option<&str>) -> anyhow::Result<()> {
+    match path {
+        Some(path) => {
+            let path = config::expand_home(path);
+            snapshot::write_atomically(std::path::Path::new(&path), &format!("{}\n", text))
+                .with_context(|| format!("Failed to write {}", path))
+        }
+        None => {
+            println!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+/// `event` itself, or for "next" the id of the running or next event within
+/// `days`.
+async fn event_or_next(config: &Config, event: String, days: i64) -> anyhow::Result<String> {
+    if event != "next" {
+        return Ok(event);
+    }
+    let now = Utc::now();
+    Ok(fetch_agenda(config, days, None, false, false)
+        .await?
+        .events
+        .into_iter()
+        .find(|e| e.end_time.with_timezone(&Utc) > now)
+        .ok_or_else(|| anyhow::anyhow!("No upcoming event in the next {} days", days))?
+        .id)
+}
+
+/// Asks a running daemon unless `no_daemon`, fetching directly when there
+/// is none (or it runs another configuration).
+async fn fetch_agenda(
+    config: &Config,
+    days_ahead: i64,
+    limit: Option<usize>,
+    offline: bool,
+    no_daemon: bool,
+) -> anyhow::Result<Agenda> {
+    fetch_agenda_within(
+        config,
+        Lookahead::Days(days_ahead),
+        None,
+        limit,
+        offline,
+        no_daemon,
+    )
+    .await
+}
+
+async fn fetch_agenda_within(
+    config: &Config,
+    lookahead: Lookahead,
+    past_days: Option<i64>,
+    limit: Option<usize>,
+    offline: bool,
+    no_daemon: bool,
+) -> anyhow::Result<Agenda> {
+    // The daemon's requests can't be traced from here, and it only keeps
+    // ranges of whole days ahead.
+    let from_daemon = match (lookahead, past_days) {
+        (Lookahead::Days(days_ahead), None) if !no_daemon && !http_trace::enabled() => {
+            daemon::query(config, days_ahead, limit, offline).await
+        }
+        _ => None,
+    };
+    match from_daemon {
+        Some(result) => result,
+        None => CalendarClient::new(config.clone())
+            .with_past(past_days)
+            .get_events_within(lookahead, limit, offline)
+            .await
+            .map_err(anyhow::Error::from),
+    }
+}
+
+#[cfg(feature = "popup")]
+fn show_popup(config: &Config, events: Vec<CalendarEvent>) -> anyhow::Result<()> {
+    popup::show(&config.popup, events)
+}
+
+#[cfg(not(feature = "popup"))]
+fn show_popup(_config: &Config, _events: Vec<CalendarEvent>) -> anyhow::Result<()> {
+    anyhow::bail!("callux was built without popup support (enable the `popup` feature)")
+}
+
+/// Hands `url` to `xdg-open`, which starts the browser or opens a tab in
+/// the running one.
+fn open_in_browser(url: &str) -> anyhow::Result<()> {
+    std::process::Command::new("xdg-open")
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to run xdg-open (pass --print instead): {}", e))?;
+    Ok(())
+}
+
+/// Hands `text` to `wl-copy`, which keeps serving the selection after
+/// callux exits.
+fn copy_to_clipboard(text: &str) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let mut child = std::process::Command::new("wl-copy")
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            anyhow::anyhow!("Failed to run wl-copy (is wl-clipboard installed?): {}", e)
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!("wl-copy exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Lists the account's calendars that aren't configured yet and asks which
+/// one to add.
+fn pick_calendar(
+    config: &Config,
+    calendars: Vec<CalendarListEntry>,
+) -> anyhow::Result<CalendarListEntry> {
+    let mut candidates: Vec<CalendarListEntry> = calendars
+        .into_iter()
+        .filter(|c| {
+            let id = c.id.as_deref().unwrap_or_default();
+            !config.calendars.iter().any(|cal| cal.id == id)
+        })
+        .collect();
+    if candidates.is_empty() {
+        anyhow::bail!("Every calendar on this account is already configured");
+    }
+
+    println!("{}", "Available Calendars:".bright_blue().bold());
+    for (i, calendar) in candidates.iter().enumerate() {
+        println!(
+            "  {:>2}. {} {}",
+            i + 1,
+            calendar.summary.as_deref().unwrap_or("Untitled"),
+            calendar.id.as_deref().unwrap_or("unknown").bright_green()
+        );
+    }
+
+    eprint!("Add which calendar? [1-{}] ", candidates.len());
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let choice = answer
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|n| (1..=candidates.len()).contains(n))
+        .ok_or_else(|| anyhow::anyhow!("No calendar selected"))?;
+
+    Ok(candidates.swap_remove(choice - 1))
+}
+
+/// `--remind` values as minutes, within what Google accepts: at most five
+/// reminders, none more than four weeks ahead.
+fn parse_reminders(remind: &[String]) -> anyhow::Result<Vec<i64>> {
+    if remind.len() > 5 {
+        anyhow::bail!("Google allows at most 5 reminders per event");
+    }
+    remind
+        .iter()
+        .map(|before| {
+            let minutes = when::parse_duration(before)
+                .map_err(|e| anyhow::anyhow!(e))?
+                .num_minutes();
+            if minutes > 40320 {
+                anyhow::bail!("Reminder '{}' is more than four weeks ahead", before);
+            }
+            Ok(minutes)
+        })
+        .collect()
+}
+
+fn parse_invitees(invite: Option<&str>, optional: Option<&str>) -> anyhow::Result<Vec<Invitee>> {
+    let mut invitees = Invitee::parse_list(invite.unwrap_or_default(), false)?;
+    invitees.extend(Invitee::parse_list(optional.unwrap_or_default(), true)?);
+    Ok(invitees)
+}
+
+/// `--send-updates`, defaulting to emailing everyone when inviting since
+/// Google otherwise sends nobody an invitation.
+fn send_updates_param(
+    send_updates: Option<SendUpdates>,
+    invitees: &[Invitee],
+) -> Option<&'static str> {
+    match send_updates {
+        Some(send_updates) => Some(send_updates.as_param()),
+        None if !invitees.is_empty() => Some(SendUpdates::All.as_param()),
+        None => None,
+    }
+}
+
+/// Google sets Meet calls up asynchronously, so the link may not be in the
+/// insert response yet.
+fn print_meet_link(event: &CalendarEvent) {
+    match &event.meeting_url {
+        Some(url) => println!("Meet: {}", url),
+        None => println!("Meet: requested; the link shows up in the agenda shortly"),
+    }
+}
+
+/// The id of the configured calendar `name_or_id` refers to.
+fn configured_calendar_id(name_or_id: &str) -> anyhow::Result<String> {
+    Ok(Config::load()?.find_calendar(name_or_id)?.id.clone())
+}
+
+/// The calendar in the account with id `query`, else the one whose title
+/// `fuzzy::find` matches, or `None` to take `query` as an id as it is, e.g.
+/// for a public calendar not in the list.
+fn find_listed_calendar(
+    calendars: Vec<CalendarListEntry>,
+    query: &str,
+) -> anyhow::Result<Option<CalendarListEntry>> {
+    if let Some(index) = calendars
+        .iter()
+        .position(|c| c.id.as_deref() == Some(query))
+    {
+        return Ok(calendars.into_iter().nth(index));
+    }
+    let titles: Vec<&str> = calendars
+        .iter()
+        .map(|c| c.summary.as_deref().unwrap_or_default())
+        .collect();
+    match fuzzy::find(query, &titles) {
+        Ok(index) => Ok(calendars.into_iter().nth(index)),
+        Err(matches) if matches.is_empty() => Ok(None),
+        Err(matches) => anyhow::bail!(
+            "'{}' matches several calendars: {}",
+            query,
+            matches
+                .iter()
+                .map(|&i| titles[i])
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// `--calendar` as a configured name or id, else the first enabled one.
+fn target_calendar(config: &Config, name: Option<&str>) -> anyhow::Result<CalendarConfig> {
+    let target = match name {
+        Some(name) => config.find_calendar(name)?,
+        None => config
+            .calendars
+            .iter()
+            .find(|cal| cal.enabled)
+            .ok_or_else(|| anyhow::anyhow!("No enabled calendar to add to"))?,
+    };
+    Ok(target.clone())
+}
+
+/// A trimmed, lowercased line from stdin, for yes/no style prompts.
+fn read_answer() -> anyhow::Result<String> {
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_ascii_lowercase())
+}
+
+fn print_cache_stats(stats: &CacheStats) {
+    println!("{}", "Cache Statistics:".bright_blue().bold());
+    println!("  TTL: {}", format_age(stats.ttl_seconds));
+    println!(
+        "  In-memory entries (this process): {}",
+        stats.memory_entries
+    );
+
+    let Some(persistent) = &stats.persistent else {
+        println!("  Persistent cache: {}", "disabled".bright_yellow());
+        return;
+    };
+
+    let lookups = persistent.hits + persistent.misses;
+    let hit_rate = if lookups > 0 {
+        persistent.hits as f64 * 100.0 / lookups as f64
+    } else {
+        0.0
+    };
+
+    println!(
+        "  Persistent cache: {}",
+        persistent.path.display().to_string().bright_green()
+    );
+    println!("  File size: {} bytes", persistent.file_bytes);
+    println!(
+        "  Hits: {}, misses: {} ({:.1}% hit rate)",
+        persistent.hits, persistent.misses, hit_rate
+    );
+    println!("  Stored ETags: {}", persistent.etag_entries);
+    println!("  Entries: {}", persistent.entries.len());
+
+    for entry in &persistent.entries {
+        let age = format_age(entry.age_seconds);
+        let age = if entry.age_seconds >= stats.ttl_seconds {
+            format!("{} (expired)", age).bright_yellow()
+        } else {
+            age.bright_green()
+        };
+        println!(
+            "    {}: {} events, {} bytes, age {}",
+            entry.key, entry.events, entry.bytes, age
+        );
+    }
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m {}s", s / 60, s % 60),
+        s => format!("{}h {}m", s / 3600, (s % 3600) / 60),
+    }
+}
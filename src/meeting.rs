@@ -0,0 +1,20 @@
+use crate::output::CalendarEvent;
+use chrono::Utc;
+
+/// The timed, busy event running now from one of `calendars` (any calendar
+/// when empty), optionally only if it has a meeting link.
+pub fn current<'a>(
+    events: &'a [CalendarEvent],
+    calendars: &[String],
+    require_link: bool,
+) -> Option<&'a CalendarEvent> {
+    let now = Utc::now();
+    events.iter().find(|e| {
+        !e.all_day
+            && !e.free
+            && e.start_time.with_timezone(&Utc) <= now
+            && e.end_time.with_timezone(&Utc) > now
+            && (calendars.is_empty() || calendars.contains(&e.calendar_name))
+            && (!require_link || e.meeting_url.is_some())
+    })
+}
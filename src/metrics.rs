@@ -0,0 +1,193 @@
+use crate::output::CalendarEvent;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Response, StatusCode, header};
+use hyper_util::rt::TokioIo;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+/// Upper bounds (seconds) of the API latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 8] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Debug, Clone, Copy)]
+pub enum ApiOutcome {
+    Ok,
+    NotModified,
+    RateLimited,
+    Error,
+}
+
+impl ApiOutcome {
+    const ALL: [ApiOutcome; 4] = [
+        ApiOutcome::Ok,
+        ApiOutcome::NotModified,
+        ApiOutcome::RateLimited,
+        ApiOutcome::Error,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ApiOutcome::Ok => "ok",
+            ApiOutcome::NotModified => "not_modified",
+            ApiOutcome::RateLimited => "rate_limited",
+            ApiOutcome::Error => "error",
+        }
+    }
+}
+
+static API_REQUESTS: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+static API_LATENCY_BUCKETS: [AtomicU64; 8] = [const { AtomicU64::new(0) }; 8];
+static API_LATENCY_MICROS: AtomicU64 = AtomicU64::new(0);
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+/// Unix time the current access token expires at; 0 until one is fetched.
+static TOKEN_EXPIRY: AtomicI64 = AtomicI64::new(0);
+
+/// Counts one API attempt (retries count separately).
+pub fn record_api(outcome: ApiOutcome, elapsed: Duration) {
+    API_REQUESTS[outcome as usize].fetch_add(1, Ordering::Relaxed);
+    let seconds = elapsed.as_secs_f64();
+    for (bucket, bound) in API_LATENCY_BUCKETS.iter().zip(LATENCY_BUCKETS) {
+        if seconds <= bound {
+            bucket.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    API_LATENCY_MICROS.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+}
+
+pub fn record_cache(hit: bool) {
+    let counter = if hit { &CACHE_HITS } else { &CACHE_MISSES };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn record_token_expiry(unix_seconds: i64) {
+    TOKEN_EXPIRY.store(unix_seconds, Ordering::Relaxed);
+}
+
+/// Prometheus text exposition of everything recorded in this process, plus
+/// gauges derived from `events` (the current agenda).
+pub fn render(events: &[CalendarEvent]) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP callux_api_requests_total Calendar API requests by outcome.\n");
+    out.push_str("# TYPE callux_api_requests_total counter\n");
+    let mut total = 0;
+    for outcome in ApiOutcome::ALL {
+        let count = API_REQUESTS[outcome as usize].load(Ordering::Relaxed);
+        total += count;
+        let _ = writeln!(
+            out,
+            "callux_api_requests_total{{outcome=\"{}\"}} {}",
+            outcome.as_str(),
+            count
+        );
+    }
+
+    out.push_str("# HELP callux_api_request_duration_seconds Calendar API request latency.\n");
+    out.push_str("# TYPE callux_api_request_duration_seconds histogram\n");
+    for (bucket, bound) in API_LATENCY_BUCKETS.iter().zip(LATENCY_BUCKETS) {
+        let _ = writeln!(
+            out,
+            "callux_api_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+            bound,
+            bucket.load(Ordering::Relaxed)
+        );
+    }
+    let _ = writeln!(
+        out,
+        "callux_api_request_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+        total
+    );
+    let _ = writeln!(
+        out,
+        "callux_api_request_duration_seconds_sum {}",
+        API_LATENCY_MICROS.load(Ordering::Relaxed) as f64 / 1_000_000.0
+    );
+    let _ = writeln!(out, "callux_api_request_duration_seconds_count {}", total);
+
+    out.push_str("# HELP callux_cache_lookups_total Agenda cache lookups by result.\n");
+    out.push_str("# TYPE callux_cache_lookups_total counter\n");
+    let _ = writeln!(
+        out,
+        "callux_cache_lookups_total{{result=\"hit\"}} {}",
+        CACHE_HITS.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        out,
+        "callux_cache_lookups_total{{result=\"miss\"}} {}",
+        CACHE_MISSES.load(Ordering::Relaxed)
+    );
+
+    let now = Utc::now();
+    let upcoming: Vec<&CalendarEvent> = events
+        .iter()
+        .filter(|e| e.end_time.with_timezone(&Utc) > now)
+        .collect();
+    out.push_str("# HELP callux_events_upcoming Events in the agenda that haven't ended.\n");
+    out.push_str("# TYPE callux_events_upcoming gauge\n");
+    let _ = writeln!(out, "callux_events_upcoming {}", upcoming.len());
+
+    if let Some(next) = upcoming.iter().find(|e| !e.all_day) {
+        let minutes = (next.start_time.with_timezone(&Utc) - now).num_seconds() as f64 / 60.0;
+        out.push_str(
+            "# HELP callux_next_event_minutes Minutes until the next timed event starts (negative while it runs).\n",
+        );
+        out.push_str("# TYPE callux_next_event_minutes gauge\n");
+        let _ = writeln!(out, "callux_next_event_minutes {:.1}", minutes);
+    }
+
+    let expiry = TOKEN_EXPIRY.load(Ordering::Relaxed);
+    if expiry > 0 {
+        out.push_str(
+            "# HELP callux_auth_token_expiry_timestamp_seconds When the current access token expires.\n",
+        );
+        out.push_str("# TYPE callux_auth_token_expiry_timestamp_seconds gauge\n");
+        let _ = writeln!(out, "callux_auth_token_expiry_timestamp_seconds {}", expiry);
+    }
+
+    out
+}
+
+pub fn response(events: &[CalendarEvent]) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(render(events))));
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    response
+}
+
+/// Serves `/metrics` for the daemon, reading the agenda it publishes.
+pub async fn serve(listen: SocketAddr, agenda: watch::Receiver<Vec<CalendarEvent>>) -> Result<()> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to listen for metrics on {}", listen))?;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let agenda = agenda.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request: hyper::Request<hyper::body::Incoming>| {
+                let reply = if request.uri().path() == "/metrics" {
+                    response(&agenda.borrow())
+                } else {
+                    let mut reply = Response::new(Full::new(Bytes::new()));
+                    *reply.status_mut() = StatusCode::NOT_FOUND;
+                    reply
+                };
+                async move { Ok::<_, hyper::Error>(reply) }
+            });
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
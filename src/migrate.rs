@@ -0,0 +1,95 @@
+use crate::config::{self, LEGACY_TOKEN_CACHE_PATH};
+use crate::security;
+use anyhow::{Result, anyhow, bail};
+use std::path::Path;
+use toml_edit::DocumentMut;
+
+pub const CURRENT_VERSION: u32 = 3;
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` document to `n + 2`. They edit
+/// the document in place so the user's comments and layout survive.
+const MIGRATIONS: &[fn(&mut DocumentMut) -> Result<()>] = &[
+    // 1 -> 2: only introduced `version` itself.
+    |_| Ok(()),
+    move_token_to_data_dir,
+];
+
+/// Brings the file at `path` up to `CURRENT_VERSION`, keeping a copy of the
+/// original next to it. Files that are already current are left untouched.
+pub fn upgrade(path: &Path) -> Result<()> {
+    let text = std::fs::read_to_string(path)?;
+    let mut doc = text
+        .parse::<DocumentMut>()
+        .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+
+    let version = match doc.get("version") {
+        None => 1,
+        Some(item) => item
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| anyhow!("Invalid version in {}", path.display()))?,
+    };
+
+    if version > CURRENT_VERSION {
+        bail!(
+            "{} is version {}, but this callux only understands up to {}; please upgrade callux",
+            path.display(),
+            version,
+            CURRENT_VERSION
+        );
+    }
+    if version == CURRENT_VERSION {
+        return Ok(());
+    }
+
+    let backup = path.with_extension(format!("toml.v{}.bak", version));
+    std::fs::copy(path, &backup)?;
+
+    for migration in &MIGRATIONS[(version.max(1) - 1) as usize..] {
+        migration(&mut doc)?;
+    }
+    doc["version"] = toml_edit::value(CURRENT_VERSION as i64);
+    std::fs::write(path, doc.to_string())?;
+
+    eprintln!(
+        "Migrated {} from version {} to {} (backup: {})",
+        path.display(),
+        version,
+        CURRENT_VERSION,
+        backup.display()
+    );
+    Ok(())
+}
+
+/// 2 -> 3: the token moved from the config directory to the XDG data
+/// directory. Only the old default location is moved; custom paths stay.
+fn move_token_to_data_dir(doc: &mut DocumentMut) -> Result<()> {
+    let current = doc
+        .get("auth")
+        .and_then(|auth| auth.get("token_cache_path"))
+        .and_then(|path| path.as_str());
+    if current != Some(LEGACY_TOKEN_CACHE_PATH) {
+        return Ok(());
+    }
+
+    let new_path = config::default_token_cache_path();
+    let from = config::expand_home(LEGACY_TOKEN_CACHE_PATH);
+    let to = config::expand_home(&new_path);
+
+    if Path::new(&from).exists() && !Path::new(&to).exists() {
+        if let Some(parent) = Path::new(&to).parent() {
+            security::create_private_dir(parent)?;
+        }
+        std::fs::rename(&from, &to)
+            .or_else(|_| std::fs::copy(&from, &to).and_then(|_| std::fs::remove_file(&from)))
+            .map_err(|e| anyhow!("Failed to move {} to {}: {}", from, to, e))?;
+        eprintln!("Moved token cache to {}", to);
+    }
+
+    if let Some(value) = doc["auth"]["token_cache_path"].as_value_mut() {
+        let decor = value.decor().clone();
+        *value = new_path.into();
+        *value.decor_mut() = decor;
+    }
+    Ok(())
+}
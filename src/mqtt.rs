@@ -0,0 +1,188 @@
+use crate::config::MqttConfig;
+use crate::output::CalendarEvent;
+use chrono::{DateTime, FixedOffset, Utc};
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use serde::Serialize;
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How often `next` is re-evaluated between agenda updates, so
+/// `in_progress` flips when a meeting starts rather than at the next refresh.
+const TICK: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Retained payload of `<topic>/next`.
+#[derive(Debug, PartialEq, Serialize)]
+struct NextEvent {
+    title: String,
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    calendar: String,
+    meeting_url: Option<String>,
+    in_progress: bool,
+}
+
+impl NextEvent {
+    /// The running or next timed event; all-day and free events don't make
+    /// anyone busy.
+    fn from_agenda(events: &[CalendarEvent]) -> Option<Self> {
+        let now = Utc::now();
+        events
+            .iter()
+            .filter(|e| !e.all_day && !e.free)
+            .find(|e| e.end_time.with_timezone(&Utc) > now)
+            .map(|e| NextEvent {
+                title: e.title.clone(),
+                start: e.start_time,
+                end: e.end_time,
+                calendar: e.calendar_name.clone(),
+                meeting_url: e.meeting_url.clone(),
+                in_progress: e.start_time.with_timezone(&Utc) <= now,
+            })
+    }
+}
+
+/// Publishes the agenda to `<topic>/agenda` and the next event to
+/// `<topic>/next` (both retained) whenever they change, plus Home Assistant
+/// discovery configs, until the daemon exits.
+pub async fn run(config: MqttConfig, mut agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    let Some(host) = config.host.clone() else {
+        return;
+    };
+    let status_topic = format!("{}/status", config.topic);
+
+    let mut options = MqttOptions::new(&config.client_id, host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(
+        &status_topic,
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+
+    let (client, mut eventloop) = AsyncClient::new(options, 16);
+    let announcer = client.clone();
+    let online_topic = status_topic.clone();
+    tokio::spawn(async move {
+        loop {
+            match eventloop.poll().await {
+                // The broker published our last will if we dropped off, so
+                // every (re)connect has to announce us again.
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    let _ = announcer.try_publish(&online_topic, QoS::AtLeastOnce, true, "online");
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("Warning: MQTT connection failed: {}", e);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    });
+
+    if config.discovery {
+        for (topic, payload) in discovery(&config) {
+            publish(&client, &topic, payload.to_string()).await;
+        }
+    }
+
+    let mut last_next: Option<Option<NextEvent>> = None;
+    let mut ticker = tokio::time::interval(TICK);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            changed = agenda.changed() => {
+                if changed.is_err() {
+                    publish(&client, &status_topic, "offline".to_string()).await;
+                    return;
+                }
+                let events = agenda.borrow_and_update().clone();
+                let payload = serde_json::to_string(&events).unwrap_or_default();
+                publish(&client, &format!("{}/agenda", config.topic), payload).await;
+            }
+        }
+
+        let next = NextEvent::from_agenda(&agenda.borrow());
+        if last_next.as_ref() != Some(&next) {
+            let payload = match &next {
+                Some(next) => serde_json::to_string(next).unwrap_or_default(),
+                None => "null".to_string(),
+            };
+            publish(&client, &format!("{}/next", config.topic), payload).await;
+            last_next = Some(next);
+        }
+    }
+}
+
+async fn publish(client: &AsyncClient, topic: &str, payload: String) {
+    if let Err(e) = client.publish(topic, QoS::AtLeastOnce, true, payload).await {
+        eprintln!("Warning: Failed to publish to {}: {}", topic, e);
+    }
+}
+
+/// Home Assistant MQTT discovery configs: the next meeting's title and
+/// start, and whether one is in progress.
+fn discovery(config: &MqttConfig) -> Vec<(String, serde_json::Value)> {
+    let device = json!({
+        "identifiers": [config.client_id],
+        "name": "callux",
+        "manufacturer": "callux",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+    let next_topic = format!("{}/next", config.topic);
+    let availability = format!("{}/status", config.topic);
+    let entity = |component: &str, object: &str, extra: serde_json::Value| {
+        let mut payload = json!({
+            "unique_id": format!("{}_{}", config.client_id, object),
+            "object_id": format!("{}_{}", config.client_id, object),
+            "state_topic": next_topic,
+            "availability_topic": availability,
+            "device": device,
+        });
+        if let (Some(payload), Some(extra)) = (payload.as_object_mut(), extra.as_object()) {
+            payload.extend(extra.clone());
+        }
+        (
+            format!(
+                "{}/{}/{}/{}/config",
+                config.discovery_prefix, component, config.client_id, object
+            ),
+            payload,
+        )
+    };
+
+    vec![
+        entity(
+            "sensor",
+            "next_meeting",
+            json!({
+                "name": "Next meeting",
+                "icon": "mdi:calendar-clock",
+                "value_template": "{{ value_json.title if value_json else 'None' }}",
+                "json_attributes_topic": next_topic,
+            }),
+        ),
+        entity(
+            "sensor",
+            "next_meeting_start",
+            json!({
+                "name": "Next meeting start",
+                "device_class": "timestamp",
+                "value_template": "{{ value_json.start if value_json else None }}",
+            }),
+        ),
+        entity(
+            "binary_sensor",
+            "in_meeting",
+            json!({
+                "name": "In meeting",
+                "icon": "mdi:account-voice",
+                "value_template": "{{ 'ON' if value_json and value_json.in_progress else 'OFF' }}",
+            }),
+        ),
+    ]
+}
@@ -0,0 +1,138 @@
+use crate::config::NotificationConfig;
+use crate::output::CalendarEvent;
+use chrono::{DateTime, Duration, Utc};
+use notify_rust::{Notification, Timeout};
+use std::collections::HashSet;
+use tokio::sync::{mpsc, watch};
+
+/// How often due reminders are looked for between agenda updates.
+const TICK: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// A reminder already shown: event, its start, and the lead time.
+type Sent = (String, i64, u32);
+
+/// Shows a desktop notification for each of an event's `reminder_minutes`
+/// as it comes due, with Join/Snooze/Dismiss actions, until the daemon
+/// exits.
+pub async fn run(config: NotificationConfig, mut agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    let (snooze_tx, mut snooze_rx) = mpsc::unbounded_channel::<CalendarEvent>();
+    let mut sent: HashSet<Sent> = HashSet::new();
+    let mut snoozed: Vec<(DateTime<Utc>, CalendarEvent)> = Vec::new();
+    let mut ticker = tokio::time::interval(TICK);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            changed = agenda.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+            Some(event) = snooze_rx.recv() => {
+                let until = Utc::now() + Duration::minutes(config.snooze_minutes as i64);
+                snoozed.push((until, event));
+            }
+        }
+
+        let now = Utc::now();
+        let mut due = Vec::new();
+        for event in agenda.borrow().iter() {
+            let start = event.start_time.with_timezone(&Utc);
+            if event.all_day || start <= now {
+                continue;
+            }
+            for &minutes in &event.reminder_minutes {
+                let key = (event.id.clone(), start.timestamp(), minutes);
+                if start - Duration::minutes(minutes as i64) <= now && sent.insert(key) {
+                    due.push(event.clone());
+                }
+            }
+        }
+
+        let (ready, waiting): (Vec<_>, Vec<_>) =
+            snoozed.into_iter().partition(|(until, _)| *until <= now);
+        snoozed = waiting;
+        due.extend(ready.into_iter().map(|(_, event)| event));
+
+        for event in due {
+            show(event, &config, snooze_tx.clone());
+        }
+        sent.retain(|(_, start, _)| *start > (now - Duration::days(1)).timestamp());
+    }
+}
+
+/// Shows the notification and waits for its action on a blocking thread;
+/// notify-rust's action loop blocks on the D-Bus connection.
+fn show(
+    event: CalendarEvent,
+    config: &NotificationConfig,
+    snooze: mpsc::UnboundedSender<CalendarEvent>,
+) {
+    let minutes = (event.start_time.with_timezone(&Utc) - Utc::now())
+        .num_minutes()
+        .max(0);
+    let body = format!(
+        "{} in {} min · {}",
+        event.start_time.format("%H:%M"),
+        minutes,
+        event.calendar_name
+    );
+    let snooze_label = format!("Snooze {} min", config.snooze_minutes);
+
+    tokio::task::spawn_blocking(move || {
+        let mut notification = Notification::new();
+        notification
+            .appname("callux")
+            .summary(&event.title)
+            .body(&body)
+            .timeout(Timeout::Never);
+        if event.meeting_url.is_some() {
+            notification.action("join", "Join");
+        }
+        notification
+            .action("snooze", &snooze_label)
+            .action("dismiss", "Dismiss");
+
+        let handle = match notification.show() {
+            Ok(handle) => handle,
+            Err(e) => {
+                eprintln!("Warning: Failed to show notification: {}", e);
+                return;
+            }
+        };
+
+        handle.wait_for_action(|action| match action {
+            "join" => {
+                if let Some(url) = &event.meeting_url {
+                    open_url(url);
+                }
+            }
+            "snooze" => {
+                let _ = snooze.send(event.clone());
+            }
+            _ => {}
+        });
+    });
+}
+
+/// A one-off notification for `callux remind`, without the daemon's
+/// actions, which would need the process to stay around.
+pub fn remind(event: &CalendarEvent, body: &str) -> anyhow::Result<()> {
+    Notification::new()
+        .appname("callux")
+        .summary(&event.title)
+        .body(body)
+        .show()?;
+    Ok(())
+}
+
+fn open_url(url: &str) {
+    if let Err(e) = std::process::Command::new("xdg-open")
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+    {
+        eprintln!("Warning: Failed to open {}: {}", url, e);
+    }
+}
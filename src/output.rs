@@ -1,33 +1,336 @@
+use crate::calendar::{StaleReason, Staleness};
+use crate::classes::ClassRules;
 use crate::cli::OutputFormat;
-use chrono::{DateTime, Local};
+use crate::config::{DisplayConfig, WeekNumberPlacement, WeekStart};
+use crate::error::CalendarError;
+use crate::sanitize::escape_markup;
+use crate::urgency;
+use crate::when;
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Offset, Utc};
+use chrono_tz::Tz;
 use colored::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Shorter breaks between events aren't worth a line of their own.
+const MIN_GAP: chrono::Duration = chrono::Duration::minutes(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct CalendarEvent {
     pub id: String,
     pub title: String,
     pub description: Option<String>,
-    pub start_time: DateTime<Local>,
-    pub end_time: DateTime<Local>,
+    pub start_time: DateTime<FixedOffset>,
+    pub end_time: DateTime<FixedOffset>,
     pub calendar_name: String,
     pub calendar_color: String,
     pub all_day: bool,
+    /// Minutes before start to remind at, from the calendar's config.
+    #[serde(default)]
+    pub reminder_minutes: Vec<u32>,
+    /// Video call link from the event's conference data or location.
+    #[serde(default)]
+    pub meeting_url: Option<String>,
+    /// The event's page in Google Calendar.
+    #[serde(default)]
+    pub html_link: Option<String>,
+    #[serde(default)]
+    pub location: Option<String>,
+    /// Google's `visibility`: `default`, `public`, `private` or
+    /// `confidential`.
+    #[serde(default)]
+    pub visibility: Option<String>,
+    /// Only kept with `display.show_cancelled`.
+    #[serde(default)]
+    pub cancelled: bool,
+    /// Your own `responseStatus` when you're a guest: `accepted`,
+    /// `declined`, `tentative` or `needsAction`.
+    #[serde(default)]
+    pub response: Option<String>,
+    /// People invited besides you; rooms and other resources don't count.
+    #[serde(default)]
+    pub guests: usize,
+    /// You organized it, rather than being invited.
+    #[serde(default)]
+    pub is_organizer: bool,
+    /// Who organized it: their name, or their email without one.
+    #[serde(default)]
+    pub organizer: Option<String>,
+    /// Shown as "Free" rather than "Busy" in Google (`transparency`), so it
+    /// doesn't make you busy.
+    #[serde(default)]
+    pub free: bool,
+    #[serde(default)]
+    pub kind: EventKind,
+    /// Id of the recurring event this is an occurrence of.
+    #[serde(default)]
+    pub series: Option<String>,
+    /// The series' `RRULE:` line, when Google returned it.
+    #[serde(default)]
+    pub recurrence: Option<String>,
+    /// IANA timezone the event was scheduled in.
+    #[serde(default)]
+    pub time_zone: Option<String>,
+    /// Year of birth, for birthdays whose series starts on the day itself.
+    #[serde(default)]
+    pub born: Option<i32>,
+    /// The event's `extendedProperties`, shared and private together; a
+    /// private one wins over a shared one with the same key.
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+}
+
+/// Google's `eventType`. Types callux has no special handling for count as
+/// `Default`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    #[default]
+    Default,
+    OutOfOffice,
+    FocusTime,
+    WorkingLocation,
+    /// From the `birthday` type or the contacts' birthdays calendar.
+    Birthday,
+    /// Flights, hotel stays, restaurant bookings and the like that Gmail
+    /// added from your email.
+    FromGmail,
+}
+
+impl EventKind {
+    pub fn parse(event_type: Option<&str>) -> Self {
+        match event_type {
+            Some("outOfOffice") => EventKind::OutOfOffice,
+            Some("focusTime") => EventKind::FocusTime,
+            Some("workingLocation") => EventKind::WorkingLocation,
+            Some("birthday") => EventKind::Birthday,
+            Some("fromGmail") => EventKind::FromGmail,
+            _ => EventKind::Default,
+        }
+    }
+
+    /// Marker shown before the title.
+    pub fn icon(self) -> Option<&'static str> {
+        match self {
+            EventKind::Default => None,
+            EventKind::OutOfOffice => Some("✈"),
+            EventKind::FocusTime => Some("◎"),
+            EventKind::WorkingLocation => Some("⌂"),
+            EventKind::Birthday => Some("🎂"),
+            EventKind::FromGmail => Some("✉"),
+        }
+    }
+}
+
+impl CalendarEvent {
+    /// The last day an all-day event covers; Google's end date is the day
+    /// after.
+    pub fn last_day(&self) -> NaiveDate {
+        let last = self.end_time.date_naive() - chrono::Duration::days(1);
+        last.max(self.start_time.date_naive())
+    }
+
+    /// "All day", or "All day until Fri Jan 05" for events spanning days.
+    pub fn all_day_label(&self) -> String {
+        let last = self.last_day();
+        if last == self.start_time.date_naive() {
+            "All day".to_string()
+        } else {
+            format!("All day until {}", last.format("%a %b %d"))
+        }
+    }
+
+    /// The start as "09:00 EST" in the event's own timezone, when that
+    /// differs from the one it's shown in.
+    pub fn source_time(&self) -> Option<String> {
+        let tz: Tz = self.time_zone.as_deref()?.parse().ok()?;
+        let source = self.start_time.with_timezone(&tz);
+        if source.offset().fix() == *self.start_time.offset() {
+            return None;
+        }
+        Some(source.format("%H:%M %Z").to_string())
+    }
+
+    /// "↻ weekly" for occurrences of a recurring event, or just "↻" when its
+    /// rule is unknown.
+    pub fn repeat_label(&self) -> Option<String> {
+        match self.recurrence.as_deref().and_then(when::describe_rrule) {
+            Some(frequency) => Some(format!("↻ {}", frequency)),
+            None if self.series.is_some() || self.recurrence.is_some() => Some("↻".to_string()),
+            None => None,
+        }
+    }
+
+    /// Timed events that have started but not ended yet.
+    pub fn in_progress(&self, now: DateTime<Utc>) -> bool {
+        !self.all_day && self.start_time <= now && now < self.end_time
+    }
+
+    /// Invitations you haven't firmly accepted: answered "maybe" or not yet.
+    pub fn is_unconfirmed(&self) -> bool {
+        matches!(self.response.as_deref(), Some("tentative" | "needsAction"))
+    }
+
+    /// "(turns 34, in 12 days)" for birthdays, seen from `today`; without
+    /// a known year of birth just the countdown.
+    pub fn birthday_note(&self, today: NaiveDate) -> Option<String> {
+        if self.kind != EventKind::Birthday {
+            return None;
+        }
+        let day = self.start_time.date_naive();
+        let when = match (day - today).num_days() {
+            ..0 => None,
+            0 => Some("today".to_string()),
+            1 => Some("tomorrow".to_string()),
+            days => Some(format!("in {} days", days)),
+        };
+        let age = self.born.map(|born| match when {
+            Some(_) => format!("turns {}", day.year() - born),
+            None => format!("turned {}", day.year() - born),
+        });
+        let parts: Vec<String> = age.into_iter().chain(when).collect();
+        if parts.is_empty() {
+            return None;
+        }
+        Some(format!("({})", parts.join(", ")))
+    }
+
+    /// The title, prefixed with its type's icon and "?" while unconfirmed,
+    /// and marked when the event was cancelled. Birthdays get their
+    /// age and countdown.
+    pub fn display_title(&self) -> String {
+        let mut title = match self.kind.icon() {
+            Some(icon) => format!("{} {}", icon, self.title),
+            None => self.title.clone(),
+        };
+        let today = Utc::now()
+            .with_timezone(self.start_time.offset())
+            .date_naive();
+        if let Some(note) = self.birthday_note(today) {
+            title = format!("{} {}", title, note);
+        }
+        if self.is_unconfirmed() {
+            title = format!("? {}", title);
+        }
+        if self.cancelled {
+            title.push_str(" (cancelled)");
+        }
+        title
+    }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct WaybarOutput {
     pub text: String,
     pub tooltip: String,
-    pub class: String,
+    pub class: WaybarClass,
     pub percentage: u8,
+    pub stale: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale_reason: Option<StaleReason>,
+    /// Your response to the next event, when it's an invitation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    /// How pressing the next event is, from 0 to 100.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub urgency: Option<u8>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// An event as `--format json-raw` prints it. `callux fmt` reads it back
+/// as a plain event, ignoring `urgency`.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RawEvent<'a> {
+    #[serde(flatten)]
+    pub event: &'a CalendarEvent,
+    /// How pressing the event is, from 0 to 100.
+    pub urgency: u8,
+}
+
+/// A single class name, or a list once `display.classes` rules add to it;
+/// Waybar accepts either.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum WaybarClass {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl WaybarClass {
+    fn new(base: &str, extra: Vec<String>) -> Self {
+        if extra.is_empty() {
+            return WaybarClass::One(base.to_string());
+        }
+        let mut classes = vec![base.to_string()];
+        classes.extend(extra);
+        WaybarClass::Many(classes)
+    }
+}
+
+/// Printed on stdout instead of events when a `--format json` command
+/// fails, so Waybar shows an error state rather than nothing.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ErrorOutput {
+    pub text: String,
+    pub tooltip: String,
+    pub class: String,
+    pub error: ErrorDetail,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ErrorDetail {
+    /// The HTTP status, when the error came from a Google response.
+    pub code: Option<u16>,
+    pub kind: &'static str,
+    pub message: String,
+    pub hint: Option<String>,
+    /// Whether the next refresh may well succeed on its own.
+    pub retryable: bool,
+}
+
+impl ErrorOutput {
+    /// `cause` is the `CalendarError` behind `message`, when there is one.
+    pub fn new(message: String, cause: Option<&CalendarError>) -> Self {
+        let hint = cause.and_then(|e| e.class().hint()).map(str::to_string);
+        let tooltip = match &hint {
+            Some(hint) => format!("{}\n\nHint: {}", message, hint),
+            None => message.clone(),
+        };
+        ErrorOutput {
+            text: "⚠ Calendar error".to_string(),
+            tooltip: escape_markup(&tooltip),
+            class: "calendar-error".to_string(),
+            error: ErrorDetail {
+                code: cause.and_then(|e| e.status()),
+                kind: cause.map_or("other", |e| e.kind()),
+                message,
+                hint,
+                retryable: cause.is_some_and(CalendarError::is_retryable),
+            },
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
 }
 
 pub struct OutputFormatter {
     format: OutputFormat,
     date_format: String,
     max_events: usize,
+    staleness: Option<Staleness>,
+    week_numbers: Option<WeekStart>,
+    week_number_placement: WeekNumberPlacement,
+    week_groups: bool,
+    event_timezones: bool,
+    gaps: bool,
+    response_colors: bool,
+    back_to_back: Option<chrono::Duration>,
+    classes: ClassRules,
+    warnings: Vec<String>,
 }
 
 impl OutputFormatter {
@@ -36,176 +339,562 @@ impl OutputFormatter {
             format,
             date_format,
             max_events,
+            staleness: None,
+            week_numbers: None,
+            week_number_placement: WeekNumberPlacement::Heading,
+            week_groups: false,
+            event_timezones: false,
+            gaps: false,
+            response_colors: false,
+            back_to_back: None,
+            classes: ClassRules::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// A formatter set up from the `[display]` settings, so `agenda`, `fmt`
+    /// and snapshots render events the same way.
+    pub fn for_display(format: OutputFormat, display: &DisplayConfig, week_groups: bool) -> Self {
+        Self::new(format, display.date_format.clone(), display.max_events)
+            .with_week_numbers(
+                (display.show_week_numbers || week_groups).then_some(display.week_start),
+            )
+            .with_week_number_placement(display.week_number_placement)
+            .with_week_groups(week_groups)
+            .with_event_timezones(display.show_event_timezone)
+            .with_gaps(display.show_gaps)
+            .with_response_colors(display.color_by_response)
+            .with_back_to_back(
+                display
+                    .back_to_back_minutes
+                    .map(|minutes| chrono::Duration::minutes(minutes as i64)),
+            )
+            .with_class_rules(ClassRules::compile(&display.classes))
+    }
+
+    /// Follows start times with the time in the event's own timezone when
+    /// that differs, e.g. "14:00 (09:00 EST)".
+    pub fn with_event_timezones(mut self, event_timezones: bool) -> Self {
+        self.event_timezones = event_timezones;
+        self
+    }
+
+    pub fn with_gaps(mut self, gaps: bool) -> Self {
+        self.gaps = gaps;
+        self
+    }
+
+    /// Styles colored titles by your response, so the agenda shows which
+    /// invitations still need one.
+    pub fn with_response_colors(mut self, response_colors: bool) -> Self {
+        self.response_colors = response_colors;
+        self
+    }
+
+    /// The free time between the events so far that day and `entry`, e.g.
+    /// "— 2h free —". Only timed events you're busy for take up time.
+    fn gap_before(
+        &self,
+        entry: &DayEntry,
+        busy_until: &mut Option<DateTime<FixedOffset>>,
+    ) -> Option<String> {
+        let event = entry.event;
+        if !self.gaps || !takes_time(event) {
+            return None;
+        }
+        let gap = busy_until.map(|until| event.start_time - until);
+        *busy_until = Some(busy_until.map_or(event.end_time, |until| until.max(event.end_time)));
+        gap.filter(|gap| *gap >= MIN_GAP)
+            .map(|gap| format!("— {} free —", when::format_duration(gap)))
+    }
+
+    /// Warns about events starting less than `buffer` after the ones before
+    /// them end, or while they're still going.
+    pub fn with_back_to_back(mut self, buffer: Option<chrono::Duration>) -> Self {
+        self.back_to_back = buffer;
+        self
+    }
+
+    /// Extra Waybar classes for the next event, from `display.classes`.
+    pub fn with_class_rules(mut self, classes: ClassRules) -> Self {
+        self.classes = classes;
+        self
+    }
+
+    /// Events that start within `back_to_back` of the end of the events
+    /// before them, in order.
+    fn back_to_back<'a>(&self, events: &[&'a CalendarEvent]) -> Vec<&'a CalendarEvent> {
+        let Some(buffer) = self.back_to_back else {
+            return Vec::new();
+        };
+        let mut busy_until: Option<DateTime<FixedOffset>> = None;
+        let mut tight = Vec::new();
+        for event in events.iter().copied().filter(|event| takes_time(event)) {
+            let tight_after = |until: DateTime<FixedOffset>| {
+                event.start_time <= until || event.start_time < until + buffer
+            };
+            if busy_until.is_some_and(tight_after) {
+                tight.push(event);
+            }
+            busy_until = Some(busy_until.map_or(event.end_time, |until| until.max(event.end_time)));
+        }
+        tight
+    }
+
+    fn entry_time(&self, entry: &DayEntry, time_format: &str) -> String {
+        let when = entry.when(time_format);
+        if !self.event_timezones || entry.continued || entry.event.all_day {
+            return when;
+        }
+        match entry.event.source_time() {
+            Some(source) => format!("{} ({})", when, source),
+            None => when,
+        }
+    }
+
+    /// Adds a "Week N" heading before the first day of each week.
+    pub fn with_week_numbers(mut self, week_start: Option<WeekStart>) -> Self {
+        self.week_numbers = week_start;
+        self
+    }
+
+    pub fn with_week_number_placement(mut self, placement: WeekNumberPlacement) -> Self {
+        self.week_number_placement = placement;
+        self
+    }
+
+    /// Puts a heading with the week's event count before every week,
+    /// whatever `week_number_placement` says.
+    pub fn with_week_groups(mut self, week_groups: bool) -> Self {
+        self.week_groups = week_groups;
+        self
+    }
+
+    /// The heading to print before `date`, if it starts a week not seen yet.
+    fn week_heading(
+        &self,
+        date: NaiveDate,
+        current: &mut Option<NaiveDate>,
+        days: &[(NaiveDate, Vec<DayEntry<'_>>)],
+    ) -> Option<String> {
+        if self.week_number_placement != WeekNumberPlacement::Heading && !self.week_groups {
+            return None;
+        }
+        let week_start = self.week_numbers?;
+        let offset = match week_start {
+            WeekStart::Monday => date.weekday().num_days_from_monday(),
+            WeekStart::Sunday => date.weekday().num_days_from_sunday(),
+        };
+        let first_day = date - chrono::Duration::days(offset as i64);
+        if *current == Some(first_day) {
+            return None;
+        }
+        *current = Some(first_day);
+
+        let label = self.week_label(date)?;
+        if !self.week_groups {
+            return Some(label);
+        }
+        // Events running across several days count once.
+        let last_day = first_day + chrono::Duration::days(6);
+        let mut events: Vec<&CalendarEvent> = Vec::new();
+        for (_, entries) in days
+            .iter()
+            .filter(|(day, _)| (first_day..=last_day).contains(day))
+        {
+            for entry in entries {
+                if !events.iter().any(|seen| std::ptr::eq(*seen, entry.event)) {
+                    events.push(entry.event);
+                }
+            }
         }
+        let noun = if events.len() == 1 { "event" } else { "events" };
+        Some(format!("{} · {} {}", label, events.len(), noun))
+    }
+
+    /// `date` in `format`, followed by its week number when those go on
+    /// every day.
+    fn day_header(&self, date: NaiveDate, format: &str) -> String {
+        let header = date.format(format).to_string();
+        if self.week_number_placement != WeekNumberPlacement::Day {
+            return header;
+        }
+        match self.week_label(date) {
+            Some(week) => format!("{} · {}", header, week),
+            None => header,
+        }
+    }
+
+    fn week_label(&self, date: NaiveDate) -> Option<String> {
+        let number = match self.week_numbers? {
+            WeekStart::Monday => date.iso_week().week(),
+            WeekStart::Sunday => {
+                let jan1 = NaiveDate::from_ymd_opt(date.year(), 1, 1)?;
+                (date.ordinal0() + jan1.weekday().num_days_from_sunday()) / 7 + 1
+            }
+        };
+        Some(format!("Week {}", number))
+    }
+
+    pub fn with_staleness(mut self, staleness: Option<Staleness>) -> Self {
+        self.staleness = staleness;
+        self
+    }
+
+    /// Per-calendar fetch problems, listed after the agenda (or in
+    /// `warnings` in JSON).
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    fn stale_notice(&self) -> Option<String> {
+        let staleness = self.staleness.as_ref()?;
+        let prefix = match staleness.reason {
+            StaleReason::Offline => "Offline",
+            StaleReason::Unreachable => "Google unreachable",
+            StaleReason::RateLimited => "Rate limited by Google",
+            StaleReason::Revalidating => "Refreshing",
+        };
+
+        Some(match staleness.since {
+            Some(fetched_at) => format!(
+                "{} - showing cached events from {}",
+                prefix,
+                fetched_at.format("%Y-%m-%d %H:%M")
+            ),
+            None => format!("{} - showing cached events", prefix),
+        })
     }
 
     pub fn format_events(&self, events: &[CalendarEvent]) -> String {
         let limited_events: Vec<&CalendarEvent> = events.iter().take(self.max_events).collect();
+        let tight = self.back_to_back(&limited_events);
+        let mut warnings = self.warnings.clone();
+        warnings.extend(tight.iter().map(|event| {
+            format!(
+                "No break before {} at {}",
+                event.display_title(),
+                event.start_time.format("%a %H:%M")
+            )
+        }));
+
+        let mut output = match self.format {
+            OutputFormat::Json => return self.format_json(&limited_events, &tight, warnings),
+            // Warnings have nowhere to go without changing the shape.
+            OutputFormat::JsonRaw => {
+                let now = Utc::now();
+                let raw: Vec<RawEvent> = limited_events
+                    .iter()
+                    .map(|event| RawEvent {
+                        event,
+                        urgency: urgency::score(event, &limited_events, now),
+                    })
+                    .collect();
+                return serde_json::to_string(&raw).unwrap_or_else(|_| "[]".to_string());
+            }
+            OutputFormat::Human => self.format_human(&limited_events, &tight),
+            OutputFormat::Colored => self.format_colored(&limited_events, &tight),
+        };
 
-        match self.format {
-            OutputFormat::Json => self.format_json(&limited_events),
-            OutputFormat::Human => self.format_human(&limited_events),
-            OutputFormat::Colored => self.format_colored(&limited_events),
+        if !warnings.is_empty() {
+            output.push('\n');
         }
+        for warning in &warnings {
+            let note = format!("Warning: {}", warning);
+            match self.format {
+                OutputFormat::Colored => output.push_str(&format!("\n{}", note.bright_yellow())),
+                _ => output.push_str(&format!("\n{}", note)),
+            }
+        }
+        output
     }
 
-    fn format_json(&self, events: &[&CalendarEvent]) -> String {
+    fn format_json(
+        &self,
+        events: &[&CalendarEvent],
+        tight: &[&CalendarEvent],
+        warnings: Vec<String>,
+    ) -> String {
         let waybar_output = if events.is_empty() {
             WaybarOutput {
                 text: "No events".to_string(),
                 tooltip: "No upcoming events".to_string(),
-                class: "calendar-empty".to_string(),
+                class: WaybarClass::One("calendar-empty".to_string()),
                 percentage: 0,
+                stale: self.staleness.is_some(),
+                stale_reason: self.staleness.as_ref().map(|s| s.reason),
+                response: None,
+                urgency: None,
+                warnings,
             }
         } else {
             let next_event = events[0];
             let text = if next_event.all_day {
-                format!("{}", next_event.title)
+                next_event.display_title()
             } else {
                 format!(
                     "{} {}",
                     next_event.start_time.format("%H:%M"),
-                    next_event.title
+                    next_event.display_title()
                 )
             };
 
-            let tooltip = self.create_tooltip(events);
-            let class = if events.len() > 1 {
-                "calendar-multiple".to_string()
+            let mut tooltip = self.create_tooltip(events, tight);
+            if let Some(notice) = self.stale_notice() {
+                tooltip = format!("{}\n\n{}", notice, tooltip);
+            }
+            let base = if events.len() > 1 {
+                "calendar-multiple"
             } else {
-                "calendar-single".to_string()
+                "calendar-single"
             };
+            let class = WaybarClass::new(base, self.classes.classes_for(next_event));
 
             WaybarOutput {
-                text,
-                tooltip,
+                text: escape_markup(&text),
+                tooltip: escape_markup(&tooltip),
                 class,
                 percentage: std::cmp::min(events.len() * 10, 100) as u8,
+                stale: self.staleness.is_some(),
+                stale_reason: self.staleness.as_ref().map(|s| s.reason),
+                response: next_event.response.clone(),
+                urgency: Some(urgency::score(next_event, events, Utc::now())),
+                warnings,
             }
         };
 
         serde_json::to_string(&waybar_output).unwrap_or_else(|_| "{}".to_string())
     }
 
-    fn format_human(&self, events: &[&CalendarEvent]) -> String {
-        if events.is_empty() {
-            return "No upcoming events".to_string();
+    fn format_human(&self, events: &[&CalendarEvent], tight: &[&CalendarEvent]) -> String {
+        let mut output = String::new();
+        if let Some(notice) = self.stale_notice() {
+            output.push_str(&format!("({})\n\n", notice));
         }
 
-        let mut output = String::new();
-        let mut current_date = String::new();
+        if events.is_empty() {
+            output.push_str("No upcoming events");
+            return output;
+        }
 
-        for event in events {
-            let event_date = event.start_time.format("%Y-%m-%d").to_string();
-            if event_date != current_date {
-                if !current_date.is_empty() {
-                    output.push('\n');
-                }
-                output.push_str(&format!("{}\n", event.start_time.format("%A, %B %d, %Y")));
-                current_date = event_date;
+        let now = Utc::now();
+        let mut current_week = None;
+        let days = by_day(events);
+        for (i, (date, entries)) in days.iter().enumerate() {
+            if i > 0 {
+                output.push('\n');
+            }
+            if let Some(week) = self.week_heading(*date, &mut current_week, &days) {
+                output.push_str(&format!("== {} ==\n", week));
             }
+            output.push_str(&format!("{}\n", self.day_header(*date, "%A, %B %d, %Y")));
 
-            if event.all_day {
-                output.push_str(&format!("  All day: {}\n", event.title));
-            } else {
+            let mut busy_until = None;
+            for entry in entries {
+                if let Some(gap) = self.gap_before(entry, &mut busy_until) {
+                    output.push_str(&format!("  {}\n", gap));
+                }
                 output.push_str(&format!(
-                    "  {}: {}\n",
-                    event.start_time.format(&self.date_format),
-                    event.title
+                    "  {}: {}",
+                    self.entry_time(entry, &self.date_format),
+                    entry.event.display_title()
                 ));
+                if entry.event.in_progress(now) {
+                    output.push_str(" (now)");
+                }
+                if starts_tight(entry, tight) {
+                    output.push_str(" (back-to-back)");
+                }
+                if let Some(repeat) = entry.event.repeat_label() {
+                    output.push_str(&format!(" {}", repeat));
+                }
+                output.push('\n');
             }
         }
 
         output.trim_end().to_string()
     }
 
-    fn format_colored(&self, events: &[&CalendarEvent]) -> String {
-        if events.is_empty() {
-            return "No upcoming events".bright_yellow().to_string();
+    fn format_colored(&self, events: &[&CalendarEvent], tight: &[&CalendarEvent]) -> String {
+        let mut output = String::new();
+        if let Some(notice) = self.stale_notice() {
+            output.push_str(&format!("{}\n\n", notice.dimmed()));
         }
 
-        let mut output = String::new();
-        let mut current_date = String::new();
+        if events.is_empty() {
+            output.push_str(&"No upcoming events".bright_yellow().to_string());
+            return output;
+        }
 
-        for event in events {
-            let event_date = event.start_time.format("%Y-%m-%d").to_string();
-            if event_date != current_date {
-                if !current_date.is_empty() {
-                    output.push('\n');
-                }
-                output.push_str(&format!(
-                    "{}\n",
-                    event
-                        .start_time
-                        .format("%A, %B %d, %Y")
-                        .to_string()
-                        .bright_blue()
-                        .bold()
-                ));
-                current_date = event_date;
+        let now = Utc::now();
+        let mut current_week = None;
+        let days = by_day(events);
+        for (i, (date, entries)) in days.iter().enumerate() {
+            if i > 0 {
+                output.push('\n');
+            }
+            if let Some(week) = self.week_heading(*date, &mut current_week, &days) {
+                output.push_str(&format!("{}\n", week.bright_magenta().bold()));
             }
+            output.push_str(&format!(
+                "{}\n",
+                self.day_header(*date, "%A, %B %d, %Y").bright_blue().bold()
+            ));
 
-            if event.all_day {
-                output.push_str(&format!(
-                    "  {}: {}\n",
-                    "All day".bright_green(),
-                    event.title.white()
-                ));
-            } else {
+            let mut busy_until = None;
+            for entry in entries {
+                if let Some(gap) = self.gap_before(entry, &mut busy_until) {
+                    output.push_str(&format!("  {}\n", gap.dimmed()));
+                }
+                let title = entry.event.display_title();
+                let title = if self.response_colors {
+                    response_style(entry.event, title)
+                } else if entry.event.is_unconfirmed() {
+                    title.dimmed()
+                } else {
+                    title.white()
+                };
                 output.push_str(&format!(
-                    "  {}: {}\n",
-                    event
-                        .start_time
-                        .format(&self.date_format)
-                        .to_string()
-                        .bright_green(),
-                    event.title.white()
+                    "  {}: {}",
+                    self.entry_time(entry, &self.date_format).bright_green(),
+                    title
                 ));
+                if entry.event.in_progress(now) {
+                    output.push_str(&format!(" {}", "(now)".bright_red()));
+                }
+                if starts_tight(entry, tight) {
+                    output.push_str(&format!(" {}", "(back-to-back)".bright_yellow()));
+                }
+                if let Some(repeat) = entry.event.repeat_label() {
+                    output.push_str(&format!(" {}", repeat.bright_black()));
+                }
+                output.push('\n');
             }
         }
 
         output.trim_end().to_string()
     }
 
-    fn create_tooltip(&self, events: &[&CalendarEvent]) -> String {
+    fn create_tooltip(&self, events: &[&CalendarEvent], tight: &[&CalendarEvent]) -> String {
         let mut tooltip = String::new();
-        let mut events_by_date: HashMap<String, Vec<&CalendarEvent>> = HashMap::new();
 
-        for event in events {
-            let date_key = event.start_time.format("%Y-%m-%d").to_string();
-            events_by_date
-                .entry(date_key)
-                .or_insert_with(Vec::new)
-                .push(event);
-        }
-
-        let mut sorted_dates: Vec<String> = events_by_date.keys().cloned().collect();
-        sorted_dates.sort();
-
-        for (i, date) in sorted_dates.iter().enumerate() {
+        let mut current_week = None;
+        let days = by_day(events);
+        for (i, (date, entries)) in days.iter().enumerate() {
             if i > 0 {
                 tooltip.push_str("\n\n");
             }
 
-            let events_on_date = &events_by_date[date];
-            let parsed_date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
-            let formatted_date = parsed_date.format("%A, %B %d");
-
-            tooltip.push_str(&format!("{}:\n", formatted_date));
+            if let Some(week) = self.week_heading(*date, &mut current_week, &days) {
+                tooltip.push_str(&format!("{}\n", week));
+            }
+            tooltip.push_str(&format!("{}:\n", self.day_header(*date, "%A, %B %d")));
 
-            for event in events_on_date {
-                if event.all_day {
-                    tooltip.push_str(&format!("• All day: {}\n", event.title));
+            for entry in entries {
+                let mark = if starts_tight(entry, tight) {
+                    " ⚠"
                 } else {
-                    tooltip.push_str(&format!(
-                        "• {}: {}\n",
-                        event.start_time.format("%H:%M"),
-                        event.title
-                    ));
-                }
+                    ""
+                };
+                tooltip.push_str(&format!(
+                    "• {}: {}{}\n",
+                    self.entry_time(entry, "%H:%M"),
+                    entry.event.display_title(),
+                    mark
+                ));
             }
         }
 
         tooltip.trim_end().to_string()
     }
 }
+
+/// `title` styled by your response to `event`: highlighted while it
+/// awaits one, dimmed when tentative, struck through when declined, and in
+/// bold when you organized it.
+fn response_style(event: &CalendarEvent, title: String) -> ColoredString {
+    let title = match event.response.as_deref() {
+        Some("needsAction") => title.black().on_bright_yellow(),
+        Some("tentative") => title.dimmed(),
+        Some("declined") => title.dimmed().strikethrough(),
+        _ => title.white(),
+    };
+    if event.is_organizer {
+        title.bold()
+    } else {
+        title
+    }
+}
+
+/// Whether an event takes up time you could otherwise use: timed, and
+/// neither cancelled, marked free nor declined.
+pub fn takes_time(event: &CalendarEvent) -> bool {
+    !event.all_day
+        && !event.cancelled
+        && !event.free
+        && event.response.as_deref() != Some("declined")
+}
+
+/// Whether `entry` is where one of the `tight` events starts.
+fn starts_tight(entry: &DayEntry, tight: &[&CalendarEvent]) -> bool {
+    !entry.continued && tight.iter().any(|event| std::ptr::eq(*event, entry.event))
+}
+
+/// An event's share of one day in a listing.
+pub struct DayEntry<'a> {
+    pub event: &'a CalendarEvent,
+    /// Carried over from an earlier day, for events running past midnight.
+    pub continued: bool,
+    date: NaiveDate,
+}
+
+impl DayEntry<'_> {
+    /// The time shown next to the title: the start on the first day, then
+    /// "Until 02:00" on the day it ends or "All day" in between.
+    pub fn when(&self, time_format: &str) -> String {
+        let event = self.event;
+        match (self.continued, event.all_day) {
+            (false, true) => event.all_day_label(),
+            (false, false) => event.start_time.format(time_format).to_string(),
+            (true, false) if event.end_time.date_naive() == self.date => {
+                format!("Until {}", event.end_time.format(time_format))
+            }
+            (true, _) => "All day".to_string(),
+        }
+    }
+}
+
+/// Events grouped by the days they cover, in date order. Overnight and
+/// multi-day events appear on each of their days from today on, after the
+/// events that started earlier.
+pub fn by_day<'a>(events: &[&'a CalendarEvent]) -> Vec<(NaiveDate, Vec<DayEntry<'a>>)> {
+    let mut days: BTreeMap<NaiveDate, Vec<DayEntry<'a>>> = BTreeMap::new();
+    for event in events {
+        let first = event.start_time.date_naive();
+        let last = if event.all_day {
+            event.last_day()
+        } else {
+            // An event ending at midnight doesn't show on the next day.
+            (event.end_time - chrono::Duration::seconds(1))
+                .date_naive()
+                .max(first)
+        };
+        let today = Utc::now()
+            .with_timezone(event.start_time.offset())
+            .date_naive();
+
+        for date in first.iter_days().take_while(|date| *date <= last) {
+            let continued = date != first;
+            if continued && date < today {
+                continue;
+            }
+            days.entry(date).or_default().push(DayEntry {
+                event,
+                continued,
+                date,
+            });
+        }
+    }
+    days.into_iter().collect()
+}
@@ -0,0 +1,146 @@
+use crate::config::{PopupConfig, PopupPosition};
+use crate::output::{CalendarEvent, by_day};
+use anyhow::{Result, anyhow};
+use gtk4::prelude::*;
+use gtk4::{
+    Application, ApplicationWindow, EventControllerKey, Label, Orientation, ScrolledWindow, gdk,
+    glib, pango,
+};
+use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
+
+const APP_ID: &str = "org.callux.Popup";
+
+/// Shows `events` in a borderless layer-shell window until Escape, the
+/// timeout, or another `callux popup` (which closes it, so a bar button
+/// toggles the popup). Blocks until the window is gone.
+pub fn show(config: &PopupConfig, events: Vec<CalendarEvent>) -> Result<()> {
+    gtk4::init().map_err(|e| anyhow!("Failed to open a display: {}", e))?;
+    if !gtk4_layer_shell::is_supported() {
+        return Err(anyhow!(
+            "The compositor doesn't support wlr-layer-shell (Hyprland, Sway, river, ... do)"
+        ));
+    }
+
+    let app = Application::builder().application_id(APP_ID).build();
+    let config = config.clone();
+    app.connect_activate(move |app| {
+        // A second `callux popup` lands here in the first one's process.
+        if let Some(window) = app.active_window() {
+            window.close();
+            return;
+        }
+        build(app, &config, &events).present();
+    });
+
+    app.run_with_args::<&str>(&[]);
+    Ok(())
+}
+
+fn build(app: &Application, config: &PopupConfig, events: &[CalendarEvent]) -> ApplicationWindow {
+    let window = ApplicationWindow::builder()
+        .application(app)
+        .title("callux")
+        .decorated(false)
+        .default_width(config.width)
+        .build();
+
+    window.init_layer_shell();
+    window.set_namespace("callux");
+    window.set_layer(Layer::Overlay);
+    window.set_keyboard_mode(KeyboardMode::OnDemand);
+    let (vertical, horizontal) = anchors(config.position);
+    for edge in vertical.into_iter().chain(horizontal) {
+        window.set_anchor(edge, true);
+        window.set_margin(edge, config.margin);
+    }
+
+    let list = gtk4::Box::new(Orientation::Vertical, 4);
+    list.set_margin_top(12);
+    list.set_margin_bottom(12);
+    list.set_margin_start(12);
+    list.set_margin_end(12);
+    if events.is_empty() {
+        list.append(&Label::new(Some("No upcoming events")));
+    }
+
+    let refs: Vec<&CalendarEvent> = events.iter().collect();
+    for (i, (day, entries)) in by_day(&refs).into_iter().enumerate() {
+        let heading = Label::new(None);
+        heading.set_markup(&format!(
+            "<b>{}</b>",
+            glib::markup_escape_text(&day.format("%A, %B %d").to_string())
+        ));
+        heading.set_xalign(0.0);
+        if i > 0 {
+            heading.set_margin_top(8);
+        }
+        list.append(&heading);
+
+        for entry in entries {
+            let event = entry.event;
+            let when = if event.all_day || entry.continued {
+                entry.when("%H:%M")
+            } else {
+                format!(
+                    "{}–{}",
+                    event.start_time.format("%H:%M"),
+                    event.end_time.format("%H:%M")
+                )
+            };
+            let row = Label::new(None);
+            row.set_markup(&format!(
+                "<span foreground=\"{}\">●</span>  {}  {}",
+                glib::markup_escape_text(&event.calendar_color),
+                glib::markup_escape_text(&when),
+                glib::markup_escape_text(&event.display_title())
+            ));
+            row.set_xalign(0.0);
+            row.set_ellipsize(pango::EllipsizeMode::End);
+            list.append(&row);
+        }
+    }
+
+    let scrolled = ScrolledWindow::builder()
+        .hscrollbar_policy(gtk4::PolicyType::Never)
+        .propagate_natural_height(true)
+        .max_content_height(config.max_height)
+        .child(&list)
+        .build();
+    window.set_child(Some(&scrolled));
+
+    let keys = EventControllerKey::new();
+    let target = window.downgrade();
+    keys.connect_key_pressed(move |_, key, _, _| {
+        if key == gdk::Key::Escape {
+            if let Some(window) = target.upgrade() {
+                window.close();
+            }
+            return glib::Propagation::Stop;
+        }
+        glib::Propagation::Proceed
+    });
+    window.add_controller(keys);
+
+    if config.timeout_seconds > 0 {
+        let target = window.downgrade();
+        glib::timeout_add_seconds_local_once(config.timeout_seconds, move || {
+            if let Some(window) = target.upgrade() {
+                window.close();
+            }
+        });
+    }
+
+    window
+}
+
+/// Edges the window is pinned to; an unanchored axis is centred.
+fn anchors(position: PopupPosition) -> (Option<Edge>, Option<Edge>) {
+    match position {
+        PopupPosition::TopLeft => (Some(Edge::Top), Some(Edge::Left)),
+        PopupPosition::Top => (Some(Edge::Top), None),
+        PopupPosition::TopRight => (Some(Edge::Top), Some(Edge::Right)),
+        PopupPosition::BottomLeft => (Some(Edge::Bottom), Some(Edge::Left)),
+        PopupPosition::Bottom => (Some(Edge::Bottom), None),
+        PopupPosition::BottomRight => (Some(Edge::Bottom), Some(Edge::Right)),
+    }
+}
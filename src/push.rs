@@ -0,0 +1,168 @@
+use crate::calendar::{CalendarClient, WatchChannel};
+use anyhow::{Context, Result};
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, Notify};
+
+/// Lifetime requested for each channel; Google may grant less.
+const CHANNEL_TTL_SECONDS: u64 = 86400;
+/// Channels are replaced this long before they expire.
+const RENEW_MARGIN: Duration = Duration::from_secs(3600);
+
+/// Google's `events.watch` channels for every enabled calendar, pointing at
+/// the public `address` that forwards to `listen`.
+pub struct PushChannels {
+    client: Arc<CalendarClient>,
+    address: String,
+    token: String,
+    channels: Mutex<Vec<WatchChannel>>,
+}
+
+impl PushChannels {
+    pub fn new(client: Arc<CalendarClient>, address: String) -> Result<Self> {
+        Ok(Self {
+            client,
+            address,
+            token: channel_token().context("Failed to generate a push channel token")?,
+            channels: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Registers fresh channels, stops the ones they replace, and returns
+    /// how long until they need renewing.
+    pub async fn renew(&self) -> Duration {
+        let mut fresh = Vec::new();
+        for (i, calendar_id) in self.client.enabled_calendar_ids().iter().enumerate() {
+            let channel_id = format!("callux-{}-{}", i, unix_millis());
+            match self
+                .client
+                .watch_events(
+                    calendar_id,
+                    &channel_id,
+                    &self.address,
+                    &self.token,
+                    CHANNEL_TTL_SECONDS,
+                )
+                .await
+            {
+                Ok(channel) => fresh.push(channel),
+                Err(e) => eprintln!(
+                    "Warning: Push notifications unavailable for {}: {}",
+                    calendar_id, e
+                ),
+            }
+        }
+
+        let earliest = fresh
+            .iter()
+            .filter_map(|channel| channel.expiration.as_deref()?.parse::<u64>().ok())
+            .min();
+        let old = std::mem::replace(&mut *self.channels.lock().await, fresh);
+        for channel in &old {
+            let _ = self.client.stop_channel(channel).await;
+        }
+
+        let remaining = earliest
+            .map(|expires| Duration::from_millis(expires.saturating_sub(unix_millis())))
+            .unwrap_or(Duration::from_secs(CHANNEL_TTL_SECONDS));
+        remaining
+            .saturating_sub(RENEW_MARGIN)
+            .max(Duration::from_secs(60))
+    }
+
+    /// Tells Google to stop sending notifications, e.g. on shutdown.
+    pub async fn stop(&self) {
+        for channel in self.channels.lock().await.drain(..) {
+            if let Err(e) = self.client.stop_channel(&channel).await {
+                eprintln!("Warning: Failed to stop push channel {}: {}", channel.id, e);
+            }
+        }
+    }
+
+    /// Receives Google's notifications on `listen` and wakes `changed` for
+    /// each one reporting a change, renewing channels before they expire.
+    pub async fn run(self: Arc<Self>, listen: SocketAddr, changed: Arc<Notify>) -> Result<()> {
+        let listener = TcpListener::bind(listen)
+            .await
+            .with_context(|| format!("Failed to listen for push notifications on {}", listen))?;
+
+        let push = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let renew_in = push.renew().await;
+                tokio::time::sleep(renew_in).await;
+            }
+        });
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let token = self.token.clone();
+            let changed = changed.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let verdict = classify(&request, &token);
+                    if verdict == Some(true) {
+                        changed.notify_one();
+                    }
+                    async move { respond(verdict.is_some()) }
+                });
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    }
+}
+
+/// `None` for requests not from our channels, otherwise whether it reports a
+/// change; the initial `sync` message only confirms the channel.
+fn classify(request: &Request<Incoming>, token: &str) -> Option<bool> {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    };
+
+    if header("x-goog-channel-token") != token {
+        return None;
+    }
+    Some(header("x-goog-resource-state") != "sync")
+}
+
+fn respond(accepted: bool) -> hyper::Result<Response<Full<Bytes>>> {
+    let status = if accepted {
+        StatusCode::OK
+    } else {
+        StatusCode::FORBIDDEN
+    };
+    let mut response = Response::new(Full::new(Bytes::new()));
+    *response.status_mut() = status;
+    Ok(response)
+}
+
+/// Echoed back by Google with each notification, so stray requests to the
+/// public address can't trigger refreshes. 128 random bits, so it can't be
+/// guessed either.
+fn channel_token() -> std::io::Result<String> {
+    let mut bytes = [0u8; 16];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,73 @@
+use crate::config::{CacheStrategy, Config};
+use crate::daemon;
+use std::os::unix::net::UnixStream;
+
+/// Google's default Calendar API limit per user, in requests a minute.
+const PER_USER_PER_MINUTE: u64 = 600;
+/// TTLs below this are where most rate limiting comes from.
+const SUGGESTED_TTL_SECONDS: u64 = 300;
+
+/// How often `config` makes callux ask Google for events, and the settings
+/// that would make it ask less, for when Google refuses with `message`.
+pub fn advice(config: &Config, message: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    if message.contains("daily quota") {
+        lines.push(
+            "The daily quota is shared by everyone using the same OAuth client. Create your own \
+             in the Google Cloud console for credentials.json, or wait for the reset at midnight \
+             Pacific time."
+                .to_string(),
+        );
+    }
+
+    let calendars = config.calendars.iter().filter(|c| c.enabled).count() as u64;
+    let daemon_running =
+        daemon::socket_path(&config.daemon).is_some_and(|path| UnixStream::connect(path).is_ok());
+    let interval = if daemon_running {
+        config
+            .daemon
+            .refresh_interval_seconds
+            .unwrap_or(config.cache.ttl_seconds * 4 / 5)
+    } else {
+        config.cache.ttl_seconds
+    }
+    .max(1);
+    let per_minute = (calendars * 60).div_ceil(interval);
+    let who = if daemon_running {
+        "by the daemon"
+    } else {
+        "by every bar or script running callux"
+    };
+    lines.push(format!(
+        "Polling {} calendar(s) every {}s is about {} request(s) a minute {}, plus one per extra \
+         page and retry; Google allows {} a minute per user by default.",
+        calendars, interval, per_minute, who, PER_USER_PER_MINUTE
+    ));
+
+    if config.cache.ttl_seconds < SUGGESTED_TTL_SECONDS {
+        lines.push(format!(
+            "Fetch less often: callux config set cache.ttl_seconds {}",
+            SUGGESTED_TTL_SECONDS
+        ));
+    }
+    if !daemon_running {
+        lines.push(
+            "Share one refresh loop between all bars and commands: callux service install --user"
+                .to_string(),
+        );
+    }
+    if !config.cache.persistent {
+        lines.push(
+            "Let separate processes reuse fetched events: callux config set cache.persistent true"
+                .to_string(),
+        );
+    }
+    if config.cache.strategy == CacheStrategy::Strict {
+        lines.push(
+            "Serve cached events while refreshing in the background: \
+             callux config set cache.strategy swr"
+                .to_string(),
+        );
+    }
+    lines
+}
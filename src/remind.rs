@@ -0,0 +1,73 @@
+use crate::hooks;
+use crate::output::CalendarEvent;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// The clock is checked at least this often while waiting. Tokio's timers
+/// don't count time spent suspended, so one long sleep would fire late after
+/// a laptop wakes up.
+const CHECK_EVERY: Duration = Duration::from_secs(30);
+
+/// Returns once the wall clock reaches `at`.
+pub async fn wait_until(at: DateTime<Utc>) {
+    loop {
+        let Ok(left) = (at - Utc::now()).to_std() else {
+            return;
+        };
+        if left.is_zero() {
+            return;
+        }
+        tokio::time::sleep(left.min(CHECK_EVERY)).await;
+    }
+}
+
+/// Runs `command` for `event` (with the same `CALLUX_EVENT_*` variables as
+/// hooks) or, without one, shows a desktop notification.
+pub async fn fire(event: &CalendarEvent, command: Option<&str>) -> anyhow::Result<()> {
+    let Some(command) = command else {
+        let minutes = (event.start_time.with_timezone(&Utc) - Utc::now())
+            .num_minutes()
+            .max(0);
+        let body = format!(
+            "{} in {} min · {}",
+            event.start_time.format("%H:%M"),
+            minutes,
+            event.calendar_name
+        );
+        return notify(event, &body);
+    };
+
+    let status = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("CALLUX_HOOK", "remind")
+        .envs(hooks::event_env(event))
+        .status()
+        .await
+        .with_context(|| format!("Failed to run '{}'", command))?;
+    if !status.success() {
+        anyhow::bail!("'{}' exited with {}", command, status);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "notifications")]
+fn notify(event: &CalendarEvent, body: &str) -> anyhow::Result<()> {
+    crate::notify::remind(event, body).context("Failed to show notification")
+}
+
+/// Without the `notifications` feature, `notify-send` (libnotify) shows it.
+#[cfg(not(feature = "notifications"))]
+fn notify(event: &CalendarEvent, body: &str) -> anyhow::Result<()> {
+    let status = std::process::Command::new("notify-send")
+        .args(["--app-name", "callux"])
+        .arg(&event.title)
+        .arg(body)
+        .status()
+        .context("Failed to run notify-send; pass --command instead")?;
+    if !status.success() {
+        anyhow::bail!("notify-send exited with {}", status);
+    }
+    Ok(())
+}
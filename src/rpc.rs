@@ -0,0 +1,323 @@
+use crate::calendar::{CalendarClient, Staleness};
+use crate::cli::MAX_DAYS;
+use crate::daemon::Ranges;
+use crate::output::CalendarEvent;
+use chrono::Utc;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::sync::{Notify, mpsc, watch};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Fetching failed (Google unreachable and nothing cached, auth, ...).
+pub const FETCH_FAILED: i64 = -32000;
+/// `get_agenda` was sent a fingerprint of a different configuration.
+pub const CONFIG_MISMATCH: i64 = -32001;
+
+/// Look-ahead used when `get_agenda` or `search` omit `days`.
+const DEFAULT_DAYS: i64 = 7;
+const DEFAULT_SEARCH_DAYS: i64 = 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// Absent for notifications, which get no response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+}
+
+impl Request {
+    pub fn new(id: u64, method: &str, params: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id: Some(Value::from(id)),
+            method: method.to_string(),
+            params,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Result of `get_agenda`, and `params` of `agenda_changed` notifications.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AgendaResult {
+    pub events: Vec<CalendarEvent>,
+    #[serde(default)]
+    pub stale: Option<Staleness>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetAgendaParams {
+    #[serde(default = "default_days")]
+    days: i64,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    offline: bool,
+    /// `callux agenda` sends its `cache_fingerprint()` so a daemon running
+    /// another configuration refuses instead of answering wrongly.
+    #[serde(default)]
+    fingerprint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchParams {
+    query: String,
+    #[serde(default = "default_search_days")]
+    days: i64,
+}
+
+fn check_days(days: i64) -> Result<(), RpcError> {
+    if (1..=MAX_DAYS).contains(&days) {
+        return Ok(());
+    }
+    Err(RpcError::new(
+        INVALID_PARAMS,
+        format!("days must be between 1 and {}", MAX_DAYS),
+    ))
+}
+
+fn default_days() -> i64 {
+    DEFAULT_DAYS
+}
+
+fn default_search_days() -> i64 {
+    DEFAULT_SEARCH_DAYS
+}
+
+/// Answers JSON-RPC 2.0 requests, one JSON object per line, on daemon
+/// socket connections.
+pub struct Server {
+    pub client: Arc<CalendarClient>,
+    pub fingerprint: String,
+    /// Ranges the daemon keeps refreshed, including those asked for here.
+    pub ranges: Arc<Mutex<Ranges>>,
+    pub refresh: Arc<Notify>,
+    pub published: watch::Receiver<Vec<CalendarEvent>>,
+}
+
+impl Server {
+    pub async fn serve(&self, stream: UnixStream) -> std::io::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let (outgoing, mut queue) = mpsc::unbounded_channel::<String>();
+        let writer_task = tokio::spawn(async move {
+            while let Some(mut line) = queue.recv().await {
+                line.push('\n');
+                if writer.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut subscriptions = Vec::new();
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) if request.jsonrpc != "2.0" => Some(error_response(
+                    request.id.unwrap_or(Value::Null),
+                    RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""),
+                )),
+                Ok(request) => {
+                    if request.method == "subscribe" {
+                        subscriptions.push(self.subscribe(outgoing.clone()));
+                    }
+                    let id = request.id.clone();
+                    let result = self.dispatch(&request.method, request.params).await;
+                    id.map(|id| match result {
+                        Ok(result) => Response {
+                            jsonrpc: "2.0".to_string(),
+                            id,
+                            result: Some(result),
+                            error: None,
+                        },
+                        Err(error) => error_response(id, error),
+                    })
+                }
+                Err(e) => Some(error_response(
+                    Value::Null,
+                    RpcError::new(PARSE_ERROR, e.to_string()),
+                )),
+            };
+
+            if let Some(response) = response {
+                let _ = outgoing.send(serde_json::to_string(&response)?);
+            }
+        }
+
+        for subscription in subscriptions {
+            subscription.abort();
+        }
+        drop(outgoing);
+        let _ = writer_task.await;
+        Ok(())
+    }
+
+    async fn dispatch(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "get_agenda" => {
+                let params: GetAgendaParams = parse_params(params)?;
+                check_days(params.days)?;
+                if params
+                    .fingerprint
+                    .as_ref()
+                    .is_some_and(|f| *f != self.fingerprint)
+                {
+                    return Err(RpcError::new(
+                        CONFIG_MISMATCH,
+                        "Daemon runs with a different configuration",
+                    ));
+                }
+                if let Ok(mut ranges) = self.ranges.lock() {
+                    ranges.ask(params.days);
+                }
+
+                let agenda = self
+                    .client
+                    .get_events(params.days, params.limit, params.offline)
+                    .await
+                    .map_err(|e| RpcError::new(FETCH_FAILED, e.to_string()))?;
+                to_value(&AgendaResult {
+                    events: agenda.events,
+                    stale: agenda.stale,
+                    warnings: agenda.warnings,
+                })
+            }
+            "get_next" => {
+                let now = Utc::now();
+                let agenda = self
+                    .client
+                    .get_events(DEFAULT_DAYS, None, false)
+                    .await
+                    .map_err(|e| RpcError::new(FETCH_FAILED, e.to_string()))?;
+                let next = agenda
+                    .events
+                    .into_iter()
+                    .find(|e| e.end_time.with_timezone(&Utc) > now);
+                to_value(&next)
+            }
+            "search" => {
+                let params: SearchParams = parse_params(params)?;
+                check_days(params.days)?;
+                let query = params.query.to_lowercase();
+                let agenda = self
+                    .client
+                    .get_events(params.days, None, false)
+                    .await
+                    .map_err(|e| RpcError::new(FETCH_FAILED, e.to_string()))?;
+                let matches: Vec<CalendarEvent> = agenda
+                    .events
+                    .into_iter()
+                    .filter(|e| {
+                        e.title.to_lowercase().contains(&query)
+                            || e.calendar_name.to_lowercase().contains(&query)
+                            || e.description
+                                .as_deref()
+                                .is_some_and(|d| d.to_lowercase().contains(&query))
+                    })
+                    .collect();
+                to_value(&matches)
+            }
+            "refresh" => {
+                self.refresh.notify_one();
+                Ok(Value::Bool(true))
+            }
+            "subscribe" => Ok(Value::Bool(true)),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method '{}'", method),
+            )),
+        }
+    }
+
+    /// Sends an `agenda_changed` notification on this connection after every
+    /// refresh that changes the published agenda.
+    fn subscribe(&self, outgoing: mpsc::UnboundedSender<String>) -> tokio::task::JoinHandle<()> {
+        let mut published = self.published.clone();
+        published.mark_unchanged();
+        tokio::spawn(async move {
+            while published.changed().await.is_ok() {
+                let events = published.borrow_and_update().clone();
+                let notification = Request {
+                    jsonrpc: "2.0".to_string(),
+                    id: None,
+                    method: "agenda_changed".to_string(),
+                    params: serde_json::to_value(AgendaResult {
+                        events,
+                        stale: None,
+                        warnings: Vec::new(),
+                    })
+                    .unwrap_or_default(),
+                };
+                let Ok(line) = serde_json::to_string(&notification) else {
+                    continue;
+                };
+                if outgoing.send(line).is_err() {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    // Omitted params mean "all defaults".
+    let params = if params.is_null() {
+        Value::Object(Default::default())
+    } else {
+        params
+    };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(FETCH_FAILED, e.to_string()))
+}
+
+fn error_response(id: Value, error: RpcError) -> Response {
+    Response {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result: None,
+        error: Some(error),
+    }
+}
@@ -0,0 +1,25 @@
+use crate::cli::SchemaKind;
+use crate::output::{ErrorOutput, RawEvent, WaybarOutput};
+use crate::rpc::AgendaResult;
+use schemars::generate::SchemaSettings;
+use schemars::{JsonSchema, Schema};
+
+/// The JSON Schema of one of callux's structured outputs. It's derived from
+/// the types that produce the output, so the two can't drift apart.
+pub fn document(kind: SchemaKind) -> Schema {
+    match kind {
+        SchemaKind::Events => schema_for::<AgendaResult>(),
+        SchemaKind::Raw => schema_for::<Vec<RawEvent>>(),
+        SchemaKind::Waybar => schema_for::<WaybarOutput>(),
+        SchemaKind::Error => schema_for::<ErrorOutput>(),
+    }
+}
+
+/// Describes what callux writes rather than what it would accept, so fields
+/// skipped when empty aren't listed as required.
+fn schema_for<T: JsonSchema>() -> Schema {
+    SchemaSettings::draft2020_12()
+        .for_serialize()
+        .into_generator()
+        .into_root_schema_for::<T>()
+}
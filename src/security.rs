@@ -0,0 +1,76 @@
+use crate::error::{CalendarError, Result};
+use std::path::Path;
+
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+/// Creates `path` (and any missing parents) readable only by the owner.
+pub fn create_private_dir(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(path)
+    }
+
+    #[cfg(not(unix))]
+    {
+        std::fs::create_dir_all(path)
+    }
+}
+
+/// Restricts an existing file to owner read/write (0600).
+pub fn restrict_permissions(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).map_err(|e| {
+            CalendarError::io(
+                format!("Failed to set permissions on {}", path.display()),
+                e,
+            )
+        })?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
+}
+
+/// Refuses to use a secret file that group or others can read, unless the
+/// user explicitly allowed it, in which case only a warning is printed.
+pub fn check_private_file(path: &Path, allow_insecure: bool) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return Ok(());
+        };
+
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 == 0 {
+            return Ok(());
+        }
+
+        let message = format!(
+            "{} is accessible by other users (mode {:o}); run `chmod 600 {}`",
+            path.display(),
+            mode,
+            path.display()
+        );
+
+        if allow_insecure {
+            eprintln!("Warning: {}", message);
+        } else {
+            return Err(CalendarError::config(format!(
+                "{} or pass --insecure-ok",
+                message
+            )));
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = (path, allow_insecure);
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use anyhow::{Context, Result};
+use google_calendar3::hyper_rustls::HttpsConnectorBuilder;
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::{Request, Uri, header};
+use hyper_util::client::legacy::Client;
+use minisign_verify::{PublicKey, Signature};
+use serde::Deserialize;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/fiscalshrike/callux/releases/latest";
+const USER_AGENT: &str = concat!("callux/", env!("CARGO_PKG_VERSION"));
+/// The minisign key releases are signed with, set when building release
+/// binaries. Without it there is nothing to check a download against, so
+/// self-update refuses to install.
+const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("CALLUX_RELEASE_PUBLIC_KEY");
+/// Each binary's detached signature is published next to it with this
+/// suffix, as `minisign -S` names it.
+const SIGNATURE_SUFFIX: &str = ".minisig";
+/// Release downloads go through a redirect or two to GitHub's CDN.
+const MAX_REDIRECTS: usize = 5;
+
+/// The newest published release with a binary for this platform.
+pub struct Release {
+    /// Without the leading "v", e.g. "0.4.0".
+    pub version: String,
+    binary_url: String,
+    signature_url: String,
+}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    assets: Vec<GithubAsset>,
+}
+
+#[derive(Deserialize)]
+struct GithubAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// The prebuilt binary for this machine, e.g. `callux-x86_64-linux`.
+fn asset_name() -> String {
+    format!("callux-{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+pub async fn latest() -> Result<Release> {
+    let body = download(LATEST_RELEASE_URL)
+        .await
+        .context("Failed to check GitHub for releases")?;
+    let release: GithubRelease =
+        serde_json::from_slice(&body).context("Unexpected response from GitHub")?;
+
+    let name = asset_name();
+    let url_of = |wanted: &str| {
+        release
+            .assets
+            .iter()
+            .find(|asset| asset.name == wanted)
+            .map(|asset| asset.browser_download_url.clone())
+    };
+    let binary_url = url_of(&name)
+        .ok_or_else(|| anyhow::anyhow!("Release {} has no {} binary", release.tag_name, name))?;
+    let signature = format!("{}{}", name, SIGNATURE_SUFFIX);
+    let signature_url = url_of(&signature)
+        .ok_or_else(|| anyhow::anyhow!("Release {} has no {}", release.tag_name, signature))?;
+
+    Ok(Release {
+        version: release.tag_name.trim_start_matches('v').to_string(),
+        binary_url,
+        signature_url,
+    })
+}
+
+/// Whether `version` comes after the running binary's. Pre-release suffixes
+/// such as "-rc.1" are ignored.
+pub fn is_newer(version: &str) -> bool {
+    let parse = |version: &str| -> Vec<u64> {
+        let release = version.split(['-', '+']).next().unwrap_or_default();
+        release
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    parse(version) > parse(env!("CARGO_PKG_VERSION"))
+}
+
+/// Downloads `release`, checks its signature against the release key and
+/// swaps it in for the running binary. Returns where it was installed.
+pub async fn install(release: &Release) -> Result<PathBuf> {
+    let key = RELEASE_PUBLIC_KEY.ok_or_else(|| {
+        anyhow::anyhow!("This build has no release signing key; download releases by hand")
+    })?;
+    let key = PublicKey::from_base64(key.trim()).context("Invalid release signing key")?;
+    let target = std::env::current_exe()
+        .and_then(|path| path.canonicalize())
+        .context("Failed to find the running binary")?;
+
+    let signature = download(&release.signature_url)
+        .await
+        .context("Failed to download the signature")?;
+    let signature = Signature::decode(&String::from_utf8_lossy(&signature))
+        .context("Invalid release signature")?;
+
+    let binary = download(&release.binary_url)
+        .await
+        .context("Failed to download the new binary")?;
+    if let Err(e) = key.verify(&binary, &signature, false) {
+        anyhow::bail!(
+            "Signature check failed for {} ({}); nothing was changed",
+            asset_name(),
+            e
+        );
+    }
+
+    // Written next to the binary so the rename is atomic; a running process
+    // keeps the old file until it exits.
+    let staged = target.with_extension("update");
+    let replace = || -> std::io::Result<()> {
+        std::fs::write(&staged, &binary)?;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+        std::fs::rename(&staged, &target)
+    };
+    if let Err(e) = replace() {
+        let _ = std::fs::remove_file(&staged);
+        return Err(e).with_context(|| {
+            format!(
+                "Failed to replace {} (if a package manager installed it, update through that)",
+                target.display()
+            )
+        });
+    }
+    Ok(target)
+}
+
+/// Where a `Location` header points, which may be relative to `base`.
+fn resolve_location(base: &str, location: &str) -> Result<String> {
+    if location.parse::<Uri>()?.scheme().is_some() {
+        return Ok(location.to_string());
+    }
+
+    let base: Uri = base.parse()?;
+    let scheme = base.scheme_str().unwrap_or("https");
+    let authority = base
+        .authority()
+        .ok_or_else(|| anyhow::anyhow!("{} has no host", base))?;
+    if let Some(rest) = location.strip_prefix("//") {
+        return Ok(format!("{}://{}", scheme, rest));
+    }
+    if location.starts_with('/') {
+        return Ok(format!("{}://{}{}", scheme, authority, location));
+    }
+    // Relative to the directory of the current path.
+    let dir = base.path().rsplit_once('/').map_or("", |(dir, _)| dir);
+    Ok(format!("{}://{}{}/{}", scheme, authority, dir, location))
+}
+
+async fn download(url: &str) -> Result<Bytes> {
+    let https = HttpsConnectorBuilder::new()
+        .with_native_roots()?
+        .https_only()
+        .enable_http1()
+        .build();
+    let client: Client<_, Empty<Bytes>> =
+        Client::builder(hyper_util::rt::TokioExecutor::new()).build(https);
+
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let request = Request::get(&url)
+            .header(header::USER_AGENT, USER_AGENT)
+            .header(header::ACCEPT, "application/vnd.github+json, */*")
+            .body(Empty::new())?;
+        let response = client.request(request).await?;
+
+        let status = response.status();
+        if status.is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or_else(|| anyhow::anyhow!("{} redirected nowhere", url))?;
+            url = resolve_location(&url, location)?;
+            continue;
+        }
+        if !status.is_success() {
+            anyhow::bail!("{} returned {}", url, status);
+        }
+        return Ok(response.into_body().collect().await?.to_bytes());
+    }
+    anyhow::bail!("Too many redirects fetching {}", url)
+}
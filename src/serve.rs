@@ -0,0 +1,180 @@
+use crate::calendar::CalendarClient;
+use crate::cli::MAX_DAYS;
+use crate::config::Config;
+use crate::metrics;
+use crate::rpc::AgendaResult;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode, header};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+const DEFAULT_DAYS: i64 = 7;
+
+struct State {
+    client: CalendarClient,
+    config: Config,
+    token: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CalendarSummary<'a> {
+    id: &'a str,
+    name: &'a str,
+    color: &'a str,
+    enabled: bool,
+}
+
+/// Serves read-only JSON at `/agenda`, `/next` and `/calendars`, and
+/// Prometheus metrics at `/metrics`, until the process is stopped. With
+/// `token`, requests need `Authorization: Bearer`.
+pub async fn run(config: Config, listen: SocketAddr, token: Option<String>) -> Result<()> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Failed to listen on {}", listen))?;
+    println!("Serving on http://{}", listen);
+    if token.is_none() && !listen.ip().is_loopback() {
+        eprintln!("Warning: Serving on {} without a token", listen);
+    }
+
+    let state = Arc::new(State {
+        client: CalendarClient::new(config.clone()),
+        config,
+        token,
+    });
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let state = state.clone();
+                async move { Ok::<_, hyper::Error>(handle(&state, request).await) }
+            });
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+async fn handle(state: &State, request: Request<Incoming>) -> Response<Full<Bytes>> {
+    if request.method() != Method::GET {
+        return error(StatusCode::METHOD_NOT_ALLOWED, "Only GET is supported");
+    }
+    if let Some(token) = &state.token {
+        let presented = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if !presented.is_some_and(|p| constant_time_eq(p.as_bytes(), token.as_bytes())) {
+            return error(StatusCode::UNAUTHORIZED, "Missing or wrong bearer token");
+        }
+    }
+
+    let query = request.uri().query().unwrap_or_default();
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    };
+
+    match request.uri().path() {
+        "/agenda" => {
+            let days = match param("days").map(str::parse::<i64>) {
+                Some(Ok(days)) if (1..=MAX_DAYS).contains(&days) => days,
+                Some(_) => {
+                    return error(
+                        StatusCode::BAD_REQUEST,
+                        &format!("days must be between 1 and {}", MAX_DAYS),
+                    );
+                }
+                None => DEFAULT_DAYS,
+            };
+            let limit = match param("limit").map(str::parse::<usize>) {
+                Some(Ok(limit)) => Some(limit),
+                Some(Err(_)) => return error(StatusCode::BAD_REQUEST, "limit must be a number"),
+                None => None,
+            };
+
+            match state.client.get_events(days, limit, false).await {
+                Ok(agenda) => json(&AgendaResult {
+                    events: agenda.events,
+                    stale: agenda.stale,
+                    warnings: agenda.warnings,
+                }),
+                Err(e) => error(StatusCode::BAD_GATEWAY, &e.to_string()),
+            }
+        }
+        "/next" => match state.client.get_events(DEFAULT_DAYS, None, false).await {
+            Ok(agenda) => {
+                let now = Utc::now();
+                let next = agenda
+                    .events
+                    .into_iter()
+                    .find(|e| e.end_time.with_timezone(&Utc) > now);
+                json(&next)
+            }
+            Err(e) => error(StatusCode::BAD_GATEWAY, &e.to_string()),
+        },
+        "/metrics" => {
+            // Scrapes read the cache only so they never cost API quota.
+            let events = match state.client.get_events(DEFAULT_DAYS, None, true).await {
+                Ok(agenda) => agenda.events,
+                Err(_) => Vec::new(),
+            };
+            metrics::response(&events)
+        }
+        "/calendars" => {
+            let calendars: Vec<CalendarSummary> = state
+                .config
+                .calendars
+                .iter()
+                .map(|cal| CalendarSummary {
+                    id: &cal.id,
+                    name: &cal.name,
+                    color: &cal.color,
+                    enabled: cal.enabled,
+                })
+                .collect();
+            json(&calendars)
+        }
+        _ => error(StatusCode::NOT_FOUND, "Unknown endpoint"),
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> Response<Full<Bytes>> {
+    match serde_json::to_vec(value) {
+        Ok(body) => respond(StatusCode::OK, body),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    let body = serde_json::json!({ "error": message }).to_string();
+    respond(status, body.into_bytes())
+}
+
+fn respond(status: StatusCode, body: Vec<u8>) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
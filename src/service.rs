@@ -0,0 +1,120 @@
+use crate::config::Config;
+use crate::daemon;
+use anyhow::{Result, anyhow};
+use std::path::PathBuf;
+
+/// One generated unit file.
+pub struct Unit {
+    pub name: String,
+    pub content: String,
+}
+
+/// User units for `callux daemon` (socket-activated) and a timer running
+/// `callux prefetch`, for the current profile and config file. There are no
+/// system units: run from sudo they would pick root's config and socket.
+pub fn units(config: &Config) -> Result<Vec<Unit>> {
+    let exe = quote(&std::env::current_exe()?.display().to_string());
+    let socket = daemon::socket_path(&config.daemon)
+        .ok_or_else(|| anyhow!("No runtime directory for the daemon socket"))?;
+
+    let mut args = String::new();
+    if let Some(path) = Config::path_override() {
+        args.push_str(&format!(" --config {}", quote(&path.display().to_string())));
+    }
+    if let Some(profile) = Config::profile() {
+        args.push_str(&format!(" --profile {}", quote(&profile)));
+    }
+    if config.auth.allow_insecure_permissions {
+        args.push_str(" --insecure-ok");
+    }
+
+    let suffix = match Config::profile() {
+        Some(profile) => format!("-{}", profile),
+        None => String::new(),
+    };
+    let prefetch_interval = config.cache.ttl_seconds.max(60);
+
+    Ok(vec![
+        Unit {
+            name: format!("callux-daemon{}.socket", suffix),
+            content: format!(
+                "[Unit]\n\
+                 Description=callux agenda socket\n\n\
+                 [Socket]\n\
+                 ListenStream={}\n\
+                 SocketMode=0600\n\
+                 DirectoryMode=0700\n\n\
+                 [Install]\n\
+                 WantedBy=sockets.target\n",
+                socket.display().to_string().replace('%', "%%")
+            ),
+        },
+        Unit {
+            name: format!("callux-daemon{}.service", suffix),
+            content: format!(
+                "[Unit]\n\
+                 Description=callux calendar daemon\n\
+                 Requires=callux-daemon{suffix}.socket\n\
+                 After=network-online.target\n\n\
+                 [Service]\n\
+                 ExecStart={exe} daemon{args}\n\
+                 Restart=on-failure\n\n\
+                 [Install]\n\
+                 WantedBy=default.target\n",
+            ),
+        },
+        Unit {
+            name: format!("callux-prefetch{}.service", suffix),
+            content: format!(
+                "[Unit]\n\
+                 Description=Refresh the callux event cache\n\
+                 After=network-online.target\n\n\
+                 [Service]\n\
+                 Type=oneshot\n\
+                 ExecStart={exe} prefetch{args}\n",
+            ),
+        },
+        Unit {
+            name: format!("callux-prefetch{}.timer", suffix),
+            content: format!(
+                "[Unit]\n\
+                 Description=Refresh the callux event cache periodically\n\n\
+                 [Timer]\n\
+                 OnBootSec=1min\n\
+                 OnUnitActiveSec={}s\n\n\
+                 [Install]\n\
+                 WantedBy=timers.target\n",
+                prefetch_interval
+            ),
+        },
+    ])
+}
+
+/// `~/.config/systemd/user`.
+pub fn unit_dir() -> Result<PathBuf> {
+    dirs::config_dir()
+        .map(|dir| dir.join("systemd").join("user"))
+        .ok_or_else(|| anyhow!("Could not find config directory"))
+}
+
+pub fn install(units: &[Unit], dir: &std::path::Path) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for unit in units {
+        let path = dir.join(&unit.name);
+        std::fs::write(&path, &unit.content)
+            .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// `arg` as a single `ExecStart=` word, whatever spaces, quotes or `%`
+/// specifiers it contains.
+fn quote(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%");
+    format!("\"{}\"", escaped)
+}
@@ -0,0 +1,39 @@
+use std::time::Duration;
+use tokio::signal::unix::{SignalKind, signal};
+
+/// The conventional status for a process stopped by Ctrl-C.
+pub const EXIT_CODE: i32 = 130;
+
+/// How long a signalled command may take to wind down on its own, such as
+/// finishing a cache write or the daemon telling Google to stop pushing.
+const GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Resolves on the first SIGINT or SIGTERM after it's called.
+pub async fn signalled() {
+    let terminate = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        () = terminate => {}
+    }
+}
+
+/// Handling the signals stops them from killing the process, so this exits
+/// in its place when a command doesn't stop within `GRACE_PERIOD`, e.g. one
+/// blocked reading the terminal, or right away on a second signal.
+pub fn exit_when_stuck() {
+    tokio::spawn(async {
+        signalled().await;
+        tokio::select! {
+            () = tokio::time::sleep(GRACE_PERIOD) => {}
+            () = signalled() => {}
+        }
+        std::process::exit(EXIT_CODE);
+    });
+}
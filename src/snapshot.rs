@@ -0,0 +1,52 @@
+use crate::calendar::CalendarClient;
+use crate::config::{Config, expand_home};
+use crate::output::OutputFormatter;
+use anyhow::Context;
+use std::io::Write;
+use std::path::Path;
+
+/// Replaces `path` with `contents` in one step, so a widget reading it
+/// never sees a half-written file: the text goes to a temporary file in the
+/// same directory, which is then renamed over `path`.
+pub fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir)?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temporary = dir.join(format!(".{}.{}.tmp", name, std::process::id()));
+
+    let write = || -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&temporary)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temporary, path)
+    };
+    let result = write();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Writes `snapshot.path` from the agenda `client` has cached, in
+/// `snapshot.format`. Does nothing when no path is configured.
+pub async fn refresh(client: &CalendarClient, config: &Config) -> anyhow::Result<()> {
+    let Some(path) = &config.snapshot.path else {
+        return Ok(());
+    };
+    let path = expand_home(path);
+
+    let agenda = client.get_events(config.snapshot.days, None, false).await?;
+    let formatter = OutputFormatter::for_display(config.snapshot.format, &config.display, false)
+        .with_staleness(agenda.stale)
+        .with_warnings(agenda.warnings);
+    let text = format!("{}\n", formatter.format_events(&agenda.events));
+
+    write_atomically(Path::new(&path), &text)
+        .with_context(|| format!("Failed to write snapshot {}", path))
+}
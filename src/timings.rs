@@ -0,0 +1,71 @@
+use colored::*;
+use std::future::Future;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SPANS: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+
+/// Turns on collection for `--timings`; recording is a no-op otherwise.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn record(label: &'static str, elapsed: Duration) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(mut spans) = SPANS.lock() {
+        spans.push((label, elapsed));
+    }
+}
+
+pub fn time<T>(label: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(label, start.elapsed());
+    result
+}
+
+pub async fn time_async<T>(label: &'static str, fut: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let result = fut.await;
+    record(label, start.elapsed());
+    result
+}
+
+/// Prints per-phase totals to stderr, keeping stdout clean for Waybar.
+pub fn report(total: Duration) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let Ok(spans) = SPANS.lock() else {
+        return;
+    };
+
+    let mut totals: Vec<(&'static str, Duration, usize)> = Vec::new();
+    for (label, elapsed) in spans.iter() {
+        match totals.iter_mut().find(|(l, _, _)| l == label) {
+            Some(entry) => {
+                entry.1 += *elapsed;
+                entry.2 += 1;
+            }
+            None => totals.push((label, *elapsed, 1)),
+        }
+    }
+
+    eprintln!("{}", "Timings:".bright_blue().bold());
+    for (label, elapsed, count) in totals {
+        if count > 1 {
+            eprintln!("  {:<14} {:>8.2}ms ({} calls)", label, ms(elapsed), count);
+        } else {
+            eprintln!("  {:<14} {:>8.2}ms", label, ms(elapsed));
+        }
+    }
+    eprintln!("  {:<14} {:>8.2}ms", "total", ms(total));
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
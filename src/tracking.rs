@@ -0,0 +1,176 @@
+use crate::config::{TrackingBackend, TrackingConfig};
+use crate::meeting;
+use crate::output::CalendarEvent;
+use chrono::{DateTime, Utc};
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Method, Request, header};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use serde_json::json;
+use tokio::sync::watch;
+
+/// How often to look for a meeting starting or ending, so entries begin
+/// and stop on time even when no refresh changes the agenda.
+const TICK: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Starts a time-tracking entry named after each meeting as it begins and
+/// stops it when the meeting ends (or is cancelled), until the daemon exits.
+pub async fn run(config: TrackingConfig, mut agenda: watch::Receiver<Vec<CalendarEvent>>) {
+    let Some(backend) = config.backend else {
+        return;
+    };
+    let tracker = Tracker::new(backend, &config);
+    // Event being tracked and when tracking started.
+    let mut active: Option<(CalendarEvent, DateTime<Utc>)> = None;
+    let mut ticker = tokio::time::interval(TICK);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            changed = agenda.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+            }
+        }
+
+        let current = meeting::current(&agenda.borrow(), &config.calendars, false).cloned();
+        let same = match (&active, &current) {
+            (Some((tracked, _)), Some(current)) => {
+                tracked.id == current.id && tracked.start_time == current.start_time
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        if same {
+            continue;
+        }
+
+        if let Some((event, since)) = active.take() {
+            tracker.stop(&event, since).await;
+        }
+        if let Some(event) = current {
+            tracker.start(&event).await;
+            active = Some((event, Utc::now()));
+        }
+    }
+
+    if let Some((event, since)) = active {
+        tracker.stop(&event, since).await;
+    }
+}
+
+struct Tracker {
+    backend: TrackingBackend,
+    tags: Vec<String>,
+    activitywatch_url: String,
+    hostname: String,
+    bucket: String,
+    http: Client<HttpConnector, Full<Bytes>>,
+}
+
+impl Tracker {
+    fn new(backend: TrackingBackend, config: &TrackingConfig) -> Self {
+        let hostname = std::fs::read_to_string("/etc/hostname")
+            .map(|h| h.trim().to_string())
+            .unwrap_or_else(|_| "unknown".to_string());
+        Self {
+            backend,
+            tags: config.tags.clone(),
+            activitywatch_url: config.activitywatch_url.trim_end_matches('/').to_string(),
+            bucket: format!("callux-meetings_{}", hostname),
+            hostname,
+            http: Client::builder(TokioExecutor::new()).build_http(),
+        }
+    }
+
+    async fn start(&self, event: &CalendarEvent) {
+        match self.backend {
+            TrackingBackend::Timewarrior => {
+                let mut args = vec!["start".to_string(), event.title.clone()];
+                args.extend(self.tags.iter().cloned());
+                timew(&args).await;
+            }
+            // ActivityWatch stores finished intervals; everything happens on
+            // stop, but make sure the bucket exists early.
+            TrackingBackend::Activitywatch => {
+                let body = json!({
+                    "client": "callux",
+                    "type": "app.calendar.meeting",
+                    "hostname": self.hostname,
+                });
+                self.post(&format!("/api/0/buckets/{}", self.bucket), body)
+                    .await;
+            }
+        }
+    }
+
+    async fn stop(&self, event: &CalendarEvent, since: DateTime<Utc>) {
+        match self.backend {
+            // Only the meeting's own interval, not whatever the user started
+            // by hand since.
+            TrackingBackend::Timewarrior => {
+                timew(&["stop".to_string(), event.title.clone()]).await;
+            }
+            TrackingBackend::Activitywatch => {
+                let duration = (Utc::now() - since).num_milliseconds() as f64 / 1000.0;
+                let body = json!([{
+                    "timestamp": since.to_rfc3339(),
+                    "duration": duration,
+                    "data": {
+                        "title": event.title,
+                        "calendar": event.calendar_name,
+                        "tags": self.tags,
+                    },
+                }]);
+                self.post(&format!("/api/0/buckets/{}/events", self.bucket), body)
+                    .await;
+            }
+        }
+    }
+
+    async fn post(&self, path: &str, body: serde_json::Value) {
+        let url = format!("{}{}", self.activitywatch_url, path);
+        let request = match Request::builder()
+            .method(Method::POST)
+            .uri(&url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body.to_string())))
+        {
+            Ok(request) => request,
+            Err(e) => {
+                eprintln!("Warning: Invalid ActivityWatch URL {}: {}", url, e);
+                return;
+            }
+        };
+
+        match self.http.request(request).await {
+            Ok(response) => {
+                let status = response.status();
+                // 304 is ActivityWatch saying the bucket already exists.
+                if !status.is_success() && status.as_u16() != 304 {
+                    eprintln!("Warning: ActivityWatch returned {} for {}", status, url);
+                }
+            }
+            Err(e) => eprintln!("Warning: Failed to reach ActivityWatch at {}: {}", url, e),
+        }
+    }
+}
+
+async fn timew(args: &[String]) {
+    let status = tokio::process::Command::new("timew")
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .status()
+        .await;
+    match status {
+        Ok(status) if !status.success() => {
+            eprintln!("Warning: timew {} exited with {}", args[0], status)
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Warning: Failed to run timew: {}", e),
+    }
+}
@@ -0,0 +1,56 @@
+use crate::output::{CalendarEvent, takes_time};
+use chrono::{DateTime, Utc};
+
+/// Points for an event starting right now, falling to none a day ahead.
+const PROXIMITY: f64 = 50.0;
+const PROXIMITY_WINDOW_MINUTES: f64 = 24.0 * 60.0;
+/// Per guest, up to `MAX_GUESTS` of them.
+const PER_GUEST: u32 = 3;
+const MAX_GUESTS: u32 = 5;
+/// For meetings you organized, which can't go ahead without you.
+const ORGANIZER: u32 = 15;
+/// For overlapping another event that takes time.
+const CONFLICT: u32 = 20;
+
+/// How pressing `event` is at `now`, from 0 to 100: mostly how soon it
+/// starts, raised by its guests, by being the organizer and by clashing
+/// with another of `events`. Events that ended or don't take time score 0.
+pub fn score(event: &CalendarEvent, events: &[&CalendarEvent], now: DateTime<Utc>) -> u8 {
+    if event.end_time <= now
+        || event.cancelled
+        || event.free
+        || event.response.as_deref() == Some("declined")
+    {
+        return 0;
+    }
+
+    let minutes = (event.start_time.with_timezone(&Utc) - now)
+        .num_minutes()
+        .max(0) as f64;
+    let mut proximity = PROXIMITY * (1.0 - minutes / PROXIMITY_WINDOW_MINUTES).max(0.0);
+    // An all-day event doesn't need you at its start the way a meeting does.
+    if event.all_day {
+        proximity /= 2.0;
+    }
+
+    let guests = (event.guests as u32).min(MAX_GUESTS) * PER_GUEST;
+    let organizer = if event.is_organizer && event.guests > 0 {
+        ORGANIZER
+    } else {
+        0
+    };
+    let conflict = if takes_time(event)
+        && events.iter().any(|other| {
+            !std::ptr::eq(*other, event)
+                && takes_time(other)
+                && other.start_time < event.end_time
+                && event.start_time < other.end_time
+        }) {
+        CONFLICT
+    } else {
+        0
+    };
+
+    let total = proximity.round() as u32 + guests + organizer + conflict;
+    total.min(100) as u8
+}
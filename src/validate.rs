@@ -0,0 +1,320 @@
+use crate::calendar::CalendarClient;
+use crate::classes;
+use crate::config::Config;
+use crate::filter;
+use crate::hours::WorkingHours;
+use crate::when;
+use crate::zone::Zone;
+use chrono::format::{Item, StrftimeItems};
+use colored::*;
+use std::path::Path;
+
+/// Largest page size the Calendar API accepts for `maxResults`.
+const MAX_RESULTS: u32 = 2500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub key: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+
+    fn warning(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Checks the config file text, with the drop-ins and profile `load` merges
+/// into it, beyond what deserialization catches. Returns the parsed config
+/// (if it parsed at all) alongside every finding.
+pub fn validate_text(text: &str) -> (Option<Config>, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+
+    if let Err(e) = toml::from_str::<toml::Value>(text) {
+        diagnostics.push(Diagnostic::error("", format!("Invalid TOML: {}", e)));
+        return (None, diagnostics);
+    }
+    let raw = match Config::merge_files(text) {
+        Ok(raw) => raw,
+        Err(e) => {
+            diagnostics.push(Diagnostic::error("", format!("{:#}", e)));
+            return (None, diagnostics);
+        }
+    };
+
+    let config: Config = match raw.clone().try_into() {
+        Ok(config) => config,
+        Err(e) => {
+            diagnostics.push(Diagnostic::error("", e.to_string().trim_end()));
+            return (None, diagnostics);
+        }
+    };
+
+    if let Ok(known) = toml::Value::try_from(&config) {
+        unknown_keys(&raw, &known, "", &mut diagnostics);
+    }
+    check_values(&config, &mut diagnostics);
+
+    (Some(config), diagnostics)
+}
+
+/// Confirms every enabled calendar is visible to the authenticated account.
+pub async fn check_online(config: &Config, diagnostics: &mut Vec<Diagnostic>) {
+    let client = CalendarClient::new(config.clone());
+    let calendars = match client.list_calendars().await {
+        Ok(calendars) => calendars,
+        Err(e) => {
+            diagnostics.push(Diagnostic::error(
+                "auth",
+                format!("API check failed: {}", e),
+            ));
+            return;
+        }
+    };
+
+    for (i, calendar) in config.calendars.iter().enumerate() {
+        let found = calendar.id == "primary"
+            || calendars
+                .iter()
+                .any(|c| c.id.as_deref() == Some(calendar.id.as_str()));
+        if !found {
+            diagnostics.push(Diagnostic::error(
+                format!("calendars.{}.id", i),
+                format!(
+                    "Calendar '{}' is not accessible by this account",
+                    calendar.id
+                ),
+            ));
+        }
+    }
+}
+
+pub fn print(diagnostics: &[Diagnostic]) {
+    if diagnostics.is_empty() {
+        println!("{}", "Configuration is valid.".bright_green().bold());
+        return;
+    }
+
+    for diagnostic in diagnostics {
+        let label = match diagnostic.severity {
+            Severity::Error => "error".red().bold(),
+            Severity::Warning => "warning".bright_yellow().bold(),
+        };
+        if diagnostic.key.is_empty() {
+            println!("{}: {}", label, diagnostic.message);
+        } else {
+            println!(
+                "{}: {}: {}",
+                label,
+                diagnostic.key.bright_blue(),
+                diagnostic.message
+            );
+        }
+    }
+}
+
+/// Keys serde silently ignores: present in the file but gone after a round
+/// trip through `Config`.
+fn unknown_keys(raw: &toml::Value, known: &toml::Value, prefix: &str, out: &mut Vec<Diagnostic>) {
+    match (raw, known) {
+        (toml::Value::Table(raw), toml::Value::Table(known)) => {
+            for (key, value) in raw {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                match known.get(key) {
+                    Some(known_value) => unknown_keys(value, known_value, &path, out),
+                    None => out.push(Diagnostic::warning(path, "Unknown key (ignored)")),
+                }
+            }
+        }
+        (toml::Value::Array(raw), toml::Value::Array(known)) => {
+            for (i, (raw, known)) in raw.iter().zip(known).enumerate() {
+                unknown_keys(raw, known, &format!("{}.{}", prefix, i), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_values(config: &Config, out: &mut Vec<Diagnostic>) {
+    let credentials = config.expand_path(&config.auth.credentials_path);
+    if !Path::new(&credentials).exists() {
+        out.push(Diagnostic::error(
+            "auth.credentials_path",
+            format!("File not found: {}", credentials),
+        ));
+    }
+
+    let token = config.expand_path(&config.auth.token_cache_path);
+    if !Path::new(&token).exists() {
+        out.push(Diagnostic::warning(
+            "auth.token_cache_path",
+            format!("No token yet at {}; run `callux auth`", token),
+        ));
+    }
+
+    if let Some(bundle) = &config.network.ca_bundle {
+        let bundle = config.expand_path(bundle);
+        if !Path::new(&bundle).exists() {
+            out.push(Diagnostic::error(
+                "network.ca_bundle",
+                format!("File not found: {}", bundle),
+            ));
+        }
+    }
+
+    if !(1..=MAX_RESULTS).contains(&config.network.max_results) {
+        out.push(Diagnostic::error(
+            "network.max_results",
+            format!("Must be between 1 and {}", MAX_RESULTS),
+        ));
+    }
+
+    if StrftimeItems::new(&config.display.date_format).any(|item| item == Item::Error) {
+        out.push(Diagnostic::error(
+            "display.date_format",
+            format!("Invalid strftime format '{}'", config.display.date_format),
+        ));
+    }
+
+    if let Err(e) = Zone::parse(&config.display.timezone) {
+        out.push(Diagnostic::error("display.timezone", e));
+    }
+
+    if let Some(hours) = &config.display.working_hours {
+        if let Err(e) = WorkingHours::parse(hours) {
+            out.push(Diagnostic::error("display.working_hours", e));
+        }
+    }
+
+    for (i, problem) in classes::check(&config.display.classes) {
+        out.push(Diagnostic::error(format!("display.classes.{}", i), problem));
+    }
+
+    if config.calendars.iter().all(|cal| !cal.enabled) {
+        out.push(Diagnostic::warning("calendars", "No calendar is enabled"));
+    }
+
+    for problem in filter::check(&config.filters) {
+        out.push(Diagnostic::error("filters", problem));
+    }
+
+    for (i, calendar) in config.calendars.iter().enumerate() {
+        for problem in filter::check(&calendar.filters) {
+            out.push(Diagnostic::error(
+                format!("calendars.{}.filters", i),
+                problem,
+            ));
+        }
+
+        if !is_hex_color(&calendar.color) {
+            out.push(Diagnostic::error(
+                format!("calendars.{}.color", i),
+                format!("'{}' is not a #RGB or #RRGGBB color", calendar.color),
+            ));
+        }
+
+        if let Some(max) = calendar.max_results {
+            if !(1..=MAX_RESULTS).contains(&max) {
+                out.push(Diagnostic::error(
+                    format!("calendars.{}.max_results", i),
+                    format!("Must be between 1 and {}", MAX_RESULTS),
+                ));
+            }
+        }
+        if calendar.max_days_ahead.is_some_and(|days| days < 1) {
+            out.push(Diagnostic::error(
+                format!("calendars.{}.max_days_ahead", i),
+                "Must be at least 1 day",
+            ));
+        }
+
+        let duplicate = config.calendars[..i].iter().any(|c| c.id == calendar.id);
+        if duplicate {
+            out.push(Diagnostic::warning(
+                format!("calendars.{}.id", i),
+                format!("Calendar '{}' is listed more than once", calendar.id),
+            ));
+        }
+    }
+
+    for (alias, id) in &config.aliases {
+        if !config.calendars.iter().any(|c| c.id == *id) {
+            out.push(Diagnostic::warning(
+                format!("aliases.{}", alias),
+                format!("Calendar '{}' is not configured", id),
+            ));
+        }
+    }
+
+    check_templates(config, out);
+}
+
+fn check_templates(config: &Config, out: &mut Vec<Diagnostic>) {
+    for (i, template) in config.templates.iter().enumerate() {
+        if template.name.is_empty() {
+            out.push(Diagnostic::error(
+                format!("templates.{}.name", i),
+                "Template has no name",
+            ));
+        } else if config.templates[..i]
+            .iter()
+            .any(|t| t.name.eq_ignore_ascii_case(&template.name))
+        {
+            out.push(Diagnostic::warning(
+                format!("templates.{}.name", i),
+                format!("Template '{}' is defined more than once", template.name),
+            ));
+        }
+
+        if let Some(duration) = &template.duration {
+            if let Err(e) = when::parse_duration(duration) {
+                out.push(Diagnostic::error(format!("templates.{}.duration", i), e));
+            }
+        }
+        for remind in &template.remind {
+            if let Err(e) = when::parse_duration(remind) {
+                out.push(Diagnostic::error(format!("templates.{}.remind", i), e));
+            }
+        }
+
+        if let Some(calendar) = &template.calendar {
+            if let Err(e) = config.find_calendar(calendar) {
+                out.push(Diagnostic::error(
+                    format!("templates.{}.calendar", i),
+                    e.to_string(),
+                ));
+            }
+        }
+    }
+}
+
+pub fn is_hex_color(color: &str) -> bool {
+    match color.strip_prefix('#') {
+        Some(hex) => {
+            (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,297 @@
+use crate::zone::Zone;
+use chrono::{
+    DateTime, Datelike, Days, Duration, FixedOffset, Months, NaiveDate, NaiveDateTime, NaiveTime,
+    Utc, Weekday,
+};
+
+/// When a new or edited event starts.
+#[derive(Debug, Clone, Copy)]
+pub enum Start {
+    Timed(DateTime<FixedOffset>),
+    AllDay(NaiveDate),
+}
+
+/// Parses `--at`: `"2024-06-03 14:00"` (or with a `T`), `"2024-06-03"` for an
+/// all-day event, `"14:00"` for today, or any day `parse_day` understands
+/// followed by a time (`"tomorrow 14:00"`, `"friday at 15:00"`). Times are
+/// wall-clock times in `zone`.
+pub fn parse_start(input: &str, zone: Zone) -> Result<Start, String> {
+    let input = input.trim();
+    let today = zone.now().date_naive();
+    let invalid = || {
+        format!(
+            "Invalid time '{}'; use e.g. \"2024-06-03 14:00\", \"2024-06-03\", \"tomorrow 14:00\" or \"next monday 9:30\"",
+            input
+        )
+    };
+
+    let (date, time) = if let Some(date) = parse_day(input, today) {
+        (date, None)
+    } else if let Some(time) = parse_time(input) {
+        (today, Some(time))
+    } else {
+        let (day, time) = input
+            .rsplit_once(' ')
+            .or_else(|| input.split_once('T'))
+            .ok_or_else(invalid)?;
+        let day = day.trim_end().to_ascii_lowercase();
+        let day = day.strip_suffix(" at").unwrap_or(&day);
+        (
+            parse_day(day, today).ok_or_else(invalid)?,
+            Some(parse_time(time.trim()).ok_or_else(invalid)?),
+        )
+    };
+
+    match time {
+        Some(time) => zone
+            .at(NaiveDateTime::new(date, time))
+            .map(Start::Timed)
+            .ok_or_else(|| format!("{} doesn't exist in this timezone (DST change)", input)),
+        None => Ok(Start::AllDay(date)),
+    }
+}
+
+/// Parses agenda and prefetch `--until`: an RFC 3339 moment, or anything
+/// `parse_start` takes. A day alone means the end of it, and a time that
+/// already passed today means tomorrow's, so `--until 02:00` works late in
+/// the evening.
+pub fn parse_until(input: &str, zone: Zone) -> Result<DateTime<FixedOffset>, String> {
+    let now = zone.now();
+    let until = match DateTime::parse_from_rfc3339(input.trim()) {
+        Ok(at) => at,
+        Err(_) => match parse_start(input, zone)? {
+            Start::Timed(at) if at <= now && at.date_naive() == now.date_naive() => {
+                zone.add_days(&at, 1)
+            }
+            Start::Timed(at) => at,
+            Start::AllDay(date) => {
+                let next_day = date + Duration::days(1);
+                zone.start_of_day(next_day)
+                    .ok_or_else(|| format!("{} doesn't start in this timezone", next_day))?
+            }
+        },
+    };
+    if until <= now {
+        return Err(format!("'{}' is in the past", input.trim()));
+    }
+    Ok(until)
+}
+
+/// A day relative to `today`: `2024-06-03`, `today`, `tomorrow`,
+/// `yesterday`, a weekday (`friday` is today on a Friday, `next friday`
+/// always a later one) or `in 3 days`/`in 2 weeks`/`in 1 month`.
+pub fn parse_day(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(input.trim(), "%Y-%m-%d") {
+        return Some(date);
+    }
+    let input = input.to_ascii_lowercase();
+    let words: Vec<&str> = input.split_whitespace().collect();
+    match words.as_slice() {
+        ["today"] => Some(today),
+        ["tomorrow"] => Some(today + Duration::days(1)),
+        ["yesterday"] => Some(today - Duration::days(1)),
+        ["next", day] => {
+            let day = day.parse::<Weekday>().ok()?;
+            Some(next_weekday(today + Duration::days(1), day))
+        }
+        ["in", count, unit] => {
+            let count: u32 = count.parse().ok()?;
+            match unit.trim_end_matches('s') {
+                "day" => today.checked_add_days(Days::new(count.into())),
+                "week" => today.checked_add_days(Days::new(u64::from(count) * 7)),
+                "month" => today.checked_add_months(Months::new(count)),
+                _ => None,
+            }
+        }
+        [day] => Some(next_weekday(today, day.parse::<Weekday>().ok()?)),
+        _ => None,
+    }
+}
+
+/// The first `day` on or after `from`.
+fn next_weekday(from: NaiveDate, day: Weekday) -> NaiveDate {
+    let ahead = (day.num_days_from_monday() + 7 - from.weekday().num_days_from_monday()) % 7;
+    from + Duration::days(ahead.into())
+}
+
+/// The inverse of `parse_start`, for prefilled edit forms.
+pub fn format_start(start: Start) -> String {
+    match start {
+        Start::Timed(at) => at.format("%Y-%m-%d %H:%M").to_string(),
+        Start::AllDay(date) => date.format("%Y-%m-%d").to_string(),
+    }
+}
+
+fn parse_time(input: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(input, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(input, "%H:%M:%S"))
+        .ok()
+}
+
+/// Parses `--duration`: `45m`, `1h`, `1h30m`, `2d`, or bare minutes (`90`).
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration '{}'; use e.g. 45m, 1h30m or 2d", input);
+    let input = input.trim();
+
+    let total = match input.parse::<i64>() {
+        Ok(minutes) => Duration::minutes(minutes),
+        Err(_) => {
+            let mut total = Duration::zero();
+            let mut number = String::new();
+            for c in input.chars() {
+                if c.is_ascii_digit() {
+                    number.push(c);
+                    continue;
+                }
+                let value: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                total += match c.to_ascii_lowercase() {
+                    'd' => Duration::days(value),
+                    'h' => Duration::hours(value),
+                    'm' => Duration::minutes(value),
+                    _ => return Err(invalid()),
+                };
+            }
+            if !number.is_empty() {
+                return Err(invalid());
+            }
+            total
+        }
+    };
+
+    if total <= Duration::zero() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// The inverse of `parse_duration`: `1h30m`, `45m`, `2d`.
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    let (days, hours, minutes) = (minutes / 1440, minutes % 1440 / 60, minutes % 60);
+    let mut out = String::new();
+    for (value, unit) in [(days, 'd'), (hours, 'h'), (minutes, 'm')] {
+        if value > 0 {
+            out.push_str(&format!("{}{}", value, unit));
+        }
+    }
+    if out.is_empty() {
+        out.push_str("0m");
+    }
+    out
+}
+
+/// How a new event repeats, from `--repeat`/`--every`/`--on`/`--until`/
+/// `--count`.
+#[derive(Debug, Clone)]
+pub struct Recurrence {
+    /// `DAILY`, `WEEKLY`, `MONTHLY` or `YEARLY`.
+    pub frequency: &'static str,
+    pub interval: u32,
+    pub on: Vec<Weekday>,
+    /// Last day it may occur on, inclusive.
+    pub until: Option<NaiveDate>,
+    pub count: Option<u32>,
+}
+
+impl Recurrence {
+    /// The RFC 5545 `RRULE:` line. A timed event's `UNTIL` has to be UTC,
+    /// so it becomes the end of that day in `zone`.
+    pub fn rrule(&self, start: Start, zone: Zone) -> String {
+        let mut rule = format!("RRULE:FREQ={}", self.frequency);
+        if self.interval > 1 {
+            rule.push_str(&format!(";INTERVAL={}", self.interval));
+        }
+        if !self.on.is_empty() {
+            let days: Vec<&str> = self.on.iter().map(|day| weekday_code(*day)).collect();
+            rule.push_str(&format!(";BYDAY={}", days.join(",")));
+        }
+        if let Some(until) = self.until {
+            let until = match start {
+                Start::AllDay(_) => until.format("%Y%m%d").to_string(),
+                Start::Timed(_) => {
+                    let next_day = until + Duration::days(1);
+                    let end = zone.start_of_day(next_day).map_or_else(
+                        || next_day.and_time(NaiveTime::MIN).and_utc(),
+                        |start| start.with_timezone(&Utc),
+                    );
+                    (end - Duration::seconds(1))
+                        .format("%Y%m%dT%H%M%SZ")
+                        .to_string()
+                }
+            };
+            rule.push_str(&format!(";UNTIL={}", until));
+        }
+        if let Some(count) = self.count {
+            rule.push_str(&format!(";COUNT={}", count));
+        }
+        rule
+    }
+}
+
+/// A short description of an `RRULE:` line's frequency: `daily`, `weekly`,
+/// `every 2 weeks`, ...
+pub fn describe_rrule(rule: &str) -> Option<String> {
+    let rule = rule.strip_prefix("RRULE:")?;
+    let part = |name: &str| {
+        rule.split(';')
+            .find_map(|part| part.strip_prefix(name)?.strip_prefix('='))
+    };
+    let (single, plural) = match part("FREQ")? {
+        "DAILY" => ("daily", "days"),
+        "WEEKLY" => ("weekly", "weeks"),
+        "MONTHLY" => ("monthly", "months"),
+        "YEARLY" => ("yearly", "years"),
+        _ => return None,
+    };
+    match part("INTERVAL").and_then(|n| n.parse::<u32>().ok()) {
+        Some(interval) if interval > 1 => Some(format!("every {} {}", interval, plural)),
+        _ => Some(single.to_string()),
+    }
+}
+
+/// Parses `--on`: `mon,wed`, `monday,Friday`, ...
+pub fn parse_weekdays(input: &str) -> Result<Vec<Weekday>, String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|day| !day.is_empty())
+        .map(|day| {
+            day.parse::<Weekday>()
+                .map_err(|_| format!("Invalid weekday '{}'; use e.g. mon,wed,fri", day))
+        })
+        .collect()
+}
+
+/// Parses `--until`: the last day the event may occur on, as anything
+/// `parse_day` understands.
+pub fn parse_date(input: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    parse_day(input, today).ok_or_else(|| {
+        format!(
+            "Invalid date '{}'; use e.g. 2024-12-20, \"next friday\" or \"in 3 months\"",
+            input
+        )
+    })
+}
+
+/// A raw `--rrule`, with or without the `RRULE:` prefix.
+pub fn normalize_rrule(input: &str) -> Result<String, String> {
+    let rule = input.trim();
+    let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+    if !rule.to_ascii_uppercase().contains("FREQ=") {
+        return Err(format!("Invalid rule '{}'; it needs a FREQ=", input));
+    }
+    Ok(format!("RRULE:{}", rule))
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
@@ -0,0 +1,159 @@
+use crate::auth::AuthManager;
+use crate::calendar::CalendarClient;
+use crate::config::{CalendarConfig, Config, DEFAULT_CALENDAR_COLOR};
+use crate::validate;
+use anyhow::{Result, anyhow};
+use colored::*;
+use google_calendar3::api::CalendarListEntry;
+use std::io::BufRead;
+
+/// Guided `config init`: installs the OAuth client, signs in, and lets the
+/// user choose calendars and colors before writing the config.
+pub async fn run(mut config: Config) -> Result<Config> {
+    println!("{}", "callux setup".bright_blue().bold());
+    println!("You need an OAuth client for a desktop app from");
+    println!("https://console.developers.google.com/ with the Calendar API enabled.\n");
+
+    let auth_manager = AuthManager::new(config.clone());
+    loop {
+        let answer = prompt("Path to the downloaded client JSON (or paste its contents):")?;
+        let content = if answer.starts_with('{') {
+            read_pasted_json(answer)?
+        } else if answer.is_empty() {
+            continue;
+        } else {
+            let path = config.expand_path(&answer);
+            match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    eprintln!("Cannot read {}: {}", path, e);
+                    continue;
+                }
+            }
+        };
+
+        match auth_manager.install_credentials(&content) {
+            Ok(()) => break,
+            Err(e) => eprintln!("{}: {}", "Not a usable client JSON".red().bold(), e),
+        }
+    }
+
+    println!("\nOpening Google sign-in...");
+    auth_manager
+        .get_token()
+        .await
+        .map_err(|e| anyhow!("Authentication failed: {}", e))?;
+    println!("{}", "Authentication successful!".bright_green().bold());
+
+    let calendars = CalendarClient::new(config.clone())
+        .list_calendars()
+        .await
+        .map_err(|e| anyhow!("Failed to list calendars: {}", e))?;
+    config.calendars = choose_calendars(&calendars)?;
+    config.save()?;
+
+    Ok(config)
+}
+
+fn choose_calendars(calendars: &[CalendarListEntry]) -> Result<Vec<CalendarConfig>> {
+    println!("\n{}", "Your calendars:".bright_blue().bold());
+    for (i, calendar) in calendars.iter().enumerate() {
+        let primary = if calendar.primary.unwrap_or(false) {
+            " (primary)"
+        } else {
+            ""
+        };
+        println!(
+            "  {:>2}. {}{}",
+            i + 1,
+            calendar.summary.as_deref().unwrap_or("Untitled"),
+            primary.bright_yellow()
+        );
+    }
+
+    let selected = loop {
+        let answer = prompt("Calendars to show, e.g. 1,3 [primary]:")?;
+        if answer.is_empty() {
+            break calendars
+                .iter()
+                .map(|c| c.primary.unwrap_or(false))
+                .collect::<Vec<_>>();
+        }
+
+        let picks: Option<Vec<usize>> = answer
+            .split(',')
+            .map(|n| n.trim().parse::<usize>().ok())
+            .collect();
+        match picks {
+            Some(picks) if picks.iter().all(|n| (1..=calendars.len()).contains(n)) => {
+                break (1..=calendars.len()).map(|n| picks.contains(&n)).collect();
+            }
+            _ => eprintln!("Enter numbers between 1 and {}", calendars.len()),
+        }
+    };
+
+    let mut configured = Vec::new();
+    for (calendar, enabled) in calendars.iter().zip(selected) {
+        let Some(id) = calendar.id.clone() else {
+            continue;
+        };
+        let name = calendar.summary.clone().unwrap_or_else(|| id.clone());
+        let google_color = calendar
+            .background_color
+            .clone()
+            .filter(|color| validate::is_hex_color(color))
+            .unwrap_or_else(|| DEFAULT_CALENDAR_COLOR.to_string());
+
+        let color = if enabled {
+            loop {
+                let answer = prompt(&format!("Color for {} [{}]:", name, google_color))?;
+                if answer.is_empty() {
+                    break google_color.clone();
+                }
+                if validate::is_hex_color(&answer) {
+                    break answer;
+                }
+                eprintln!("Use #RGB or #RRGGBB");
+            }
+        } else {
+            google_color
+        };
+
+        // Keep Google's alias for the account's own calendar, as the
+        // default config does.
+        let id = if calendar.primary.unwrap_or(false) {
+            "primary".to_string()
+        } else {
+            id
+        };
+        configured.push(CalendarConfig::new(id, name, color, enabled));
+    }
+
+    Ok(configured)
+}
+
+fn prompt(question: &str) -> Result<String> {
+    eprint!("{} ", question);
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer)? == 0 {
+        return Err(anyhow!("Setup cancelled"));
+    }
+    Ok(answer.trim().to_string())
+}
+
+/// Keeps reading lines after `first` until the pasted text is complete JSON.
+fn read_pasted_json(first: String) -> Result<String> {
+    let mut content = first;
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    while serde_json::from_str::<serde_json::Value>(&content).is_err() {
+        match lines.next() {
+            Some(line) => {
+                content.push('\n');
+                content.push_str(&line?);
+            }
+            None => return Err(anyhow!("Pasted JSON is incomplete")),
+        }
+    }
+    Ok(content)
+}
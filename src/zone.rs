@@ -0,0 +1,80 @@
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// The timezone events are converted to and shown in, from
+/// `display.timezone`: the system's (`"local"`) or any IANA name.
+#[derive(Debug, Clone, Copy)]
+pub enum Zone {
+    Local,
+    Named(Tz),
+}
+
+impl Zone {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        if name.is_empty() || name.eq_ignore_ascii_case("local") {
+            return Ok(Zone::Local);
+        }
+        name.parse::<Tz>()
+            .map(Zone::Named)
+            .map_err(|_| format!("Unknown timezone '{}'", name))
+    }
+
+    /// Like `parse`, but warns and falls back to local time so a typo
+    /// doesn't take the whole bar down.
+    pub fn from_config(name: &str) -> Self {
+        Self::parse(name).unwrap_or_else(|e| {
+            eprintln!("Warning: {}; using local time", e);
+            Zone::Local
+        })
+    }
+
+    /// The IANA name, which Google needs for recurring events.
+    pub fn iana_name(&self) -> Option<String> {
+        match self {
+            Zone::Local => iana_time_zone::get_timezone().ok(),
+            Zone::Named(tz) => Some(tz.name().to_string()),
+        }
+    }
+
+    pub fn convert<T: TimeZone>(&self, time: &DateTime<T>) -> DateTime<FixedOffset> {
+        match self {
+            Zone::Local => time.with_timezone(&Local).fixed_offset(),
+            Zone::Named(tz) => time.with_timezone(tz).fixed_offset(),
+        }
+    }
+
+    /// `days` calendar days after `time` at the same wall-clock time, so a
+    /// range across a DST change is neither an hour short nor long. Where
+    /// that time doesn't exist, whole 24-hour days are added instead.
+    pub fn add_days<T: TimeZone>(&self, time: &DateTime<T>, days: i64) -> DateTime<FixedOffset> {
+        let local = self.convert(time);
+        self.at(local.naive_local() + Duration::days(days))
+            .unwrap_or(local + Duration::days(days))
+    }
+
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        self.convert(&Utc::now())
+    }
+
+    /// `time` read as wall-clock time in this zone; the earlier instant when
+    /// a DST change makes it ambiguous, `None` when it doesn't exist.
+    pub fn at(&self, time: NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self {
+            Zone::Local => Some(Local.from_local_datetime(&time).earliest()?.fixed_offset()),
+            Zone::Named(tz) => Some(tz.from_local_datetime(&time).earliest()?.fixed_offset()),
+        }
+    }
+
+    /// Start of `date` in this zone. Where a DST change skips midnight the
+    /// day starts at the first hour that exists.
+    pub fn start_of_day(&self, date: NaiveDate) -> Option<DateTime<FixedOffset>> {
+        (0..3).find_map(|hour| {
+            let time = date.and_hms_opt(hour, 0, 0)?;
+            let start = match self {
+                Zone::Local => Local.from_local_datetime(&time).earliest()?.fixed_offset(),
+                Zone::Named(tz) => tz.from_local_datetime(&time).earliest()?.fixed_offset(),
+            };
+            Some(start)
+        })
+    }
+}
@@ -0,0 +1,255 @@
+mod common;
+
+use common::Harness;
+use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn converts_recorded_events() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.serve_events("primary", "events_primary.json").await;
+
+    let (output, json) = harness.agenda_json(&[]).await;
+    assert!(output.status.success(), "{}", common::stderr(&output));
+
+    assert_eq!(json["text"], "09:00 Standup");
+    assert_eq!(json["class"], "calendar-multiple");
+    assert_eq!(json["stale"], false);
+    assert!(
+        json["urgency"]
+            .as_u64()
+            .is_some_and(|urgency| urgency <= 100)
+    );
+
+    let tooltip = json["tooltip"].as_str().unwrap();
+    // `end.date` is exclusive, so the offsite covers two days.
+    assert!(tooltip.contains("All day until"), "{}", tooltip);
+    assert!(tooltip.contains("Team offsite"), "{}", tooltip);
+    // Event times are shown in display.timezone, not the event's own zone.
+    assert!(tooltip.contains("08:00: Design review"), "{}", tooltip);
+    assert!(tooltip.contains("Untitled Event"), "{}", tooltip);
+    assert!(tooltip.contains("Doctor &amp; dentist"), "{}", tooltip);
+    // Without an end an event counts as an hour long, but is still listed.
+    assert!(tooltip.contains("10:00: Coffee"), "{}", tooltip);
+    assert!(!tooltip.contains("Cancelled sync"), "{}", tooltip);
+}
+
+#[tokio::test]
+async fn shows_cancelled_events_on_request() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.serve_events("primary", "events_primary.json").await;
+
+    let (_, json) = harness.agenda_json(&["--show-cancelled"]).await;
+    let tooltip = json["tooltip"].as_str().unwrap();
+    assert!(
+        tooltip.contains("Cancelled sync (cancelled)"),
+        "{}",
+        tooltip
+    );
+
+    let requests = harness.server.received_requests().await.unwrap();
+    assert!(
+        requests[0]
+            .url
+            .query()
+            .unwrap()
+            .contains("showDeleted=true")
+    );
+}
+
+#[tokio::test]
+async fn masks_private_events() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.serve_events("primary", "events_primary.json").await;
+
+    let (_, json) = harness.agenda_json(&["--hide-private"]).await;
+    let tooltip = json["tooltip"].as_str().unwrap();
+    assert!(!tooltip.contains("Doctor"), "{}", tooltip);
+
+    let mut harness = Harness::new(&["primary"]).await;
+    harness.set_env("CALLUX_DISPLAY__VISIBILITY", "mask");
+    harness.serve_events("primary", "events_primary.json").await;
+
+    let (_, json) = harness.agenda_json(&[]).await;
+    let tooltip = json["tooltip"].as_str().unwrap();
+    assert!(!tooltip.contains("Doctor"), "{}", tooltip);
+    assert!(tooltip.contains("11:00: Busy"), "{}", tooltip);
+
+    let raw = harness
+        .run(&["agenda", "--format", "json-raw", "--no-daemon"])
+        .await;
+    assert!(raw.status.success(), "{}", common::stderr(&raw));
+    let events: serde_json::Value = serde_json::from_slice(&raw.stdout).unwrap();
+    let doctor = events
+        .as_array()
+        .and_then(|events| events.iter().find(|event| event["id"] == "doctor"))
+        .expect("masked event listed");
+    assert_eq!(doctor["title"], "Busy");
+    for field in [
+        "description",
+        "location",
+        "meeting_url",
+        "html_link",
+        "organizer",
+    ] {
+        assert!(doctor[field].is_null(), "{}: {}", field, doctor);
+    }
+    assert_eq!(doctor["properties"], serde_json::json!({}));
+}
+
+#[tokio::test]
+async fn merges_calendars_in_start_order() {
+    let harness = Harness::new(&["primary", "team"]).await;
+    harness.serve_events("primary", "events_primary.json").await;
+    harness.serve_events("team", "events_team.json").await;
+
+    let (_, json) = harness.agenda_json(&[]).await;
+    let tooltip = json["tooltip"].as_str().unwrap();
+    let standup = tooltip.find("Standup").unwrap();
+    let planning = tooltip.find("Sprint planning").unwrap();
+    let review = tooltip.find("Design review").unwrap();
+    assert!(standup < planning && planning < review, "{}", tooltip);
+}
+
+#[tokio::test]
+async fn follows_page_tokens() {
+    let harness = Harness::new(&["primary"]).await;
+    Mock::given(method("GET"))
+        .and(path("/calendars/primary/events"))
+        .and(query_param_is_missing("pageToken"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_string(common::load_fixture("events_page1.json")),
+        )
+        .expect(1)
+        .mount(&harness.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/calendars/primary/events"))
+        .and(query_param("pageToken", "page-2"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_string(common::load_fixture("events_page2.json")),
+        )
+        .expect(1)
+        .mount(&harness.server)
+        .await;
+
+    let (_, json) = harness.agenda_json(&[]).await;
+    let tooltip = json["tooltip"].as_str().unwrap();
+    assert!(tooltip.contains("First page"), "{}", tooltip);
+    assert!(tooltip.contains("Second page"), "{}", tooltip);
+}
+
+#[tokio::test]
+async fn applies_limit_and_filters() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.serve_events("primary", "events_primary.json").await;
+
+    let (_, json) = harness.agenda_json(&["--limit", "1"]).await;
+    assert_eq!(json["class"], "calendar-single");
+
+    let (_, json) = harness
+        .agenda_json(&["--filter", "review|Coffee", "--exclude", "Coffee"])
+        .await;
+    assert_eq!(json["class"], "calendar-single");
+    assert!(json["text"].as_str().unwrap().ends_with("Design review"));
+
+    let (_, json) = harness.agenda_json(&["--meetings-only"]).await;
+    assert_eq!(json["text"], "09:00 Standup");
+    assert_eq!(json["class"], "calendar-single");
+
+    let (_, json) = harness
+        .agenda_json(&["--property", "team=design", "--property", "ticket"])
+        .await;
+    assert_eq!(json["class"], "calendar-single");
+    assert!(json["text"].as_str().unwrap().ends_with("Design review"));
+
+    let (_, json) = harness.agenda_json(&["--property", "team=sales"]).await;
+    assert_eq!(json["class"], "calendar-empty");
+}
+
+#[tokio::test]
+async fn adds_classes_from_rules() {
+    let harness = Harness::with_config(
+        &["primary"],
+        r#"
+[[display.classes]]
+class = "standup"
+title = "(?i)^stand"
+
+[[display.classes]]
+class = "other-calendar"
+calendar = "team"
+"#,
+    )
+    .await;
+    harness.serve_events("primary", "events_primary.json").await;
+
+    let (_, json) = harness.agenda_json(&[]).await;
+    assert_eq!(
+        json["class"],
+        serde_json::json!(["calendar-multiple", "standup"])
+    );
+}
+
+#[tokio::test]
+async fn reformats_raw_events_from_stdin() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.serve_events("primary", "events_primary.json").await;
+
+    let raw = harness
+        .run(&["agenda", "--format", "json-raw", "--no-daemon"])
+        .await;
+    assert!(raw.status.success(), "{}", common::stderr(&raw));
+    let events: serde_json::Value = serde_json::from_slice(&raw.stdout).unwrap();
+    assert!(events.as_array().is_some_and(|events| !events.is_empty()));
+    assert!(
+        events[0]["urgency"]
+            .as_u64()
+            .is_some_and(|urgency| urgency <= 100)
+    );
+
+    let output = harness
+        .run_with_input(&["fmt", "--format", "json"], &raw.stdout)
+        .await;
+    assert!(output.status.success(), "{}", common::stderr(&output));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["text"], "09:00 Standup");
+    // Nothing is fetched to render events given on stdin.
+    assert_eq!(harness.requests().await, 1);
+
+    let output = harness
+        .run_with_input(&["fmt"], b"{\"not\": \"events\"}")
+        .await;
+    assert!(!output.status.success());
+    assert!(common::stderr(&output).contains("JSON array of events"));
+}
+
+#[tokio::test]
+async fn declines_despite_hide_declined() {
+    let mut harness = Harness::new(&["primary"]).await;
+    harness.set_env("CALLUX_DISPLAY__HIDE_DECLINED", "true");
+    let event = |response: &str| {
+        serde_json::json!({
+            "id": "evt1",
+            "summary": "Planning",
+            "start": {"dateTime": "2030-01-07T09:00:00Z"},
+            "end": {"dateTime": "2030-01-07T10:00:00Z"},
+            "attendees": [{"email": "me@example.com", "self": true, "responseStatus": response}]
+        })
+    };
+    Mock::given(method("GET"))
+        .and(path("/calendars/primary/events/evt1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(event("needsAction")))
+        .mount(&harness.server)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/calendars/primary/events/evt1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(event("declined")))
+        .expect(1)
+        .mount(&harness.server)
+        .await;
+
+    let output = harness.run(&["rsvp", "evt1", "decline"]).await;
+    assert!(output.status.success(), "{}", common::stderr(&output));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Declined Planning"));
+}
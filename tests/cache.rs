@@ -0,0 +1,92 @@
+mod common;
+
+use common::Harness;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn serves_repeat_agendas_from_the_cache() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.serve_events("primary", "events_primary.json").await;
+
+    let (_, first) = harness.agenda_json(&[]).await;
+    assert_eq!(harness.requests().await, 1);
+
+    let (_, second) = harness.agenda_json(&[]).await;
+    assert_eq!(harness.requests().await, 1);
+    assert_eq!(first["tooltip"], second["tooltip"]);
+}
+
+#[tokio::test]
+async fn one_off_flags_keep_the_plain_agenda_cached() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.serve_events("primary", "events_primary.json").await;
+
+    harness.agenda_json(&[]).await;
+    harness.agenda_json(&["--show-cancelled"]).await;
+    assert_eq!(harness.requests().await, 2);
+
+    let (_, json) = harness.agenda_json(&[]).await;
+    assert_eq!(harness.requests().await, 2);
+    assert_eq!(json["text"], "09:00 Standup");
+}
+
+#[tokio::test]
+async fn offline_uses_the_cache_only() {
+    let harness = Harness::new(&["primary"]).await;
+
+    let output = harness.run(&["agenda", "--offline", "--no-daemon"]).await;
+    assert!(!output.status.success());
+    assert!(common::stderr(&output).contains("no cached events"));
+
+    harness.serve_events("primary", "events_primary.json").await;
+    harness.agenda_json(&[]).await;
+    let (_, json) = harness.agenda_json(&["--offline"]).await;
+    assert_eq!(json["stale"], true);
+    assert_eq!(json["stale_reason"], "offline");
+    assert_eq!(json["text"], "09:00 Standup");
+    assert_eq!(harness.requests().await, 1);
+}
+
+#[tokio::test]
+async fn falls_back_to_stale_events_when_rate_limited() {
+    let mut harness = Harness::new(&["primary"]).await;
+    // Every cached entry is already expired, so each run asks Google first.
+    harness.set_env("CALLUX_CACHE__TTL_SECONDS", "0");
+    Mock::given(method("GET"))
+        .and(path("/calendars/primary/events"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_string(common::load_fixture("events_primary.json")),
+        )
+        .up_to_n_times(1)
+        .mount(&harness.server)
+        .await;
+    harness
+        .fail_events("primary", 403, "error_rate_limit.json")
+        .await;
+
+    let (_, fresh) = harness.agenda_json(&[]).await;
+    assert_eq!(fresh["stale"], false);
+
+    let (output, stale) = harness.agenda_json(&[]).await;
+    assert!(output.status.success(), "{}", common::stderr(&output));
+    assert_eq!(stale["stale"], true);
+    assert_eq!(stale["stale_reason"], "rate_limited");
+    assert_eq!(stale["text"], "09:00 Standup");
+}
+
+#[tokio::test]
+async fn swr_marks_expired_entries_as_stale() {
+    let mut harness = Harness::new(&["primary"]).await;
+    harness.set_env("CALLUX_CACHE__TTL_SECONDS", "0");
+    harness.set_env("CALLUX_CACHE__STRATEGY", "swr");
+    harness.serve_events("primary", "events_primary.json").await;
+
+    let (_, fresh) = harness.agenda_json(&[]).await;
+    assert_eq!(fresh["stale"], false);
+
+    let (_, revalidating) = harness.agenda_json(&[]).await;
+    assert_eq!(revalidating["stale"], true);
+    assert_eq!(revalidating["stale_reason"], "revalidating");
+    assert_eq!(revalidating["text"], "09:00 Standup");
+}
@@ -0,0 +1,212 @@
+//! Runs the real `callux` binary against a mock Google Calendar API.
+//!
+//! Each `Harness` gets its own config, cache and data directories, and points
+//! callux at a `wiremock` server with `CALLUX_API_BASE`. `CALLUX_ACCESS_TOKEN`
+//! skips signing in, so no credentials are needed.
+
+#![allow(dead_code)]
+
+use chrono::{Duration, Utc};
+use std::path::PathBuf;
+use std::process::{Output, Stdio};
+use tempfile::TempDir;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, Command};
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub const TOKEN: &str = "test-token";
+
+pub struct Harness {
+    pub server: MockServer,
+    dir: TempDir,
+    config: PathBuf,
+    env: Vec<(String, String)>,
+}
+
+impl Harness {
+    /// A config with the given `[[calendars]]` ids, all enabled.
+    pub async fn new(calendars: &[&str]) -> Self {
+        Self::with_config(calendars, "").await
+    }
+
+    /// Like `new`, with `extra` TOML appended to the config.
+    pub async fn with_config(calendars: &[&str], extra: &str) -> Self {
+        let server = MockServer::start().await;
+        let dir = TempDir::new().expect("temp dir");
+        let config = dir.path().join("config.toml");
+
+        let mut toml = format!(
+            r#"version = 3
+
+[auth]
+credentials_path = "{root}/credentials.json"
+token_cache_path = "{root}/token.json"
+
+[cache]
+ttl_seconds = 300
+max_entries = 100
+path = "{root}/cache.db"
+
+[display]
+max_events = 10
+date_format = "%H:%M"
+timezone = "UTC"
+
+[network]
+max_pages = 5
+retry_attempts = 1
+retry_base_delay_ms = 1
+request_spacing_ms = 0
+max_retry_after_seconds = 1
+"#,
+            root = dir.path().display()
+        );
+        for id in calendars {
+            toml.push_str(&format!(
+                "\n[[calendars]]\nid = \"{id}\"\nname = \"{id}\"\n\
+                 color = \"#4285f4\"\nenabled = true\n"
+            ));
+        }
+        toml.push_str(extra);
+        std::fs::write(&config, toml).expect("write config");
+
+        Self {
+            server,
+            dir,
+            config,
+            env: Vec::new(),
+        }
+    }
+
+    /// Answers every events list request for `calendar` with `fixture`.
+    pub async fn serve_events(&self, calendar: &str, fixture: &str) {
+        Mock::given(method("GET"))
+            .and(path(format!("/calendars/{}/events", calendar)))
+            .and(header(
+                "authorization",
+                format!("Bearer {}", TOKEN).as_str(),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(load_fixture(fixture)))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers the events list of `calendar` with `status` and `fixture`.
+    pub async fn fail_events(&self, calendar: &str, status: u16, fixture: &str) {
+        Mock::given(method("GET"))
+            .and(path(format!("/calendars/{}/events", calendar)))
+            .respond_with(ResponseTemplate::new(status).set_body_string(load_fixture(fixture)))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Writes `text` as the `config.d/<name>` drop-in next to the config.
+    pub fn drop_in(&self, name: &str, text: &str) {
+        let dir = self.dir.path().join("config.d");
+        std::fs::create_dir_all(&dir).expect("create config.d");
+        std::fs::write(dir.join(name), text).expect("write drop-in");
+    }
+
+    /// Sets `name` for every later run, e.g. a `CALLUX_SECTION__KEY`
+    /// override.
+    pub fn set_env(&mut self, name: &str, value: &str) {
+        self.env.push((name.to_string(), value.to_string()));
+    }
+
+    /// Runs `callux` with `args` after `--config`.
+    pub async fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().await.expect("run callux")
+    }
+
+    /// Like `run`, with `input` on stdin.
+    pub async fn run_with_input(&self, args: &[&str], input: &[u8]) -> Output {
+        let mut child = self
+            .command(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("run callux");
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(input).await.expect("write stdin");
+        drop(stdin);
+        child.wait_with_output().await.expect("run callux")
+    }
+
+    /// Starts `callux` with `args` and leaves it running, e.g. the daemon.
+    pub fn spawn(&self, args: &[&str]) -> Child {
+        self.command(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("start callux")
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_callux"));
+        command
+            .arg("--config")
+            .arg(&self.config)
+            .arg("--insecure-ok")
+            .args(args)
+            .env("CALLUX_API_BASE", self.server.uri())
+            .env("CALLUX_ACCESS_TOKEN", TOKEN)
+            .env("HOME", self.dir.path())
+            .env("XDG_CACHE_HOME", self.dir.path().join("cache"))
+            .env("XDG_DATA_HOME", self.dir.path().join("data"))
+            .env("XDG_RUNTIME_DIR", self.dir.path().join("run"))
+            .env("XDG_CONFIG_HOME", self.dir.path().join("config"))
+            .env("NO_COLOR", "1")
+            .envs(self.env.iter().cloned());
+        command
+    }
+
+    /// `agenda --format json --no-daemon` plus `args`, parsed.
+    pub async fn agenda_json(&self, args: &[&str]) -> (Output, serde_json::Value) {
+        let mut all = vec!["agenda", "--format", "json", "--no-daemon"];
+        all.extend_from_slice(args);
+        let output = self.run(&all).await;
+        let json = serde_json::from_slice(&output.stdout).unwrap_or_else(|e| {
+            panic!(
+                "stdout is not JSON ({}): {}\nstderr: {}",
+                e,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            )
+        });
+        (output, json)
+    }
+
+    /// How many requests the mock server has answered.
+    pub async fn requests(&self) -> usize {
+        self.server
+            .received_requests()
+            .await
+            .map_or(0, |requests| requests.len())
+    }
+}
+
+/// `tests/fixtures/<name>` with `{{DAY<n>}}` replaced by the date `n` days
+/// from now, so recorded events always fall inside the agenda window.
+pub fn load_fixture(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name);
+    let mut text =
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("read {}: {}", path.display(), e));
+    let today = Utc::now().date_naive();
+    for days in 0..8 {
+        let date = today + Duration::days(days);
+        text = text.replace(
+            &format!("{{{{DAY{}}}}}", days),
+            &date.format("%Y-%m-%d").to_string(),
+        );
+    }
+    text
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use common::Harness;
+use std::time::Instant;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Waits up to 10 seconds for a request to the mock server matching `wanted`.
+async fn wait_for(harness: &Harness, wanted: impl Fn(&wiremock::Request) -> bool) -> bool {
+    let deadline = Instant::now() + std::time::Duration::from_secs(10);
+    while Instant::now() < deadline {
+        let requests = harness.server.received_requests().await.unwrap_or_default();
+        if requests.iter().any(&wanted) {
+            return true;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn stops_tracking_when_the_daemon_stops() {
+    let mut harness = Harness::new(&["primary"]).await;
+    harness.set_env("CALLUX_TRACKING__BACKEND", "activitywatch");
+    let activitywatch = harness.server.uri();
+    harness.set_env("CALLUX_TRACKING__ACTIVITYWATCH_URL", &activitywatch);
+    let now = Utc::now();
+    let events = serde_json::json!({
+        "items": [{
+            "id": "review",
+            "summary": "Design review",
+            "start": {"dateTime": (now - Duration::minutes(10)).to_rfc3339()},
+            "end": {"dateTime": (now + Duration::minutes(50)).to_rfc3339()}
+        }]
+    });
+    Mock::given(method("GET"))
+        .and(path("/calendars/primary/events"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(events))
+        .mount(&harness.server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path_regex("^/api/0/buckets/"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&harness.server)
+        .await;
+    let is_event = |request: &wiremock::Request| {
+        request.method.as_str() == "POST" && request.url.path().ends_with("/events")
+    };
+
+    let daemon = harness.spawn(&["daemon"]);
+    // ActivityWatch is told about the bucket when tracking starts and gets
+    // the interval only when it stops.
+    let started = wait_for(&harness, |request| {
+        request.method.as_str() == "POST" && request.url.path().starts_with("/api/0/buckets/")
+    })
+    .await;
+    assert!(started, "tracking never started");
+    let requests = harness.server.received_requests().await.unwrap_or_default();
+    assert!(!requests.iter().any(is_event));
+
+    let pid = daemon.id().expect("daemon is running").to_string();
+    let killed = tokio::process::Command::new("kill")
+        .args(["-TERM", &pid])
+        .status()
+        .await
+        .expect("run kill");
+    assert!(killed.success());
+    let output = tokio::time::timeout(
+        std::time::Duration::from_secs(10),
+        daemon.wait_with_output(),
+    )
+    .await
+    .expect("daemon stops")
+    .expect("wait for daemon");
+    assert!(output.status.success(), "{}", common::stderr(&output));
+
+    let requests = harness.server.received_requests().await.unwrap_or_default();
+    let stop = requests
+        .iter()
+        .find(|request| is_event(request))
+        .expect("tracking was stopped");
+    let body: serde_json::Value = serde_json::from_slice(&stop.body).expect("JSON body");
+    assert_eq!(body[0]["data"]["title"], "Design review");
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::Harness;
+
+#[tokio::test]
+async fn reports_expired_sign_in_with_a_hint() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.fail_events("primary", 401, "error_401.json").await;
+
+    let (output, json) = harness.agenda_json(&[]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(json["class"], "calendar-error");
+    assert_eq!(json["error"]["code"], 401);
+    assert_eq!(json["error"]["kind"], "api");
+    assert_eq!(json["error"]["retryable"], false);
+    assert!(
+        json["error"]["hint"]
+            .as_str()
+            .unwrap()
+            .contains("callux auth")
+    );
+    // Sign-in problems aren't retried.
+    assert_eq!(harness.requests().await, 1);
+}
+
+#[tokio::test]
+async fn retries_server_errors() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.fail_events("primary", 500, "error_500.json").await;
+
+    let (output, json) = harness.agenda_json(&[]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(json["error"]["code"], 500);
+    assert_eq!(json["error"]["retryable"], true);
+    // network.retry_attempts is 1 in the test config.
+    assert_eq!(harness.requests().await, 2);
+}
+
+#[tokio::test]
+async fn prints_human_errors_on_stderr() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.fail_events("primary", 401, "error_401.json").await;
+
+    let output = harness.run(&["agenda", "--no-daemon"]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = common::stderr(&output);
+    assert!(stderr.starts_with("Error:"), "{}", stderr);
+    assert!(stderr.contains("Hint: run `callux auth`"), "{}", stderr);
+}
+
+#[tokio::test]
+async fn keeps_other_calendars_when_one_fails() {
+    let harness = Harness::new(&["primary", "team"]).await;
+    harness.serve_events("primary", "events_primary.json").await;
+    harness.fail_events("team", 404, "error_500.json").await;
+
+    let (output, json) = harness.agenda_json(&[]).await;
+    assert!(output.status.success(), "{}", common::stderr(&output));
+    assert_eq!(json["text"], "09:00 Standup");
+    let warnings = json["warnings"].as_array().unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].as_str().unwrap().contains("calendar team"));
+}
+
+#[tokio::test]
+async fn rejects_malformed_responses() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.serve_events("primary", "events_invalid.json").await;
+
+    let (output, json) = harness.agenda_json(&[]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(json["error"]["kind"], "parse");
+}
+
+#[tokio::test]
+async fn validate_reports_broken_files() {
+    let harness = Harness::with_config(&["primary"], "\n[display\n").await;
+
+    let output = harness.run(&["config", "validate"]).await;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Invalid TOML"), "{}", stdout);
+}
+
+#[tokio::test]
+async fn validate_reads_drop_ins() {
+    let harness = Harness::new(&["primary"]).await;
+    harness.drop_in("laptop.toml", "[display\n");
+
+    let output = harness.run(&["config", "validate"]).await;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("laptop.toml"), "{}", stdout);
+}